@@ -6,10 +6,9 @@ use crate::models::object::Item;
 use std::io::{self, Write};
 use std::collections::HashSet;
 
-pub fn create_character_party() -> (Vec<Character>, Vec<Item>) {
+pub fn create_character_party() -> Vec<Character> {
     println!("Crea tu grupo de aventureros:");
     let mut characters = Vec::new();
-    let mut initial_inventory = Vec::new();
     for i in 1..=4 {
         println!("Aventurero {}:", i);
         println!("1. Guerrero");
@@ -33,77 +32,77 @@ pub fn create_character_party() -> (Vec<Character>, Vec<Item>) {
                 let shield_warrior = Item::new_equipment("escudo_guerrero", "una escudo de hierro viejo", true, EquipmentType::Shield);
                 let armor_warrior = Item::new_equipment("armadura_guerrero", "una armadura ligera de escamas", true, EquipmentType::Armor(ArmorType::Light));
                 let mut character = Character::new(Class::Fighter);
-                character.equip(sword_warrior.to_equipment().unwrap());
-                character.equip(shield_warrior.to_equipment().unwrap());
-                character.equip(armor_warrior.to_equipment().unwrap());
-                (character, vec![sword_warrior, shield_warrior, armor_warrior])
+                character.equip(sword_warrior.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                character.equip(shield_warrior.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                character.equip(armor_warrior.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                (character, vec![])
             },
             "2" => {
                 let mace_cleric = Item::new_equipment("maza_clerigo", "una maza", true, EquipmentType::Weapon(WeaponType::Medium));
                 let shield_cleric = Item::new_equipment("escudo_clerigo", "una escudo de madera", true, EquipmentType::Shield);
                 let armor_cleric = Item::new_equipment("armadura_clerigo", "una armadura ligera de malla", true, EquipmentType::Armor(ArmorType::Light));
                 let mut character = Character::new(Class::Cleric);
-                character.equip(mace_cleric.to_equipment().unwrap());
-                character.equip(shield_cleric.to_equipment().unwrap());
-                character.equip(armor_cleric.to_equipment().unwrap());
-                (character, vec![mace_cleric, shield_cleric, armor_cleric])
+                character.equip(mace_cleric.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                character.equip(shield_cleric.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                character.equip(armor_cleric.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                (character, vec![])
             },
             "3" => {
-                let rope = Item::new_equipment("cuerda_inicial", "una cuerda de cáñamo en buen estado", false, EquipmentType::Basic);
+                let rope = Item::new_equipment("cuerda_inicial", "una cuerda de cáñamo en buen estado", false, EquipmentType::Basic).with_weight(3);
                 let picks = Item::new_equipment("ganzuas", "un juego de ganzúas básico", false, EquipmentType::Basic);
                 let armor_rogue = Item::new_equipment("armadura_picaro", "una armadura ligera de cuero", true, EquipmentType::Armor(ArmorType::Light));
                 let dagger_rogue = Item::new_equipment("daga_picaro", "una daga", true, EquipmentType::Weapon(WeaponType::Light));
                 let mut character = Character::new(Class::Rogue);
-                character.equip(armor_rogue.to_equipment().unwrap());
-                character.equip(dagger_rogue.to_equipment().unwrap());
-                (character, vec![rope, picks, armor_rogue, dagger_rogue])
+                character.equip(armor_rogue.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                character.equip(dagger_rogue.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                (character, vec![rope, picks])
             },
             "4" => {
                 let dagger_wizard = Item::new_equipment("daga_inicial", "una daga ligera", true, EquipmentType::Weapon(WeaponType::Light));
-                let spellbook_wizard = Item::new_equipment("libro_de_hechizos", "un libro de hechizos", false, EquipmentType::Basic);
+                let spellbook_wizard = Item::new_equipment("libro_de_hechizos", "un libro de hechizos", false, EquipmentType::Basic).with_weight(2);
                 let writing_implements = Item::new_equipment("elementos_de_escritura", "un conjunto de elementos de escritura", false, EquipmentType::Basic);
                 let mut character = Character::new(Class::Wizard);
-                character.equip(dagger_wizard.to_equipment().unwrap());
-                (character, vec![dagger_wizard, spellbook_wizard, writing_implements])
+                character.equip(dagger_wizard.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                (character, vec![spellbook_wizard, writing_implements])
             },
             "5" => {
                 let hacha_barbarian = Item::new_equipment("hacha_barbaro", "un hacha de guerra", true, EquipmentType::Weapon(WeaponType::Medium));
                 let shield_barbarian = Item::new_equipment("escudo_barbaro", "un escudo de madera reforzado", true, EquipmentType::Shield);
                 let armor_barbarian = Item::new_equipment("armadura_barbaro", "una armadura ligera de pieles", true, EquipmentType::Armor(ArmorType::Light));
                 let mut character = Character::new(Class::Barbarian);
-                character.equip(hacha_barbarian.to_equipment().unwrap());
-                character.equip(shield_barbarian.to_equipment().unwrap());
-                character.equip(armor_barbarian.to_equipment().unwrap());
-                (character, vec![hacha_barbarian, shield_barbarian, armor_barbarian])
+                character.equip(hacha_barbarian.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                character.equip(shield_barbarian.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                character.equip(armor_barbarian.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                (character, vec![])
             },
             "6" => {
                 let espada_elf = Item::new_equipment("espada_elfo", "una espada larga de hierro", true, EquipmentType::Weapon(WeaponType::Medium));
                 let armor_elf = Item::new_equipment("armadura_elfo", "una armadura de cuero", true, EquipmentType::Armor(ArmorType::Light));
                 let bow_elf = Item::new_equipment("arco_elfo", "un arco", true, EquipmentType::Bow);
                 let mut character = Character::new(Class::Elf);
-                character.equip(espada_elf.to_equipment().unwrap());
-                character.equip(armor_elf.to_equipment().unwrap());
-                character.equip(bow_elf.to_equipment().unwrap());
-                (character, vec![espada_elf, armor_elf, bow_elf])
+                character.equip(espada_elf.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                character.equip(armor_elf.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                character.equip(bow_elf.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                (character, vec![])
             },
             "7" => {
                 let hacha_dwarf = Item::new_equipment("hacha_enano", "un hacha de guerra", true, EquipmentType::Weapon(WeaponType::Medium));
                 let shield_dwarf = Item::new_equipment("escudo_enano", "un pequeño escudo de madera reforzado", true, EquipmentType::Shield);
                 let armadura_dwarf = Item::new_equipment("armadura_enano", "una armadura de cuero", true, EquipmentType::Armor(ArmorType::Light));
                 let mut character = Character::new(Class::Dwarf);
-                character.equip(hacha_dwarf.to_equipment().unwrap());
-                character.equip(shield_dwarf.to_equipment().unwrap());
-                character.equip(armadura_dwarf.to_equipment().unwrap());
-                (character, vec![hacha_dwarf, shield_dwarf, armadura_dwarf])
+                character.equip(hacha_dwarf.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                character.equip(shield_dwarf.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                character.equip(armadura_dwarf.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                (character, vec![])
             },
             "8" => {
                 let snacks = Item::new_equipment("snacks", "un monton de snacks", false, EquipmentType::Basic);
                 let sling_halfling = Item::new_equipment("honda_halfling", "una honda", true, EquipmentType::Bow);
                 let daga_halfling = Item::new_equipment("daga_halfling", "una daga ligera", true, EquipmentType::Weapon(WeaponType::Light));
                 let mut character = Character::new(Class::Halfling);
-                character.equip(daga_halfling.to_equipment().unwrap());
-                character.equip(sling_halfling.to_equipment().unwrap());
-                (character, vec![daga_halfling, sling_halfling, snacks])
+                character.equip(daga_halfling.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                character.equip(sling_halfling.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                (character, vec![snacks])
             },
             _ => {
                 println!("Opción no válida, se creará un guerrero por defecto.");
@@ -111,10 +110,10 @@ pub fn create_character_party() -> (Vec<Character>, Vec<Item>) {
                 let shield = Item::new_equipment("escudo_hierro", "una escudo de hierro viejo", true, EquipmentType::Shield);
                 let light_armor = Item::new_equipment("armadura_ligera_escamas", "una armadura ligera de escamas", true, EquipmentType::Armor(ArmorType::Light));
                 let mut character = Character::new(Class::Fighter);
-                character.equip(espada.to_equipment().unwrap());
-                character.equip(shield.to_equipment().unwrap());
-                character.equip(light_armor.to_equipment().unwrap());
-                (character, vec![espada, shield, light_armor])
+                character.equip(espada.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                character.equip(shield.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                character.equip(light_armor.to_equipment().unwrap()).expect("equipo inicial válido para la clase");
+                (character, vec![])
             }
         };
 
@@ -126,19 +125,15 @@ pub fn create_character_party() -> (Vec<Character>, Vec<Item>) {
             std::io::stdin().read_line(&mut name).unwrap();
             let name_trimmed = name.trim().to_string();
             let existing_names: HashSet<String> = characters.iter().map(|c: &Character| c.name.clone()).collect();
-            if name_trimmed.is_empty() {
-                println!("El nombre no puede estar vacío.");
-                continue;
-            }
-            if existing_names.contains(&name_trimmed) {
-                println!("Ya existe un personaje con ese nombre. Elige otro.");
+            if let Err(reason) = Character::validate_name(&name_trimmed, &existing_names) {
+                println!("{}. Elige otro.", reason);
                 continue;
             }
             character.set_name(name_trimmed, &existing_names);
             break;
         }
+        character.pack.extend(items);
         characters.push(character);
-        initial_inventory.extend(items);
     }
-    (characters, initial_inventory)
+    characters
 } 
\ No newline at end of file
@@ -3,14 +3,23 @@ mod models {
     pub mod object;
     pub mod character;
     pub mod enemy;
+    pub mod shop;
+    pub mod inn;
+    pub mod dialogue;
+    pub mod quest;
+    pub mod puzzle;
+    pub mod trap;
+    pub mod mode;
+    pub mod map;
 }
 mod parsexec;
 mod character_creation;
 
 use std::io::{self, Write};
-use crate::models::player::Player;
+use crate::models::player::{Player, RealDiceRoller};
 use crate::models::enemy::{Enemy, get_enemy, get_enemies_by_difficulty, get_enemies_by_level_range, get_enemies_by_location};
 use crate::parsexec::{parse_command, execute_command};
+use crate::parsexec::shortcuts::{Shortcuts, CONFIG_FILE};
 
 struct Game {
     player: Player
@@ -26,12 +35,12 @@ fn main() {
     println!("Bienvenido a Aventura en Woodspring");
     println!("-----------------------------------\n");
 
-    let (characters, initial_inventory) = character_creation::create_character_party();
+    let characters = character_creation::create_character_party();
 
     let mut player = Player::new(characters);
-    player.inventory = initial_inventory;
+    player.shortcuts = Shortcuts::load(CONFIG_FILE);
     let mut game = Game::new(player);
-    println!("{}", game.player.execute_go(Some("pueblo")));
+    println!("{}", game.player.execute_go(Some("pueblo"), &mut RealDiceRoller));
 
     loop {
         print!("> ");
@@ -39,20 +48,23 @@ fn main() {
 
         let mut input = String::new();
         std::io::stdin().read_line(&mut input).unwrap();
-        let input = input.trim().to_lowercase();
+        let input = input.trim().to_string();
 
         if input.is_empty() {
             continue;
         }
-        
-        if input == "salir" {
-            break;
-        }
 
-        let command = parse_command(&input);
+        game.player.command_history.push(input.clone());
+
+        let command = parse_command(&game.player, &input);
         let response = execute_command(&mut game.player, command);
         if !response.is_empty() {
             println!("{}", response);
         }
+
+        if game.player.is_party_defeated() {
+            println!("Fin de la partida.");
+            return;
+        }
     }
 }
@@ -1,5 +1,6 @@
 use std::fmt::{Display, Formatter};
 use crate::models::object::{Item, NPCTag};
+use crate::models::trap::TrapKind;
 use std::collections::HashSet;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -36,6 +37,37 @@ pub enum CharacterTrait {
     Halfling,        // Mediano: +1 a la defensa
 }
 
+impl Display for CharacterTrait {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CharacterTrait::Strong => write!(f, "Fuerte"),
+            CharacterTrait::Agile => write!(f, "Ágil"),
+            CharacterTrait::Tough => write!(f, "Resistente"),
+            CharacterTrait::Precise => write!(f, "Preciso"),
+            CharacterTrait::ShieldMaster => write!(f, "Maestro de escudo"),
+            CharacterTrait::Stealthy => write!(f, "Sigiloso"),
+            CharacterTrait::Perceptive => write!(f, "Perspicaz"),
+            CharacterTrait::Lucky => write!(f, "Afortunado"),
+            CharacterTrait::Spellcaster => write!(f, "Lanzador de conjuros"),
+            CharacterTrait::Healer => write!(f, "Sanador"),
+            CharacterTrait::Thief => write!(f, "Ladrón"),
+            CharacterTrait::Berserker => write!(f, "Berserker"),
+            CharacterTrait::NaturalArmor => write!(f, "Armadura natural"),
+            CharacterTrait::ForestFriend => write!(f, "Amigo del bosque"),
+            CharacterTrait::MountainBorn => write!(f, "Nacido en la montaña"),
+            CharacterTrait::Nimble => write!(f, "Escurridizo"),
+            CharacterTrait::Warrior => write!(f, "Guerrero"),
+            CharacterTrait::Wizard => write!(f, "Mago"),
+            CharacterTrait::Rogue => write!(f, "Pícaro"),
+            CharacterTrait::Cleric => write!(f, "Clérigo"),
+            CharacterTrait::Barbarian => write!(f, "Bárbaro"),
+            CharacterTrait::Dwarf => write!(f, "Enano"),
+            CharacterTrait::Elf => write!(f, "Elfo"),
+            CharacterTrait::Halfling => write!(f, "Mediano"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum WeaponType {
     Light,
@@ -58,12 +90,87 @@ pub enum EquipmentType {
     Bow,
 }
 
+impl EquipmentType {
+    // Dos tipos ocupan la misma ranura aunque difieran en peso (p. ej. arma ligera y pesada)
+    pub fn same_slot(&self, other: &EquipmentType) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Equipment {
-    pub name: String,
+    pub item: Item,                    // Instancia concreta del objeto equipado
     pub equipment_type: EquipmentType,
 }
 
+// Cómo se comporta en combate un compañero reclutado
+#[derive(Debug, Clone, PartialEq)]
+pub enum CombatBehaviour {
+    Aggressive,      // Agresivo: +1 al ataque
+    Cautious,        // Prudente: deja de atacar cuando está malherido
+}
+
+impl Display for CombatBehaviour {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CombatBehaviour::Aggressive => write!(f, "agresivo"),
+            CombatBehaviour::Cautious => write!(f, "prudente"),
+        }
+    }
+}
+
+// Estados que duran unos turnos, como el veneno de una trampa
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatusEffect {
+    Poisoned(u32),   // Pierde 1 PV por turno durante tantos turnos (nunca por debajo de 1)
+}
+
+impl Display for StatusEffect {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            StatusEffect::Poisoned(turns) => write!(f, "envenenado ({} turnos)", turns),
+        }
+    }
+}
+
+// Datos de un NPC que se ha unido al grupo
+#[derive(Debug, Clone)]
+pub struct Companion {
+    pub npc_tag: String,             // NPC del que procede
+    pub loyalty: i32,                // Si llega a 0, abandona el grupo
+    pub behaviour: CombatBehaviour,
+}
+
+pub const MAX_LOYALTY: i32 = 10;
+
+// Letras que puede tener como mucho el nombre de un personaje
+pub const MAX_NAME_LENGTH: usize = 20;
+
+// Acciones sociales con las que el grupo trata con los NPCs
+#[derive(Debug, Clone, PartialEq)]
+pub enum SocialAction {
+    Bribe,           // Sobornar
+    Intimidate,      // Intimidar
+    Persuade,        // Persuadir
+}
+
+impl Display for SocialAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SocialAction::Bribe => write!(f, "sobornar"),
+            SocialAction::Intimidate => write!(f, "intimidar"),
+            SocialAction::Persuade => write!(f, "persuadir"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Proficiency {
+    Proficient,      // Uso sin penalización
+    NonProficient,   // Puede usarlo, pero con penalización
+    Forbidden,       // No puede equiparlo
+}
+
 impl Display for Class {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -92,6 +199,110 @@ pub enum Class {
 }
 
 impl Class {
+    // Interpreta el nombre de una clase tal y como lo escribe el jugador
+    pub fn from_name(name: &str) -> Option<Class> {
+        match name.to_uppercase().as_str() {
+            "GUERRERO" => Some(Class::Fighter),
+            "CLERIGO" | "CLÉRIGO" => Some(Class::Cleric),
+            "PICARO" | "PÍCARO" => Some(Class::Rogue),
+            "MAGO" => Some(Class::Wizard),
+            "BARBARO" | "BÁRBARO" => Some(Class::Barbarian),
+            "ELFO" => Some(Class::Elf),
+            "ENANO" => Some(Class::Dwarf),
+            "MEDIANO" => Some(Class::Halfling),
+            _ => None,
+        }
+    }
+
+    // Tabla de competencias de cada clase con cada tipo de equipo
+    pub fn proficiency(&self, equipment_type: &EquipmentType) -> Proficiency {
+        use Proficiency::*;
+        match (self, equipment_type) {
+            (_, EquipmentType::Basic) => Forbidden,
+
+            (Class::Fighter, _) => Proficient,
+
+            (Class::Cleric, EquipmentType::Bow) => NonProficient,
+            (Class::Cleric, _) => Proficient,
+
+            (Class::Rogue, EquipmentType::Weapon(WeaponType::Light)) => Proficient,
+            (Class::Rogue, EquipmentType::Weapon(WeaponType::Medium)) => NonProficient,
+            (Class::Rogue, EquipmentType::Weapon(WeaponType::Heavy)) => Forbidden,
+            (Class::Rogue, EquipmentType::Armor(ArmorType::Light)) => Proficient,
+            (Class::Rogue, EquipmentType::Armor(ArmorType::Heavy)) => Forbidden,
+            (Class::Rogue, EquipmentType::Shield) => Forbidden,
+            (Class::Rogue, EquipmentType::Bow) => Proficient,
+
+            (Class::Wizard, EquipmentType::Weapon(WeaponType::Light)) => Proficient,
+            (Class::Wizard, _) => Forbidden,
+
+            (Class::Barbarian, EquipmentType::Armor(ArmorType::Heavy)) => NonProficient,
+            (Class::Barbarian, _) => Proficient,
+
+            (Class::Elf, EquipmentType::Weapon(WeaponType::Heavy)) => NonProficient,
+            (Class::Elf, EquipmentType::Armor(ArmorType::Heavy)) => NonProficient,
+            (Class::Elf, _) => Proficient,
+
+            (Class::Dwarf, EquipmentType::Bow) => NonProficient,
+            (Class::Dwarf, _) => Proficient,
+
+            (Class::Halfling, EquipmentType::Weapon(WeaponType::Light)) => Proficient,
+            (Class::Halfling, EquipmentType::Weapon(WeaponType::Medium)) => NonProficient,
+            (Class::Halfling, EquipmentType::Weapon(WeaponType::Heavy)) => Forbidden,
+            (Class::Halfling, EquipmentType::Armor(ArmorType::Light)) => Proficient,
+            (Class::Halfling, EquipmentType::Armor(ArmorType::Heavy)) => Forbidden,
+            (Class::Halfling, EquipmentType::Shield) => Forbidden,
+            (Class::Halfling, EquipmentType::Bow) => Proficient,
+        }
+    }
+
+    // Peso máximo que puede cargar un personaje de esta clase sin penalizaciones
+    pub fn carrying_capacity(&self) -> u32 {
+        match self {
+            Class::Fighter => 24,
+            Class::Cleric => 22,
+            Class::Rogue => 18,
+            Class::Wizard => 14,
+            Class::Barbarian => 30,
+            Class::Elf => 20,
+            Class::Dwarf => 28,
+            Class::Halfling => 12,
+        }
+    }
+
+    // Los clérigos rezan con la armadura puesta; el resto de lanzadores necesita libertad de movimiento
+    pub fn can_cast_in_heavy_armor(&self) -> bool {
+        matches!(self, Class::Cleric)
+    }
+
+    pub fn proficiency_summary(&self) -> String {
+        let equipment = [
+            ("armas ligeras", EquipmentType::Weapon(WeaponType::Light)),
+            ("armas medias", EquipmentType::Weapon(WeaponType::Medium)),
+            ("armas pesadas", EquipmentType::Weapon(WeaponType::Heavy)),
+            ("armadura ligera", EquipmentType::Armor(ArmorType::Light)),
+            ("armadura pesada", EquipmentType::Armor(ArmorType::Heavy)),
+            ("escudo", EquipmentType::Shield),
+            ("arco", EquipmentType::Bow),
+        ];
+
+        let mut proficient = Vec::new();
+        let mut non_proficient = Vec::new();
+        for (name, equipment_type) in equipment.iter() {
+            match self.proficiency(equipment_type) {
+                Proficiency::Proficient => proficient.push(*name),
+                Proficiency::NonProficient => non_proficient.push(*name),
+                Proficiency::Forbidden => (),
+            }
+        }
+
+        let mut summary = format!("Competente con: {}", proficient.join(", "));
+        if !non_proficient.is_empty() {
+            summary.push_str(&format!("; con penalización: {}", non_proficient.join(", ")));
+        }
+        summary
+    }
+
     pub fn get_traits(&self) -> HashSet<CharacterTrait> {
         let mut traits = HashSet::new();
         match self {
@@ -100,31 +311,39 @@ impl Class {
             },
             Class::Cleric => {
                 traits.insert(CharacterTrait::Cleric);
+                traits.insert(CharacterTrait::Spellcaster);
             },
             Class::Rogue => {
                 traits.insert(CharacterTrait::Rogue);
+                traits.insert(CharacterTrait::Stealthy);
+                traits.insert(CharacterTrait::Thief);
             },
             Class::Wizard => {
                 traits.insert(CharacterTrait::Wizard);
+                traits.insert(CharacterTrait::Spellcaster);
             },
             Class::Barbarian => {
                 traits.insert(CharacterTrait::Barbarian);
+                traits.insert(CharacterTrait::Strong);
             },
             Class::Elf => {
                 traits.insert(CharacterTrait::Elf);
+                traits.insert(CharacterTrait::Spellcaster);
+                traits.insert(CharacterTrait::Perceptive);
             },
             Class::Dwarf => {
                 traits.insert(CharacterTrait::Dwarf);
             },
             Class::Halfling => {
                 traits.insert(CharacterTrait::Halfling);
+                traits.insert(CharacterTrait::Stealthy);
             }
         }
         traits
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Character {
     pub name: String,
     pub class: Class,
@@ -135,13 +354,18 @@ pub struct Character {
     pub shield: Option<Equipment>,
     pub armor: Option<Equipment>,
     pub bow: Option<Equipment>,
-    pub traits: HashSet<CharacterTrait>
+    pub traits: HashSet<CharacterTrait>,
+    pub pack: Vec<Item>,           // Objetos que lleva este personaje (sin contar el equipo puesto)
+    pub spell_slots: u32,          // Hechizos que le quedan hasta el próximo descanso
+    pub companion: Option<Companion>, // Presente si es un NPC reclutado
+    pub status_effects: Vec<StatusEffect>, // Estados que le afectan ahora mismo
 }
 
 impl Character {
     pub fn new(class: Class) -> Character {
         let max_hit_points = Self::calculate_hit_points(&class, 1);
         let traits = class.get_traits();
+        let spell_slots = Self::calculate_spell_slots(&class, 1);
         Character {
             name: format!("Aventurero {}", class),
             class: class.clone(),
@@ -152,18 +376,66 @@ impl Character {
             shield: None,
             armor: None,
             bow: None,
-            traits
+            traits,
+            pack: Vec::new(),
+            spell_slots,
+            companion: None,
+            status_effects: Vec::new(),
         }
     }
 
-    pub fn set_name(&mut self, name: String, existing_names: &HashSet<String>) {
+    // Crea el personaje directamente en un nivel superior al primero
+    pub fn at_level(mut self, level: u32) -> Self {
+        self.level = level.max(1);
+        self.max_hit_points = Self::calculate_hit_points(&self.class, self.level);
+        self.hit_points = self.max_hit_points;
+        self.spell_slots = self.max_spell_slots();
+        self
+    }
+
+    pub fn is_companion(&self) -> bool {
+        self.companion.is_some()
+    }
+
+    // Suma o resta lealtad a un compañero y devuelve la que le queda
+    pub fn change_loyalty(&mut self, amount: i32) -> Option<i32> {
+        let companion = self.companion.as_mut()?;
+        companion.loyalty = (companion.loyalty + amount).min(MAX_LOYALTY);
+        Some(companion.loyalty)
+    }
+
+    // Un compañero prudente no se arriesga cuando le queda la mitad de la vida o menos
+    pub fn holds_back(&self) -> bool {
+        self.companion.as_ref().is_some_and(|companion| companion.behaviour == CombatBehaviour::Cautious)
+            && self.hit_points * 2 <= self.max_hit_points
+    }
+
+    pub fn companion_attack_bonus(&self) -> i32 {
+        match self.companion.as_ref().map(|companion| &companion.behaviour) {
+            Some(CombatBehaviour::Aggressive) => 1,
+            _ => 0,
+        }
+    }
+
+    // Un nombre válido no está vacío, no es demasiado largo y no lo lleva ya otro personaje
+    pub fn validate_name(name: &str, existing_names: &HashSet<String>) -> Result<(), String> {
         // Verificar que el nombre no esté vacío
         if name.trim().is_empty() {
-            panic!("El nombre no puede estar vacío");
+            return Err("El nombre no puede estar vacío".to_string());
+        }
+        if name.chars().count() > MAX_NAME_LENGTH {
+            return Err(format!("El nombre no puede tener más de {} letras", MAX_NAME_LENGTH));
         }
         // Verificar que el nombre no esté duplicado
-        if existing_names.contains(&name) {
-            panic!("Ya existe un personaje con el nombre: {}", name);
+        if existing_names.iter().any(|existing| existing.to_lowercase() == name.to_lowercase()) {
+            return Err(format!("Ya existe un personaje con el nombre: {}", name));
+        }
+        Ok(())
+    }
+
+    pub fn set_name(&mut self, name: String, existing_names: &HashSet<String>) {
+        if let Err(reason) = Self::validate_name(&name, existing_names) {
+            panic!("{}", reason);
         }
         self.name = name;
     }
@@ -181,14 +453,197 @@ impl Character {
         }
     }
 
-    pub fn equip(&mut self, equipment: Equipment) -> Option<Equipment> {
-        match &equipment.equipment_type {
+    fn calculate_spell_slots(class: &Class, level: u32) -> u32 {
+        match class {
+            Class::Wizard => level + 2,     // Mago: el que más hechizos prepara
+            Class::Elf | Class::Cleric => level,
+            _ => 0,
+        }
+    }
+
+    pub fn max_spell_slots(&self) -> u32 {
+        Self::calculate_spell_slots(&self.class, self.level)
+    }
+
+    // Un buen descanso devuelve todos los puntos de vida y los hechizos
+    pub fn rest(&mut self) {
+        self.hit_points = self.max_hit_points;
+        self.spell_slots = self.max_spell_slots();
+        self.status_effects.clear();
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.status_effects.iter().any(|effect| matches!(effect, StatusEffect::Poisoned(_)))
+    }
+
+    // Un turno más bajo sus estados; devuelve los PV perdidos
+    pub fn tick_status_effects(&mut self) -> u32 {
+        let mut lost = 0;
+        for effect in &mut self.status_effects {
+            match effect {
+                StatusEffect::Poisoned(turns) => {
+                    if self.hit_points > 1 {
+                        self.hit_points -= 1;
+                        lost += 1;
+                    }
+                    *turns -= 1;
+                },
+            }
+        }
+        self.status_effects.retain(|effect| !matches!(effect, StatusEffect::Poisoned(0)));
+        lost
+    }
+
+    // Bonus para descubrir una trampa: los sigilosos y perceptivos la intuyen, los enanos leen la piedra
+    pub fn trap_sense(&self, kind: &TrapKind) -> i32 {
+        let mut bonus = 0;
+        if self.has_trait(&CharacterTrait::Stealthy) || self.has_trait(&CharacterTrait::Perceptive) {
+            bonus += 1;
+        }
+        if self.has_trait(&CharacterTrait::Dwarf) && matches!(kind, TrapKind::Pit | TrapKind::CollapsingCeiling) {
+            bonus += 1;
+        }
+        bonus
+    }
+
+    // Solo un ladrón con ganzúas puede desarmar una trampa
+    pub fn can_disarm(&self) -> bool {
+        self.has_trait(&CharacterTrait::Thief) && self.carries("ganzuas")
+    }
+
+    pub fn equip(&mut self, equipment: Equipment) -> Result<Option<Equipment>, String> {
+        if equipment.item.stats.magical && !self.has_trait(&CharacterTrait::Spellcaster) {
+            return Err(format!("{} ({}) no puede usar {}: solo un lanzador de conjuros domina su magia.", self.name, self.class, equipment.name()));
+        }
+        if self.proficiency_for(&equipment) == Proficiency::Forbidden {
+            return Err(match &equipment.equipment_type {
+                EquipmentType::Basic => format!("{} no es un objeto equipable.", equipment.name()),
+                _ => format!("{} ({}) no puede equipar {}: su clase no sabe usarlo.", self.name, self.class, equipment.name()),
+            });
+        }
+
+        Ok(match &equipment.equipment_type {
             EquipmentType::Weapon(_) => self.weapon.replace(equipment),
             EquipmentType::Shield => self.shield.replace(equipment),
             EquipmentType::Armor(_) => self.armor.replace(equipment),
             EquipmentType::Bow => self.bow.replace(equipment),
-            _ => None,
+            EquipmentType::Basic => None,
+        })
+    }
+
+    // Los objetos mágicos responden a quien sabe canalizar su poder, sea cual sea su clase
+    fn proficiency_for(&self, equipment: &Equipment) -> Proficiency {
+        if equipment.item.stats.magical && self.has_trait(&CharacterTrait::Spellcaster) {
+            Proficiency::Proficient
+        } else {
+            self.class.proficiency(&equipment.equipment_type)
+        }
+    }
+
+    fn is_proficient_with(&self, equipment: &Option<Equipment>) -> bool {
+        match equipment {
+            Some(equipment) => self.proficiency_for(equipment) == Proficiency::Proficient,
+            None => true,
+        }
+    }
+
+    pub fn can_cast_spells(&self) -> bool {
+        let wears_heavy_armor = self.armor.as_ref()
+            .is_some_and(|armor| armor.equipment_type == EquipmentType::Armor(ArmorType::Heavy));
+        self.has_trait(&CharacterTrait::Spellcaster) && (!wears_heavy_armor || self.class.can_cast_in_heavy_armor())
+    }
+
+    // Modificador de clase y rasgos a la tirada de una acción social
+    pub fn social_bonus(&self, action: &SocialAction) -> i32 {
+        let mut bonus = match (action, &self.class) {
+            (SocialAction::Bribe, Class::Rogue | Class::Halfling) => 1,
+            (SocialAction::Intimidate, Class::Barbarian) => 2,
+            (SocialAction::Intimidate, Class::Fighter | Class::Dwarf) => 1,
+            (SocialAction::Persuade, Class::Cleric) => 2,
+            (SocialAction::Persuade, Class::Elf | Class::Halfling) => 1,
+            _ => 0,
+        };
+        if self.has_trait(&CharacterTrait::Lucky) {
+            bonus += 1;
+        }
+        if *action == SocialAction::Intimidate && self.has_trait(&CharacterTrait::Strong) {
+            bonus += 1;
+        }
+        bonus
+    }
+
+    // Modificador para abrir una cerradura sin llave: +1 por ser ladrón y +1 por llevar ganzúas.
+    // None si no sabe ni tiene con qué intentarlo
+    pub fn lockpick_bonus(&self) -> Option<i32> {
+        let is_thief = self.has_trait(&CharacterTrait::Thief);
+        let has_picks = self.carries("ganzuas");
+        if !is_thief && !has_picks {
+            return None;
+        }
+        Some(is_thief as i32 + has_picks as i32)
+    }
+
+    // Elfos (perceptivos) y enanos distinguen formas en la oscuridad
+    pub fn has_darkvision(&self) -> bool {
+        self.has_trait(&CharacterTrait::Perceptive) || self.has_trait(&CharacterTrait::Dwarf)
+    }
+
+    pub fn carries_light(&self) -> bool {
+        self.pack.iter().any(|item| item.lit)
+    }
+
+    // Modificador para echar abajo una puerta o reventar un cofre
+    pub fn force_bonus(&self) -> i32 {
+        let mut bonus = match self.class {
+            Class::Barbarian => 2,
+            Class::Fighter | Class::Dwarf => 1,
+            _ => 0,
+        };
+        if self.has_trait(&CharacterTrait::Strong) {
+            bonus += 1;
         }
+        bonus
+    }
+
+    pub fn proficiency_rules(&self) -> String {
+        let mut rules = self.class.proficiency_summary();
+        if self.has_trait(&CharacterTrait::Spellcaster) {
+            if self.can_cast_spells() {
+                rules.push_str(". Puede lanzar hechizos");
+            } else {
+                rules.push_str(". No puede lanzar hechizos con armadura pesada");
+            }
+        }
+        rules
+    }
+
+    pub fn equipped_items(&self) -> Vec<&Equipment> {
+        [&self.weapon, &self.shield, &self.armor, &self.bow].into_iter()
+            .flatten()
+            .collect()
+    }
+
+    pub fn equipped_weight(&self) -> u32 {
+        self.equipped_items().iter().map(|equipment| equipment.item.weight).sum()
+    }
+
+    // Peso total que carga el personaje: equipo puesto más su mochila
+    pub fn load(&self) -> u32 {
+        self.equipped_weight() + self.pack.iter().map(|item| item.weight).sum::<u32>()
+    }
+
+    pub fn is_encumbered(&self) -> bool {
+        self.load() > self.class.carrying_capacity()
+    }
+
+    pub fn carries(&self, item_tag: &str) -> bool {
+        self.pack.iter().any(|item| item.base.tag == item_tag) ||
+            self.equipped_items().iter().any(|equipment| equipment.item.base.tag == item_tag)
+    }
+
+    pub fn take_from_pack(&mut self, item_tag: &str) -> Option<Item> {
+        let index = self.pack.iter().position(|item| item.base.tag == item_tag)?;
+        Some(self.pack.remove(index))
     }
 
     pub fn unequip(&mut self, equipment_type: EquipmentType) -> Option<Equipment> {
@@ -201,46 +656,54 @@ impl Character {
         }
     }
 
-    pub fn get_equipment_attack_bonus(&self) -> Option<i32> {
-        if self.weapon.is_none() {
-            return None;
-        }
-        let mut bonus = 0;
-        
+    pub fn get_equipment_attack_bonus(&self, enemy_tags: &[NPCTag]) -> Option<i32> {
         // Bonus por arma
-        if let Some(weapon) = &self.weapon {
-            if weapon.equipment_type == EquipmentType::Weapon(WeaponType::Light) {
-                bonus += -1;
-            } else if weapon.equipment_type == EquipmentType::Weapon(WeaponType::Medium) {
-                bonus += 0;
-            } else if weapon.equipment_type == EquipmentType::Weapon(WeaponType::Heavy) {
-                bonus += 1;
-            }
-        }
+        self.weapon.as_ref().map(|weapon| weapon.item.stats.attack + weapon.item.stats.bonus_against(enemy_tags))
+    }
 
-        Some(bonus)
+    pub fn get_weapon_damage_dice(&self) -> u8 {
+        self.weapon.as_ref().map_or(0, |weapon| weapon.item.stats.damage_dice)
     }
 
     pub fn get_equipment_defense_bonus(&self) -> i32 {
-        let mut bonus = 0;
-
-        // Bonus por armadura
-        if let Some(armor) = &self.armor {
-            if armor.equipment_type == EquipmentType::Armor(ArmorType::Light) {
-                bonus += 1;
-            } else if armor.equipment_type == EquipmentType::Armor(ArmorType::Heavy) {
-                bonus += 2;
-            }
+        // Bonus por armadura y escudo
+        let mut bonus: i32 = [&self.armor, &self.shield].into_iter()
+            .flatten()
+            .map(|equipment| equipment.item.stats.defense)
+            .sum();
+
+        // Penalización por llevar armadura o escudo sin ser competente
+        if !self.is_proficient_with(&self.armor) {
+            bonus -= 1;
         }
-
-        // Bonus por escudo
-        if let Some(shield) = &self.shield {
-            bonus += 1;
+        if !self.is_proficient_with(&self.shield) {
+            bonus -= 1;
         }
 
         bonus
     }
 
+    // Desgasta el objeto de la ranura indicada; si se rompe, lo retira y devuelve un mensaje
+    pub fn wear_equipment(&mut self, equipment_type: EquipmentType) -> Option<String> {
+        let slot = match equipment_type {
+            EquipmentType::Weapon(_) => &mut self.weapon,
+            EquipmentType::Shield => &mut self.shield,
+            EquipmentType::Armor(_) => &mut self.armor,
+            EquipmentType::Bow => &mut self.bow,
+            EquipmentType::Basic => return None,
+        };
+
+        let equipment = slot.as_mut()?;
+        let durability = equipment.item.stats.durability.as_mut()?;
+        *durability = durability.saturating_sub(1);
+        if equipment.item.stats.is_broken() {
+            let broken = slot.take()?;
+            Some(format!("¡{} de {} se ha roto!", broken.name(), self.name))
+        } else {
+            None
+        }
+    }
+
     pub fn get_class_defense_bonus(&self, enemy_tags: &[NPCTag]) -> i32 {
         let mut bonus = 0;
 
@@ -254,17 +717,10 @@ impl Character {
     }
 
     pub fn get_class_attack_bonus(&self, enemies_outnumbered: bool, enemy_tags: &[NPCTag]) -> i32 {
-        let is_two_handed = if let Some(weapon) = &self.weapon {
-            matches!(weapon.equipment_type, EquipmentType::Weapon(WeaponType::Heavy))
-        } else {
-            false
-        };
-
-        let is_using_bow = if let Some(weapon) = &self.weapon {
-            matches!(weapon.equipment_type, EquipmentType::Bow)
-        } else {
-            false
-        };
+        // Sin competencia con el arma equipada se pierde el bonus de clase
+        if !self.is_proficient_with(&self.weapon) {
+            return 0;
+        }
 
         let mut bonus = match &self.class {
             Class::Fighter => self.level as i32,
@@ -276,8 +732,8 @@ impl Character {
             Class::Rogue => if enemies_outnumbered { self.level as i32 } else { 0 },
             Class::Wizard => 0,
             Class::Barbarian => self.level as i32,
-            Class::Elf => if is_two_handed { 0 } else { self.level as i32 },
-            Class::Dwarf => if is_using_bow { 0 } else { self.level as i32 },
+            Class::Elf => self.level as i32,
+            Class::Dwarf => self.level as i32,
             Class::Halfling => 0,
         };
 
@@ -292,6 +748,15 @@ impl Character {
         bonus
     }
 
+    // Ataque (None sin arma) y defensa totales contra enemigos con esta etiqueta, sin contar la tirada
+    pub fn bonuses_against(&self, tag: &NPCTag) -> (Option<i32>, i32) {
+        let tags = [tag.clone()];
+        let attack = self.get_equipment_attack_bonus(&tags)
+            .map(|equipment_bonus| equipment_bonus + self.get_class_attack_bonus(false, &tags));
+        let defense = self.get_equipment_defense_bonus() + self.get_class_defense_bonus(&tags);
+        (attack, defense)
+    }
+
     pub fn take_damage(&mut self, damage: u32) -> u32 {
         let actual_damage = damage.min(self.hit_points);
         self.hit_points = self.hit_points.saturating_sub(damage);
@@ -308,27 +773,19 @@ impl Character {
 }
 
 impl Equipment {
-    pub fn new(name: String, equipment_type: EquipmentType) -> Self {
+    pub fn new(item: Item, equipment_type: EquipmentType) -> Self {
         Self {
-            name,
+            item,
             equipment_type,
         }
     }
 
+    pub fn name(&self) -> &str {
+        &self.item.base.description
+    }
+
     pub fn get_bonus(&self) -> i32 {
-        match &self.equipment_type {
-            EquipmentType::Weapon(weapon_type) => match weapon_type {
-                WeaponType::Light => -1,
-                WeaponType::Medium => 0,
-                WeaponType::Heavy => 1,
-            },
-            EquipmentType::Shield => 1,
-            EquipmentType::Armor(armor_type) => match armor_type {
-                ArmorType::Light => 1,
-                ArmorType::Heavy => 2,
-            },
-            _ => -2,
-        }
+        self.item.stats.attack + self.item.stats.defense
     }
 }
 
@@ -339,17 +796,13 @@ pub fn parse_new_character(input: String, existing_names: &HashSet<String>) -> R
     }
 
     let name = parts[0].to_string();
-    let class_str = parts[1].to_uppercase();
-
-    let class = match class_str.as_str() {
-        "GUERRERO" => Class::Fighter,
-        "MAGO" => Class::Wizard,
-        "PICARO" => Class::Rogue,
-        _ => return Err("Clase no válida. Clases disponibles: GUERRERO, MAGO, PICARO".to_string()),
-    };
+    let class = Class::from_name(parts[1]).ok_or_else(|| {
+        "Clase no válida. Clases disponibles: GUERRERO, CLERIGO, PICARO, MAGO, BARBARO, ELFO, ENANO, MEDIANO".to_string()
+    })?;
+    Character::validate_name(&name, existing_names)?;
 
     let mut character = Character::new(class);
-    character.set_name(name, existing_names);
+    character.name = name;
     Ok(character)
 }
 
@@ -357,6 +810,27 @@ pub fn parse_new_character(input: String, existing_names: &HashSet<String>) -> R
 mod tests {
     use super::*;
 
+    fn equipment(tag: &str, description: &str, equipment_type: EquipmentType) -> Equipment {
+        Item::new_equipment(tag, description, true, equipment_type).to_equipment().unwrap()
+    }
+
+    #[test]
+    fn test_unequip_returns_same_instance() {
+        let mut fighter = Character::new(Class::Fighter);
+        let dagger = Item::new_equipment("daga", "una daga ligera", true, EquipmentType::Weapon(WeaponType::Light));
+        let other_dagger = Item::new_equipment("daga", "una daga ligera", true, EquipmentType::Weapon(WeaponType::Light));
+        assert_ne!(dagger.id, other_dagger.id);
+
+        fighter.equip(dagger.to_equipment().unwrap()).unwrap();
+        let replaced = fighter.equip(other_dagger.to_equipment().unwrap()).unwrap().unwrap();
+        let returned = Item::from_equipment(replaced);
+        assert_eq!(returned.id, dagger.id);
+        assert_eq!(returned.base.tag, "daga");
+
+        let unequipped = fighter.unequip(EquipmentType::Weapon(WeaponType::Light)).unwrap();
+        assert_eq!(Item::from_equipment(unequipped).id, other_dagger.id);
+    }
+
     #[test]
     #[should_panic(expected = "Ya existe un personaje con el nombre: Gandalf")]
     fn test_duplicate_character_name() {
@@ -366,6 +840,73 @@ mod tests {
         character.set_name("Gandalf".to_string(), &existing_names);
     }
 
+    #[test]
+    fn test_wizard_cannot_equip_armor() {
+        let mut wizard = Character::new(Class::Wizard);
+        let armor = equipment("armadura_pesada", "una armadura de placas", EquipmentType::Armor(ArmorType::Heavy));
+        assert!(wizard.equip(armor).is_err());
+        assert!(wizard.armor.is_none());
+    }
+
+    #[test]
+    fn test_non_proficient_weapon_loses_class_bonus() {
+        let mut elf = Character::new(Class::Elf);
+        let axe = equipment("hacha", "un hacha de batalla", EquipmentType::Weapon(WeaponType::Heavy));
+        assert!(elf.equip(axe).is_ok());
+        assert_eq!(elf.get_class_attack_bonus(false, &[]), 0);
+
+        let sword = equipment("espada", "una espada", EquipmentType::Weapon(WeaponType::Medium));
+        assert!(elf.equip(sword).is_ok());
+        assert_eq!(elf.get_class_attack_bonus(false, &[NPCTag::Orc]), 2);
+    }
+
+    #[test]
+    fn test_magical_items_need_a_spellcaster() {
+        let wand = Item::new_equipment("varita", "una varita", true, EquipmentType::Weapon(WeaponType::Light))
+            .with_attack(1)
+            .with_bonus_vs(NPCTag::Undead, 2)
+            .magical();
+        let robe = Item::new_equipment("tunica", "una túnica", true, EquipmentType::Armor(ArmorType::Light))
+            .with_defense(2)
+            .magical();
+
+        let mut fighter = Character::new(Class::Fighter);
+        assert!(fighter.equip(wand.to_equipment().unwrap()).is_err());
+
+        // El mago no sabe llevar armadura, pero sí una túnica mágica
+        let mut wizard = Character::new(Class::Wizard);
+        wizard.equip(wand.to_equipment().unwrap()).unwrap();
+        wizard.equip(robe.to_equipment().unwrap()).unwrap();
+        assert_eq!(wizard.get_equipment_attack_bonus(&[NPCTag::Undead]), Some(3));
+        assert_eq!(wizard.get_equipment_defense_bonus(), 2);
+    }
+
+    #[test]
+    fn test_equipment_breaks_when_worn_out() {
+        let mut fighter = Character::new(Class::Fighter);
+        let sword = Item::new_equipment("espada", "una espada", true, EquipmentType::Weapon(WeaponType::Medium))
+            .with_durability(2);
+        fighter.equip(sword.to_equipment().unwrap()).unwrap();
+        assert!(fighter.wear_equipment(EquipmentType::Weapon(WeaponType::Medium)).is_none());
+        assert!(fighter.wear_equipment(EquipmentType::Weapon(WeaponType::Medium)).is_some());
+        assert!(fighter.weapon.is_none());
+    }
+
+    #[test]
+    fn test_heavy_armor_blocks_spellcasting() {
+        let mut elf = Character::new(Class::Elf);
+        assert!(elf.can_cast_spells());
+        let plate = equipment("armadura_pesada", "una armadura de placas", EquipmentType::Armor(ArmorType::Heavy));
+        elf.equip(plate).unwrap();
+        assert!(!elf.can_cast_spells());
+        assert_eq!(elf.get_equipment_defense_bonus(), 1);
+
+        let mut cleric = Character::new(Class::Cleric);
+        let plate = equipment("armadura_pesada", "una armadura de placas", EquipmentType::Armor(ArmorType::Heavy));
+        cleric.equip(plate).unwrap();
+        assert!(cleric.can_cast_spells());
+    }
+
     #[test]
     #[should_panic(expected = "El nombre no puede estar vacío")]
     fn test_empty_character_name() {
@@ -373,4 +914,14 @@ mod tests {
         let existing_names = HashSet::new();
         character.set_name("".to_string(), &existing_names);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_new_character_accepts_every_class() {
+        let existing_names = HashSet::from(["Gandalf".to_string()]);
+        let cleric = parse_new_character("Tuck clérigo".to_string(), &existing_names).unwrap();
+        assert_eq!(cleric.class, Class::Cleric);
+        assert_eq!(cleric.spell_slots, 1);
+        assert!(parse_new_character("Bilbo MEDIANO".to_string(), &existing_names).is_ok());
+        assert!(parse_new_character("gandalf mago".to_string(), &existing_names).is_err());
+    }
+}
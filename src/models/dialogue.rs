@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use crate::models::object::{Attitude, Faction};
+
+// Condiciones que debe cumplir el grupo para que una respuesta esté disponible
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    HasItem(String),        // Algún personaje lleva el objeto
+    Flag(String),           // La marca está activada
+    NotFlag(String),        // La marca aún no está activada
+    Defeated(String),       // El NPC indicado ha sido derrotado
+    Attitude(Attitude),     // El NPC con el que se habla tiene esta actitud
+    QuestAvailable(String), // La misión no se ha aceptado todavía
+    QuestActive(String),    // La misión está en curso
+    QuestCompleted(String), // La misión ya se ha completado
+    Gold(u32),              // El grupo tiene al menos este oro
+}
+
+// Efectos que se aplican al elegir una respuesta
+#[derive(Debug, Clone, PartialEq)]
+pub enum Effect {
+    GiveItem(String),       // El NPC entrega un objeto del catálogo al grupo
+    SetFlag(String),        // Activa una marca en la partida
+    SetAttitude(Attitude),  // Cambia la actitud del NPC hacia el grupo
+    StartQuest(String),     // El NPC encarga una misión al grupo
+    PayGold(u32),           // El grupo entrega oro al NPC
+    ChangeReputation(Faction, i32), // Mejora o empeora la reputación con un bando
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub text: String,
+    pub next: Option<String>,         // Nodo siguiente; None termina la conversación
+    pub conditions: Vec<Condition>,
+    pub effects: Vec<Effect>,
+}
+
+impl Response {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            next: None,
+            conditions: Vec::new(),
+            effects: Vec::new(),
+        }
+    }
+
+    pub fn leads_to(mut self, node_id: &str) -> Self {
+        self.next = Some(node_id.to_string());
+        self
+    }
+
+    pub fn requires(mut self, condition: Condition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    pub fn with_effect(mut self, effect: Effect) -> Self {
+        self.effects.push(effect);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DialogueNode {
+    pub id: String,
+    pub text: String,                 // Lo que dice el NPC
+    pub responses: Vec<Response>,     // Respuestas numeradas del grupo
+}
+
+impl DialogueNode {
+    pub fn new(id: &str, text: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            text: text.to_string(),
+            responses: Vec::new(),
+        }
+    }
+
+    pub fn with_response(mut self, response: Response) -> Self {
+        self.responses.push(response);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DialogueTree {
+    pub start: String,
+    pub nodes: HashMap<String, DialogueNode>,
+}
+
+impl DialogueTree {
+    pub fn new(start: &str) -> Self {
+        Self {
+            start: start.to_string(),
+            nodes: HashMap::new(),
+        }
+    }
+
+    pub fn with_node(mut self, node: DialogueNode) -> Self {
+        self.nodes.insert(node.id.clone(), node);
+        self
+    }
+
+    pub fn node(&self, id: &str) -> Option<&DialogueNode> {
+        self.nodes.get(id)
+    }
+}
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+use lazy_static::lazy_static;
+
+#[derive(Debug, Clone)]
+pub struct Inn {
+    pub location: String,      // Tag de la ubicación de la posada
+    pub innkeeper: String,     // Tag del NPC que la regenta
+    pub rest_price: u32,       // Precio por cada aventurero que descansa
+    pub hire_price: u32,       // Precio por contratar a un nuevo aventurero
+    pub rumors: Vec<String>,   // Rumores que se escuchan junto a la chimenea
+}
+
+impl Inn {
+    pub fn new(location: &str, innkeeper: &str, rest_price: u32, hire_price: u32) -> Self {
+        Self {
+            location: location.to_string(),
+            innkeeper: innkeeper.to_string(),
+            rest_price,
+            hire_price,
+            rumors: Vec::new(),
+        }
+    }
+
+    pub fn add_rumor(&mut self, rumor: &str) {
+        self.rumors.push(rumor.to_string());
+    }
+
+    // Los rumores van rotando según cuántos se han escuchado ya
+    pub fn rumor(&self, heard: u32) -> Option<&str> {
+        if self.rumors.is_empty() {
+            return None;
+        }
+        Some(&self.rumors[heard as usize % self.rumors.len()])
+    }
+}
+
+lazy_static! {
+    pub static ref INNS: HashMap<String, Inn> = {
+        let mut m = HashMap::new();
+
+        let mut posada = Inn::new("posada", "posadera", 3, 25);
+        posada.add_rumor("Un leñador jura que al fondo de la cueva hay una grieta por la que sopla un aire helado. Nadie se atreve a cruzarla sin una buena luz.");
+        posada.add_rumor("Cuentan que el viejo bibliotecario de las profundidades escondía la llave de su tesoro entre sus propios libros.");
+        posada.add_rumor("Más allá de las ruinas se alza una torre. Allí vive un hechicero vestido de negro que no recibe visitas... al menos no vivas.");
+        posada.add_rumor("La puerta de piedra bajo la cueva no se abre con fuerza, sino con ingenio: dicen que hay que contestar a lo que pregunta.");
+        m.insert("posadera".to_string(), posada);
+
+        m
+    };
+}
+
+// Las posadas se guardan por su posadero, como las tiendas por su comerciante
+pub fn find_inn(location_tag: &str) -> Option<&'static Inn> {
+    INNS.values().find(|inn| inn.location == location_tag)
+}
@@ -0,0 +1,244 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use crate::models::object::Direction;
+
+// Lo que el grupo sabe de una ubicación, tal como se dibuja en el mapa
+#[derive(Debug, Clone)]
+pub struct MapRoom {
+    pub tag: String,
+    pub visited: bool,          // Sin visitar, solo se sabe que está ahí
+    pub current: bool,          // El grupo está aquí
+    pub hostile: bool,          // Hay enemigos a la vista
+    pub exits: Vec<MapExit>,    // Salidas conocidas hacia otras ubicaciones del mapa
+}
+
+#[derive(Debug, Clone)]
+pub struct MapExit {
+    pub direction: Direction,
+    pub to: String,
+    pub locked: bool,           // Pasaje o puerta aún cerrados con llave
+    pub one_way: bool,          // No se puede volver por el mismo camino
+}
+
+// Casilla del mapa: columna hacia el este y fila hacia el norte
+type Cell = (i32, i32);
+
+// Separación entre casillas, donde se dibujan los pasos este-oeste
+const GAP: usize = 3;
+
+// Desplazamiento en la cuadrícula de cada punto cardinal; subir y bajar no caben en el plano
+fn offset(direction: Direction) -> Option<Cell> {
+    match direction {
+        Direction::North => Some((0, 1)),
+        Direction::South => Some((0, -1)),
+        Direction::East => Some((1, 0)),
+        Direction::West => Some((-1, 0)),
+        Direction::Up | Direction::Down => None,
+    }
+}
+
+// Casilla libre más cercana a una dada, recorriendo anillos cada vez más amplios
+fn free_cell_near(taken: &HashSet<Cell>, (x, y): Cell) -> Cell {
+    (1..)
+        .flat_map(|ring: i32| (-ring..=ring).flat_map(move |dx| (-ring..=ring).map(move |dy| (x + dx, y + dy))))
+        .find(|cell| !taken.contains(cell))
+        .unwrap_or((x, y))
+}
+
+fn place(positions: &mut HashMap<String, Cell>, taken: &mut HashSet<Cell>, tag: &str, cell: Cell) {
+    let cell = if taken.contains(&cell) { free_cell_near(taken, cell) } else { cell };
+    taken.insert(cell);
+    positions.insert(tag.to_string(), cell);
+}
+
+// Coloca cada ubicación en una casilla siguiendo los puntos cardinales de sus salidas. Las que
+// solo se alcanzan subiendo o bajando, o cuya casilla ya está ocupada, van a la libre más cercana
+pub fn layout(rooms: &[MapRoom]) -> HashMap<String, Cell> {
+    // Cada paso se conoce desde cualquiera de sus dos extremos
+    let mut links: HashMap<&str, Vec<(Option<Cell>, &str)>> = HashMap::new();
+    for room in rooms {
+        for exit in &room.exits {
+            let step = offset(exit.direction);
+            links.entry(room.tag.as_str()).or_default().push((step, exit.to.as_str()));
+            links.entry(exit.to.as_str()).or_default().push((step.map(|(dx, dy)| (-dx, -dy)), room.tag.as_str()));
+        }
+    }
+
+    let mut positions = HashMap::new();
+    let mut taken = HashSet::new();
+    for room in rooms {
+        if positions.contains_key(&room.tag) {
+            continue;
+        }
+        // Un grupo de ubicaciones sin relación con las ya colocadas empieza en una casilla libre
+        place(&mut positions, &mut taken, &room.tag, (0, 0));
+
+        // Primero se recorren los pasos en el plano, y solo cuando se acaban los que suben o bajan
+        let mut planar = VecDeque::from([room.tag.as_str()]);
+        let mut vertical = VecDeque::new();
+        loop {
+            let tag = match planar.pop_front() {
+                Some(tag) => tag,
+                None => match vertical.pop_front() {
+                    Some((from, to)) => {
+                        if positions.contains_key(to) {
+                            continue;
+                        }
+                        let (x, y) = positions[from];
+                        place(&mut positions, &mut taken, to, (x + 1, y - 1));
+                        to
+                    },
+                    None => break,
+                },
+            };
+            let (x, y) = positions[tag];
+            for &(step, next) in links.get(tag).into_iter().flatten() {
+                if positions.contains_key(next) {
+                    continue;
+                }
+                match step {
+                    Some((dx, dy)) => {
+                        place(&mut positions, &mut taken, next, (x + dx, y + dy));
+                        planar.push_back(next);
+                    },
+                    None => vertical.push_back((tag, next)),
+                }
+            }
+        }
+    }
+    positions
+}
+
+// Paso entre dos casillas vecinas, si lo hay: "cerrado" cuenta si lo está en cualquier sentido
+fn link(rooms: &[MapRoom], a: &str, b: &str, direction: Direction, back: Direction) -> Option<bool> {
+    let locked = rooms.iter()
+        .flat_map(|room| room.exits.iter().map(move |exit| (room.tag.as_str(), exit)))
+        .filter(|(from, exit)| (*from == a && exit.to == b && exit.direction == direction) ||
+            (*from == b && exit.to == a && exit.direction == back))
+        .map(|(_, exit)| exit.locked)
+        .reduce(|a, b| a || b)?;
+    Some(locked)
+}
+
+fn label(room: &MapRoom) -> String {
+    let name = format!("{}{}{}", if room.current { "@" } else { "" }, room.tag, if room.hostile { "!" } else { "" });
+    if room.visited {
+        format!("[{}]", name)
+    } else {
+        format!("({})", name)
+    }
+}
+
+// Dibuja el mapa en una cuadrícula, con el norte arriba. Los pasos que suben o bajan, o que no
+// unen casillas vecinas, se enumeran debajo
+pub fn render_ascii(rooms: &[MapRoom]) -> String {
+    let positions = layout(rooms);
+    if positions.is_empty() {
+        return String::new();
+    }
+    let cells: HashMap<Cell, &MapRoom> = rooms.iter()
+        .filter_map(|room| positions.get(&room.tag).map(|cell| (*cell, room)))
+        .collect();
+    let width = rooms.iter().map(|room| label(room).chars().count()).max().unwrap_or(0);
+    let min_x = positions.values().map(|(x, _)| *x).min().unwrap_or(0);
+    let max_x = positions.values().map(|(x, _)| *x).max().unwrap_or(0);
+    let min_y = positions.values().map(|(_, y)| *y).min().unwrap_or(0);
+    let max_y = positions.values().map(|(_, y)| *y).max().unwrap_or(0);
+
+    let mut lines = Vec::new();
+    for y in (min_y..=max_y).rev() {
+        let mut row = String::new();
+        let mut below = String::new();
+        for x in min_x..=max_x {
+            let here = cells.get(&(x, y));
+            row.push_str(&format!("{:^width$}", here.map(|room| label(room)).unwrap_or_default(), width = width));
+            let east = here.zip(cells.get(&(x + 1, y)))
+                .and_then(|(a, b)| link(rooms, &a.tag, &b.tag, Direction::East, Direction::West));
+            row.push_str(match east {
+                Some(true) => "-X-",
+                Some(false) => "---",
+                None => "   ",
+            });
+            let south = here.zip(cells.get(&(x, y - 1)))
+                .and_then(|(a, b)| link(rooms, &a.tag, &b.tag, Direction::South, Direction::North));
+            let mark = match south {
+                Some(true) => "X",
+                Some(false) => "|",
+                None => "",
+            };
+            below.push_str(&format!("{:^width$}{}", mark, " ".repeat(GAP), width = width));
+        }
+        lines.push(row.trim_end().to_string());
+        if y > min_y {
+            lines.push(below.trim_end().to_string());
+        }
+    }
+
+    // Pasos que el dibujo no muestra, una vez cada uno aunque se conozca desde los dos lados
+    let mut listed = HashSet::new();
+    let others: Vec<String> = rooms.iter()
+        .flat_map(|room| room.exits.iter().map(move |exit| (room, exit)))
+        .filter(|(room, exit)| match (offset(exit.direction), positions.get(&room.tag), positions.get(&exit.to)) {
+            (Some((dx, dy)), Some((x, y)), Some(to)) => (x + dx, y + dy) != *to,
+            _ => true,
+        })
+        .filter(|(room, exit)| {
+            let mut pair = [room.tag.as_str(), exit.to.as_str()];
+            pair.sort();
+            exit.one_way || listed.insert(pair)
+        })
+        .map(|(room, exit)| format!("- {} -> {} ({}){}{}", room.tag, exit.to, exit.direction,
+            if exit.one_way { ", sin vuelta" } else { "" },
+            if exit.locked { ", cerrado" } else { "" }))
+        .collect();
+    if !others.is_empty() {
+        lines.push(String::new());
+        lines.push("Otros pasos:".to_string());
+        lines.extend(others);
+    }
+    lines.push(String::new());
+    lines.push("@ estáis aquí   ! enemigos   (...) sin explorar   X cerrado".to_string());
+    lines.join("\n")
+}
+
+// Grafo en formato DOT de Graphviz: los pasos de un solo sentido son flechas y los cerrados,
+// líneas discontinuas
+pub fn render_dot(rooms: &[MapRoom]) -> String {
+    let mut dot = String::from("digraph mapa {\n    node [shape=box];\n");
+    for room in rooms {
+        let mut attributes = vec![format!("label=\"{}\"", room.tag)];
+        if room.current {
+            attributes.push("style=filled, fillcolor=lightblue".to_string());
+        } else if !room.visited {
+            attributes.push("style=dashed".to_string());
+        }
+        if room.hostile {
+            attributes.push("color=red".to_string());
+        }
+        dot.push_str(&format!("    \"{}\" [{}];\n", room.tag, attributes.join(", ")));
+    }
+
+    // Un paso de doble sentido aparece en las dos ubicaciones, pero se dibuja una sola vez
+    let mut drawn = HashSet::new();
+    for room in rooms {
+        for exit in &room.exits {
+            let mut pair = [room.tag.as_str(), exit.to.as_str()];
+            pair.sort();
+            if !exit.one_way && !drawn.insert(pair) {
+                continue;
+            }
+            let mut attributes = Vec::new();
+            if !exit.one_way {
+                attributes.push("dir=none".to_string());
+            }
+            if matches!(exit.direction, Direction::Up | Direction::Down) {
+                attributes.push(format!("label=\"{}\"", exit.direction));
+            }
+            if exit.locked {
+                attributes.push("style=dashed".to_string());
+            }
+            dot.push_str(&format!("    \"{}\" -> \"{}\" [{}];\n", room.tag, exit.to, attributes.join(", ")));
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
@@ -2,5 +2,13 @@ pub mod player;
 pub mod object;
 pub mod character;
 pub mod enemy;
+pub mod shop;
+pub mod inn;
+pub mod dialogue;
+pub mod quest;
+pub mod puzzle;
+pub mod trap;
+pub mod mode;
+pub mod map;
 
 pub use character::EquipmentType;
@@ -0,0 +1,45 @@
+// En qué está el grupo ahora mismo. Cada orden avanza un solo paso dentro del modo en curso,
+// así que un combate o una conversación se pueden guardar, deshacer o probar a medias
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameMode {
+    Exploration,
+    Combat(Combat),
+    Dialogue { npc: String, node: String },    // NPC y nodo de la conversación en curso
+    Puzzle(String),                             // Enigma que se está intentando resolver
+    LevelUp,                                    // A la espera de elegir quién sube de nivel
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Combat {
+    pub enemies: Vec<String>,   // Tags de los NPCs hostiles contra los que se lucha
+    pub remaining: u8,          // Enemigos que siguen en pie
+    pub defeated: u8,           // Enemigos derrotados desde que empezó el combate
+    pub round: u8,              // Rondas ya jugadas
+}
+
+impl Combat {
+    pub fn new(enemies: Vec<String>, remaining: u8) -> Self {
+        Self { enemies, remaining, defeated: 0, round: 0 }
+    }
+}
+
+// Opciones del menú de combate, en el orden en que se muestran
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CombatAction {
+    Fight,
+    Flee,
+    UseItem,
+    Status,
+}
+
+impl CombatAction {
+    pub fn from_choice(choice: u32) -> Option<CombatAction> {
+        match choice {
+            1 => Some(CombatAction::Fight),
+            2 => Some(CombatAction::Flee),
+            3 => Some(CombatAction::UseItem),
+            4 => Some(CombatAction::Status),
+            _ => None,
+        }
+    }
+}
@@ -1,6 +1,10 @@
 use lazy_static::lazy_static;
 use std::collections::HashMap;
-use crate::models::character::{Equipment, EquipmentType, WeaponType, ArmorType};
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicU32, Ordering};
+use crate::models::character::{Equipment, EquipmentType, WeaponType, ArmorType, Class, CombatBehaviour, CharacterTrait};
+use crate::models::trap::{Trap, TrapKind};
+use crate::models::dialogue::{Condition, DialogueNode, DialogueTree, Effect, Response};
 
 #[derive(Debug, Clone)]
 pub struct GameObject {
@@ -17,14 +21,104 @@ pub enum Attitude {
     Friendly,
 }
 
+impl Attitude {
+    // Desplaza la actitud hacia amistosa (pasos positivos) u hostil (negativos)
+    pub fn shifted(&self, steps: i32) -> Attitude {
+        let level = match self {
+            Attitude::Hostile => 0,
+            Attitude::Neutral => 1,
+            Attitude::Friendly => 2,
+        };
+        match (level + steps).clamp(0, 2) {
+            0 => Attitude::Hostile,
+            1 => Attitude::Neutral,
+            _ => Attitude::Friendly,
+        }
+    }
+}
+
+// Bandos a los que pertenecen los NPCs; la reputación del grupo se lleva por bando
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Faction {
+    Village,
+    Goblins,
+    Orcs,
+}
+
+impl Display for Faction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Faction::Village => write!(f, "Woodspring"),
+            Faction::Goblins => write!(f, "los goblins"),
+            Faction::Orcs => write!(f, "los orcos"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RoomContent {
     pub items: Vec<Item>,      // Items en la sala
     pub hidden_items: Vec<Item>, // Items ocultos en la sala
     pub npcs: Vec<String>,     // Tags de los NPCs en la sala
+    pub chests: Vec<Chest>,    // Cofres de la sala, con su contenido
     pub is_visited: bool,      // Si la sala ha sido visitada
-    pub is_locked: bool,       // Si la sala está bloqueada
-    pub required_key: Option<String>, // Tag del item necesario para desbloquear la sala
+    pub lock: Option<Lock>,    // Cerradura de la puerta de la sala
+    pub trap: Option<Trap>,    // Trampa que salta al entrar
+}
+
+// Cerradura de una puerta, pasaje o cofre: se abre con su llave o con una tirada de 1d6 + bonus
+#[derive(Debug, Clone)]
+pub struct Lock {
+    pub difficulty: i32,       // Resultado mínimo de la tirada para abrirla sin llave
+    pub key: Option<String>,   // Tag de la llave que la abre
+}
+
+impl Lock {
+    pub fn new(difficulty: i32) -> Self {
+        Self {
+            difficulty,
+            key: None,
+        }
+    }
+
+    pub fn with_key(mut self, key_tag: &str) -> Self {
+        self.key = Some(key_tag.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Chest {
+    pub base: GameObject,
+    pub lock: Option<Lock>,
+    pub trap: Option<Trap>,    // Trampa que salta al abrirlo
+    pub items: Vec<Item>,      // Lo que aparece al abrirlo
+}
+
+impl Chest {
+    pub fn new(tag: &str, description: &str) -> Self {
+        Self {
+            base: GameObject::new(tag, description, true),
+            lock: None,
+            trap: None,
+            items: Vec::new(),
+        }
+    }
+
+    pub fn with_lock(mut self, lock: Lock) -> Self {
+        self.lock = Some(lock);
+        self
+    }
+
+    pub fn with_trap(mut self, trap: Trap) -> Self {
+        self.trap = Some(trap);
+        self
+    }
+
+    pub fn with_item(mut self, item: Item) -> Self {
+        self.items.push(item);
+        self
+    }
 }
 
 impl RoomContent {
@@ -33,9 +127,10 @@ impl RoomContent {
             items: Vec::new(),
             hidden_items: Vec::new(),
             npcs: Vec::new(),
+            chests: Vec::new(),
             is_visited: false,
-            is_locked: false,
-            required_key: None,
+            lock: None,
+            trap: None,
         }
     }
 
@@ -81,14 +176,24 @@ impl RoomContent {
         self.is_visited = true;
     }
 
-    pub fn lock(&mut self, key_tag: Option<&str>) {
-        self.is_locked = true;
-        self.required_key = key_tag.map(|s| s.to_string());
+    pub fn add_chest(&mut self, chest: Chest) {
+        self.chests.push(chest);
+    }
+
+    pub fn find_chest(&self, chest_tag: &str) -> Option<&Chest> {
+        self.chests.iter().find(|chest| chest.base.tag == chest_tag)
+    }
+
+    pub fn lock(&mut self, lock: Lock) {
+        self.lock = Some(lock);
+    }
+
+    pub fn set_trap(&mut self, trap: Trap) {
+        self.trap = Some(trap);
     }
 
     pub fn unlock(&mut self) {
-        self.is_locked = false;
-        self.required_key = None;
+        self.lock = None;
     }
 }
 
@@ -96,15 +201,125 @@ impl RoomContent {
 pub struct Location {
     pub base: GameObject,
     pub connections: Vec<String>, // Tags de los pasajes que conectan con esta ubicación
+    pub exits: Vec<(Direction, String)>, // Hacia dónde queda cada ubicación conectada
     pub content: RoomContent,     // Contenido de la sala
+    pub is_dark: bool,            // Sin luz no se distingue nada
+}
+
+// Contador global para dar a cada instancia de objeto un identificador único
+static NEXT_ITEM_ID: AtomicU32 = AtomicU32::new(1);
+
+fn next_item_id() -> u32 {
+    NEXT_ITEM_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rarity {
+    Common,
+    Uncommon,
+    Rare,
+    Legendary,
+}
+
+impl Display for Rarity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rarity::Common => write!(f, "común"),
+            Rarity::Uncommon => write!(f, "poco común"),
+            Rarity::Rare => write!(f, "raro"),
+            Rarity::Legendary => write!(f, "legendario"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ItemStats {
+    pub attack: i32,                  // Modificador al ataque
+    pub defense: i32,                 // Modificador a la defensa
+    pub damage_dice: u8,              // Dados de daño: cada 6 derrota a un enemigo más del grupo
+    pub bonus_vs: Vec<(NPCTag, i32)>, // Bonus al ataque contra enemigos con ese tag
+    pub magical: bool,                // Solo los lanzadores de conjuros pueden usarlo
+    pub rarity: Rarity,
+    pub durability: Option<u32>,      // Usos restantes antes de romperse (None = irrompible)
+    pub max_durability: Option<u32>,
+}
+
+impl ItemStats {
+    pub fn new() -> Self {
+        Self {
+            attack: 0,
+            defense: 0,
+            damage_dice: 0,
+            bonus_vs: Vec::new(),
+            magical: false,
+            rarity: Rarity::Common,
+            durability: None,
+            max_durability: None,
+        }
+    }
+
+    // Estadísticas por defecto según el tipo de equipo
+    pub fn for_equipment_type(equipment_type: &EquipmentType) -> Self {
+        let mut stats = Self::new();
+        match equipment_type {
+            EquipmentType::Weapon(WeaponType::Light) => stats.attack = -1,
+            EquipmentType::Weapon(WeaponType::Medium) => stats.attack = 0,
+            EquipmentType::Weapon(WeaponType::Heavy) => stats.attack = 1,
+            EquipmentType::Shield => stats.defense = 1,
+            EquipmentType::Armor(ArmorType::Light) => stats.defense = 1,
+            EquipmentType::Armor(ArmorType::Heavy) => stats.defense = 2,
+            EquipmentType::Bow | EquipmentType::Basic => (),
+        }
+        stats
+    }
+
+    pub fn bonus_against(&self, enemy_tags: &[NPCTag]) -> i32 {
+        self.bonus_vs.iter()
+            .filter(|(tag, _)| enemy_tags.contains(tag))
+            .map(|(_, bonus)| bonus)
+            .sum()
+    }
+
+    pub fn is_broken(&self) -> bool {
+        self.durability == Some(0)
+    }
+
+    pub fn summary(&self) -> String {
+        let mut parts = vec![self.rarity.to_string()];
+        if self.magical {
+            parts.push("mágico".to_string());
+        }
+        if self.attack != 0 {
+            parts.push(format!("{:+} ataque", self.attack));
+        }
+        if self.defense != 0 {
+            parts.push(format!("{:+} defensa", self.defense));
+        }
+        if self.damage_dice > 0 {
+            parts.push(format!("{}d6 de daño", self.damage_dice));
+        }
+        for (tag, bonus) in &self.bonus_vs {
+            parts.push(format!("{:+} contra {}", bonus, tag));
+        }
+        if let (Some(durability), Some(max_durability)) = (self.durability, self.max_durability) {
+            parts.push(format!("durabilidad {}/{}", durability, max_durability));
+        }
+        parts.join(", ")
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Item {
-    pub base: GameObject,
+    pub id: u32,                 // Identificador único de esta instancia
+    pub base: GameObject,        // base.tag es la plantilla de la que procede
     pub is_dropped: bool,        // Si el item fue soltado por el jugador
     pub is_equipment: bool,      // Si el item es equipable
     pub equipment_type: Option<EquipmentType>, // Tipo de equipamiento si es equipable
+    pub stats: ItemStats,        // Modificadores, rareza y durabilidad propios del objeto
+    pub weight: u32,             // Peso en unidades de carga
+    pub value: u32,              // Valor en monedas de oro
+    pub fuel: Option<u32>,       // Turnos de luz que le quedan (None si no alumbra)
+    pub lit: bool,               // Si está encendido
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -135,6 +350,32 @@ pub enum NPCTag {
     Hostile,
 }
 
+impl Display for NPCTag {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NPCTag::Human => write!(f, "humanos"),
+            NPCTag::Elf => write!(f, "elfos"),
+            NPCTag::Dwarf => write!(f, "enanos"),
+            NPCTag::Orc => write!(f, "orcos"),
+            NPCTag::Goblin => write!(f, "goblins"),
+            NPCTag::Halfling => write!(f, "medianos"),
+            NPCTag::Guard => write!(f, "guardias"),
+            NPCTag::Monster => write!(f, "monstruos"),
+            NPCTag::Undead => write!(f, "no muertos"),
+            NPCTag::Beast => write!(f, "bestias"),
+            NPCTag::Merchant => write!(f, "mercaderes"),
+            NPCTag::Bandit => write!(f, "bandidos"),
+            NPCTag::Troll => write!(f, "trolls"),
+            NPCTag::Ogre => write!(f, "ogros"),
+            NPCTag::Giant => write!(f, "gigantes"),
+            NPCTag::Vermin => write!(f, "alimañas"),
+            NPCTag::Friendly => write!(f, "amistosos"),
+            NPCTag::Neutral => write!(f, "neutrales"),
+            NPCTag::Hostile => write!(f, "hostiles"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct NPC {
     pub base: GameObject,
@@ -144,6 +385,40 @@ pub struct NPC {
     pub level: u8,              // Nivel del NPC (1-20)
     pub count: u8,              // Cantidad de NPCs de este tipo
     pub tags: Vec<NPCTag>,      // Tags específicos del NPC
+    pub dialogue_tree: Option<DialogueTree>, // Conversación ramificada con el NPC
+    pub faction: Option<Faction>, // Bando del NPC, si pertenece a alguno
+    pub recruitment: Option<Recruitment>, // Presente si el NPC puede unirse al grupo
+}
+
+// Condiciones con las que un NPC acepta unirse al grupo
+#[derive(Debug, Clone)]
+pub struct Recruitment {
+    pub name: String,              // Nombre con el que aparece en el grupo
+    pub class: Class,
+    pub level: u32,
+    pub price: u32,                // Oro que pide por unirse
+    pub loyalty: i32,              // Lealtad inicial
+    pub behaviour: CombatBehaviour,
+    pub equipment: Vec<String>,    // Tags de los objetos con los que llega equipado
+}
+
+impl Recruitment {
+    pub fn new(name: &str, class: Class, level: u32, price: u32, loyalty: i32, behaviour: CombatBehaviour) -> Self {
+        Self {
+            name: name.to_string(),
+            class,
+            level,
+            price,
+            loyalty,
+            behaviour,
+            equipment: Vec::new(),
+        }
+    }
+
+    pub fn with_equipment(mut self, item_tag: &str) -> Self {
+        self.equipment.push(item_tag.to_string());
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -151,13 +426,84 @@ pub struct Passage {
     pub base: GameObject,
     pub from: String,           // Tag de la ubicación de origen
     pub to: String,            // Tag de la ubicación de destino
-    pub requires_item: bool,    // Si se necesita una llave para usar el pasaje
-    pub item_tag: Option<String>,
-    pub requires_key: bool,    // Si se necesita una llave para usar el pasaje
-    pub key_tag: Option<String>, // Tag del item que funciona como llave
-    pub has_riddle: bool,      // Si el pasaje tiene un acertijo
-    pub riddle: Option<String>, // El acertijo
-    pub riddle_answer: Option<String>, // La respuesta al acertijo
+    pub lock: Option<Lock>,    // Cerradura que hay que abrir para usar el pasaje
+    pub trap: Option<Trap>,    // Trampa que salta al cruzarlo
+    pub puzzles: Vec<String>,  // Enigmas que hay que resolver antes de poder pasar
+    pub one_way: bool,         // Solo se puede recorrer de origen a destino
+    pub conditions: Vec<(Requirement, String)>, // Requisito y motivo que se da si no se cumple
+    pub effects: Vec<PassageEffect>, // Lo que ocurre al cruzarlo
+    pub traverse_text: Option<String>, // Lo que se cuenta al cruzarlo
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    North,
+    South,
+    East,
+    West,
+    Up,
+    Down,
+}
+
+impl Display for Direction {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Direction::North => write!(f, "el norte"),
+            Direction::South => write!(f, "el sur"),
+            Direction::East => write!(f, "el este"),
+            Direction::West => write!(f, "el oeste"),
+            Direction::Up => write!(f, "arriba"),
+            Direction::Down => write!(f, "abajo"),
+        }
+    }
+}
+
+impl Direction {
+    // Reconoce el punto cardinal completo o abreviado: "n", "norte", "o", "oeste"...
+    pub fn from_word(word: &str) -> Option<Direction> {
+        match word {
+            "n" | "norte" => Some(Direction::North),
+            "s" | "sur" => Some(Direction::South),
+            "e" | "este" => Some(Direction::East),
+            "o" | "oeste" => Some(Direction::West),
+            "ar" | "arriba" => Some(Direction::Up),
+            "ab" | "abajo" => Some(Direction::Down),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeOfDay {
+    Day,
+    Night,
+}
+
+impl Display for TimeOfDay {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            TimeOfDay::Day => write!(f, "de día"),
+            TimeOfDay::Night => write!(f, "de noche"),
+        }
+    }
+}
+
+// Requisitos para cruzar un pasaje
+#[derive(Debug, Clone, PartialEq)]
+pub enum Requirement {
+    State(Condition),          // Condición de la partida: objetos, marcas, misiones...
+    TimeOfDay(TimeOfDay),      // Solo se puede cruzar a esta hora
+    MaxPartySize(usize),       // Como mucho tantos personajes en pie, compañeros incluidos
+    Trait(CharacterTrait),     // Algún personaje en pie tiene el rasgo
+    Light,                     // El grupo lleva una luz encendida
+}
+
+// Efectos de cruzar un pasaje
+#[derive(Debug, Clone, PartialEq)]
+pub enum PassageEffect {
+    ConsumeItem(String),       // El objeto se queda atrás (una cuerda atada, una llave rota)
+    Damage(u32),               // Cada personaje pierde estos PV (derrumbes, caídas)
+    SetFlag(String),           // Activa una marca; junto a NotFlag sirve para pasajes de un solo uso
 }
 
 // Implementaciones para crear instancias
@@ -182,14 +528,33 @@ impl Location {
         Self {
             base: GameObject::new(tag, description, visible),
             connections: Vec::new(),
+            exits: Vec::new(),
             content: RoomContent::new(),
+            is_dark: false,
         }
     }
 
+    pub fn dark(mut self) -> Self {
+        self.is_dark = true;
+        self
+    }
+
     pub fn add_connection(&mut self, passage_tag: &str) {
         self.connections.push(passage_tag.to_string());
     }
 
+    // Conexión que además se puede tomar por su dirección: "ir norte", "n"...
+    pub fn add_exit(&mut self, direction: Direction, location_tag: &str) {
+        self.add_connection(location_tag);
+        self.exits.push((direction, location_tag.to_string()));
+    }
+
+    pub fn exit_towards(&self, direction: Direction) -> Option<&str> {
+        self.exits.iter()
+            .find(|(exit, _)| *exit == direction)
+            .map(|(_, tag)| tag.as_str())
+    }
+
     pub fn with_long_description(mut self, long_description: &str) -> Self {
         self.base.long_description = long_description.to_string();
         self
@@ -199,37 +564,124 @@ impl Location {
 impl Item {
     pub fn new(tag: &str, description: &str) -> Self {
         Self {
+            id: next_item_id(),
             base: GameObject::new(tag, description, true),
             is_dropped: false,
             is_equipment: false,
             equipment_type: None,
+            stats: ItemStats::new(),
+            weight: 1,
+            value: 1,
+            fuel: None,
+            lit: false,
+        }
+    }
+
+    // Valor por defecto según el tipo de equipo
+    fn default_value(equipment_type: &EquipmentType) -> u32 {
+        match equipment_type {
+            EquipmentType::Basic => 1,
+            EquipmentType::Weapon(WeaponType::Light) => 4,
+            EquipmentType::Weapon(WeaponType::Medium) => 10,
+            EquipmentType::Weapon(WeaponType::Heavy) => 15,
+            EquipmentType::Shield => 6,
+            EquipmentType::Armor(ArmorType::Light) => 12,
+            EquipmentType::Armor(ArmorType::Heavy) => 40,
+            EquipmentType::Bow => 8,
+        }
+    }
+
+    // Peso por defecto según el tipo de equipo
+    fn default_weight(equipment_type: &EquipmentType) -> u32 {
+        match equipment_type {
+            EquipmentType::Basic => 1,
+            EquipmentType::Weapon(WeaponType::Light) => 1,
+            EquipmentType::Weapon(WeaponType::Medium) => 3,
+            EquipmentType::Weapon(WeaponType::Heavy) => 5,
+            EquipmentType::Shield => 4,
+            EquipmentType::Armor(ArmorType::Light) => 5,
+            EquipmentType::Armor(ArmorType::Heavy) => 12,
+            EquipmentType::Bow => 2,
         }
     }
 
     pub fn new_equipment(tag: &str, description: &str, is_equipment: bool, equipment_type: EquipmentType) -> Self {
         Self {
+            id: next_item_id(),
             base: GameObject::new(tag, description, true),
             is_dropped: false,
             is_equipment,
+            stats: ItemStats::for_equipment_type(&equipment_type),
+            weight: Self::default_weight(&equipment_type),
+            value: Self::default_value(&equipment_type),
             equipment_type: Some(equipment_type),
+            fuel: None,
+            lit: false,
         }
     }
 
+    pub fn with_weight(mut self, weight: u32) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    pub fn with_value(mut self, value: u32) -> Self {
+        self.value = value;
+        self
+    }
+
+    // Fuente de luz con combustible para tantos turnos
+    pub fn with_fuel(mut self, turns: u32) -> Self {
+        self.fuel = Some(turns);
+        self
+    }
+
+    pub fn with_attack(mut self, attack: i32) -> Self {
+        self.stats.attack = attack;
+        self
+    }
+
+    pub fn with_defense(mut self, defense: i32) -> Self {
+        self.stats.defense = defense;
+        self
+    }
+
+    pub fn with_damage_dice(mut self, dice: u8) -> Self {
+        self.stats.damage_dice = dice;
+        self
+    }
+
+    pub fn with_bonus_vs(mut self, tag: NPCTag, bonus: i32) -> Self {
+        self.stats.bonus_vs.push((tag, bonus));
+        self
+    }
+
+    pub fn magical(mut self) -> Self {
+        self.stats.magical = true;
+        self
+    }
+
+    pub fn with_rarity(mut self, rarity: Rarity) -> Self {
+        self.stats.rarity = rarity;
+        self
+    }
+
+    pub fn with_durability(mut self, durability: u32) -> Self {
+        self.stats.durability = Some(durability);
+        self.stats.max_durability = Some(durability);
+        self
+    }
+
     pub fn to_equipment(&self) -> Option<Equipment> {
         if self.is_equipment {
-            Some(Equipment::new(self.base.description.clone(), self.equipment_type.clone()?))
+            Some(Equipment::new(self.clone(), self.equipment_type.clone()?))
         } else {
             None
         }
     }
 
     pub fn from_equipment(equipment: Equipment) -> Self {
-        Self {
-            base: GameObject::new(&equipment.name, &equipment.name, true),
-            is_dropped: false,
-            is_equipment: true,
-            equipment_type: Some(equipment.equipment_type),
-        }
+        equipment.item
     }
 }
 
@@ -243,6 +695,9 @@ impl NPC {
             level: 1,                    // Por defecto, los NPCs son nivel 1
             count: 1,                    // Por defecto, hay 1 NPC
             tags: Vec::new(),            // Inicialmente sin tags específicos
+            dialogue_tree: None,
+            faction: None,
+            recruitment: None,
         }
     }
 
@@ -261,6 +716,21 @@ impl NPC {
         self
     }
 
+    pub fn with_recruitment(mut self, recruitment: Recruitment) -> Self {
+        self.recruitment = Some(recruitment);
+        self
+    }
+
+    pub fn with_faction(mut self, faction: Faction) -> Self {
+        self.faction = Some(faction);
+        self
+    }
+
+    pub fn with_dialogue_tree(mut self, tree: DialogueTree) -> Self {
+        self.dialogue_tree = Some(tree);
+        self
+    }
+
     pub fn add_dialogue(&mut self, text: &str) {
         self.dialogue.push(text.to_string());
     }
@@ -282,32 +752,48 @@ impl Passage {
             base: GameObject::new(tag, description, visible),
             from: from.to_string(),
             to: to.to_string(),
-            requires_item: false,
-            item_tag: None,
-            requires_key: false,
-            key_tag: None,
-            has_riddle: false,
-            riddle: None,
-            riddle_answer: None,
+            lock: None,
+            trap: None,
+            puzzles: Vec::new(),
+            one_way: false,
+            conditions: Vec::new(),
+            effects: Vec::new(),
+            traverse_text: None,
         }
     }
 
-    pub fn with_item(mut self, item_tag: &str) -> Self {
-        self.requires_item = true;
-        self.item_tag = Some(item_tag.to_string());
+    pub fn with_lock(mut self, lock: Lock) -> Self {
+        self.lock = Some(lock);
         self
     }
 
-    pub fn with_key(mut self, key_tag: &str) -> Self {
-        self.requires_key = true;
-        self.key_tag = Some(key_tag.to_string());
+    pub fn with_trap(mut self, trap: Trap) -> Self {
+        self.trap = Some(trap);
+        self
+    }
+
+    pub fn with_puzzle(mut self, puzzle_tag: &str) -> Self {
+        self.puzzles.push(puzzle_tag.to_string());
+        self
+    }
+
+    pub fn one_way(mut self) -> Self {
+        self.one_way = true;
+        self
+    }
+
+    pub fn with_condition(mut self, requirement: Requirement, blocked_reason: &str) -> Self {
+        self.conditions.push((requirement, blocked_reason.to_string()));
+        self
+    }
+
+    pub fn with_effect(mut self, effect: PassageEffect) -> Self {
+        self.effects.push(effect);
         self
     }
 
-    pub fn with_riddle(mut self, riddle: &str, answer: &str) -> Self {
-        self.has_riddle = true;
-        self.riddle = Some(riddle.to_string());
-        self.riddle_answer = Some(answer.to_string());
+    pub fn with_traverse_text(mut self, text: &str) -> Self {
+        self.traverse_text = Some(text.to_string());
         self
     }
 }
@@ -324,6 +810,8 @@ lazy_static! {
             .with_long_description("Un campo abierto, recién labrado. La tierra está fresca y húmeda, lista para la siembra. A poca distancia puedes ver las humildes casas de Woodspring y cerca hay una pequeña cueva que parece ser la entrada a algo más grande.");
         let mut pueblo = Location::new("pueblo", "el pueblo de Woodspring", true)
             .with_long_description("El pueblo de Woodspring, un asentamiento modesto pero acogedor. Unas pocas casas de campesinos se organizan alrededor de una plaza central. Puedes ver un pequeño comercio con su letrero desgastado y una posada con su chimenea humeante. Los campos rodean el pueblo, proporcionando sustento a sus habitantes.");
+        let mut posada = Location::new("posada", "la posada del Roble Viejo", true)
+            .with_long_description("La posada del Roble Viejo, el único lugar de Woodspring donde un viajero puede encontrar cama y comida caliente. Una gran chimenea caldea la sala común, donde los lugareños beben y comparten chismes. Tras la barra, la posadera atiende a sus huéspedes y de vez en cuando algún aventurero sin trabajo ofrece sus servicios.");
         let mut bosque = Location::new("bosque", "un bosque de robles", true)
             .with_long_description("Un bosque de robles a las afueras de Woodspring. Los árboles se elevan majestuosamente, sus ramas entrelazadas creando un dosel que filtra la luz del sol. Unas antiguas ruinas emergen de su umbral, sugiriendo una historia olvidada. El bosque se extiende hasta el horizonte, su tamaño es magnífico y su atmósfera, misteriosa.");
         let mut ruinas = Location::new("ruinas", "unas ruinas antiguas", true)
@@ -331,74 +819,109 @@ lazy_static! {
         
         // Ubicaciones ocultas
         let mut grieta = Location::new("grieta", "una grieta en la pared", false)
-            .with_long_description("Una grieta estrecha en el fondo de la cueva. A través de ella se puede ver un corredor oscuro. El espacio es justo lo suficientemente grande para que una persona pueda pasar, pero requiere cierta agilidad. El aire que viene del otro lado es más frío y huele a humedad y antigüedad. El pasadizo está en completa oscuridad, no puedes entrar sin una fuente de luz.");
+            .with_long_description("Una grieta estrecha en el fondo de la cueva. A través de ella se puede ver un corredor oscuro. El espacio es justo lo suficientemente grande para que una persona pueda pasar, pero requiere cierta agilidad. El aire que viene del otro lado es más frío y huele a humedad y antigüedad. El pasadizo está en completa oscuridad, no puedes entrar sin una fuente de luz.")
+            .dark();
         let mut corredor = Location::new("corredor", "un corredor oscuro", true)
-            .with_long_description("Un corredor estrecho y oscuro que termina en una puerta de piedra con símbolos grabados. Las paredes están húmedas y el suelo es irregular. La única iluminación proviene de la grieta por la que entraste, creando sombras que bailan en las paredes.");
+            .with_long_description("Un corredor estrecho y oscuro que termina en una puerta de piedra con símbolos grabados. Las paredes están húmedas y el suelo es irregular. La única iluminación proviene de la grieta por la que entraste, creando sombras que bailan en las paredes.")
+            .dark();
         let mut puerta = Location::new("puerta", "una puerta de piedra", true)
-            .with_long_description("Una pesada puerta de piedra con símbolos grabados. Los símbolos parecen contar una historia antigua, pero están parcialmente erosionados. La puerta parece estar sellada, pero hay un mecanismo que sugiere que puede ser abierta de alguna manera.");
+            .with_long_description("Una pesada puerta de piedra con símbolos grabados. Los símbolos parecen contar una historia antigua, pero están parcialmente erosionados. La puerta parece estar sellada, pero hay un mecanismo que sugiere que puede ser abierta de alguna manera.")
+            .dark();
         let mut camara = Location::new("camara", "una cámara abandonada", true)
-            .with_long_description("Esta habtación parece haberse usado tiempo atrás como improvisado dormitorio y cocina. Hay una modesta mesa carcomida en una esquina. Una gruesa capa de polvo lo cubre todo.");
+            .with_long_description("Esta habtación parece haberse usado tiempo atrás como improvisado dormitorio y cocina. Hay una modesta mesa carcomida en una esquina. Una gruesa capa de polvo lo cubre todo.")
+            .dark();
         let mut laboratorio = Location::new("laboratorio", "un laboratorio abandonado", true)
-            .with_long_description("Un laboratorio abandonado que parece haber sido usado por alquimistas o magos. Mesas de trabajo cubiertas de polvo y estantes con frascos de cristal se alinean en las paredes. Algunos frascos aún contienen restos de líquidos de colores extraños, y hay notas y diagramas esparcidos por las mesas.");
+            .with_long_description("Un laboratorio abandonado que parece haber sido usado por alquimistas o magos. Mesas de trabajo cubiertas de polvo y estantes con frascos de cristal se alinean en las paredes. Algunos frascos aún contienen restos de líquidos de colores extraños, y hay notas y diagramas esparcidos por las mesas.")
+            .dark();
         let mut biblioteca = Location::new("biblioteca", "una biblioteca antigua", true)
-            .with_long_description("Una biblioteca antigua con estanterías de madera oscura que llegan hasta el techo. Los libros están cubiertos de polvo y algunos parecen estar escritos en idiomas olvidados. El aire huele a papel viejo y madera envejecida.");
+            .with_long_description("Una biblioteca antigua con estanterías de madera oscura que llegan hasta el techo. Los libros están cubiertos de polvo y algunos parecen estar escritos en idiomas olvidados. El aire huele a papel viejo y madera envejecida.")
+            .dark();
         let mut tesoro = Location::new("tesoro", "una sala de tesoros", true)
             .with_long_description("Una sala de tesoros que parece haber pertenecido a alguien muy importante. Cofres antiguos y estatuas de valor decoran esta cámara. El oro y las gemas brillan a la luz de las antorchas, y el aire está cargado de la emoción de descubrir algo extraordinario.");
 
         // Añadir contenido a las ubicaciones
-        cueva.content.add_item(Item::new("antorcha", "una antorcha"));
-        campo.content.add_item(Item::new("cuerda", "una cuerda en buen estado"));
-        campo.content.add_item(Item::new("moneda", "una moneda de plata"));
+        cueva.content.add_item(Item::new("antorcha", "una antorcha").with_value(2).with_fuel(20));
+        campo.content.add_item(Item::new("cuerda", "una cuerda en buen estado").with_weight(3).with_value(2));
+        campo.content.add_item(Item::new("moneda", "una moneda de plata").with_value(5));
+        // Secretos que solo aparecen al buscar
+        biblioteca.content.add_hidden_item(Item::new("llave", "una llave de hierro escondida en el hueco de un libro vaciado").with_value(5));
+        ruinas.content.add_hidden_item(Item::new("bolsa", "una bolsa de cuero con monedas antiguas, escondida bajo una losa del altar").with_value(20));
+        ruinas.content.add_hidden_item(Item::new_equipment("hacha", "un hacha de batalla envuelta en tela encerada", true, EquipmentType::Weapon(WeaponType::Heavy))
+            .with_damage_dice(1)
+            .with_durability(25));
+        // Trampas del templo
+        ruinas.content.set_trap(Trap::new(TrapKind::Pit, 4));
+
+        // Puertas y cofres cerrados
+        biblioteca.content.lock(Lock::new(4));
+        camara.content.add_chest(Chest::new("baul", "un baúl carcomido junto a la mesa")
+            .with_item(Item::new("manta", "una manta de lana apolillada").with_weight(2)));
+        tesoro.content.add_chest(Chest::new("cofre", "un cofre de hierro con remaches de bronce")
+            .with_lock(Lock::new(5))
+            .with_trap(Trap::new(TrapKind::PoisonNeedle, 5))
+            .with_item(Item::new("gemas", "un puñado de gemas talladas").with_value(60))
+            .with_item(Item::new_equipment("cota", "una cota de malla finamente trabajada", true, EquipmentType::Armor(ArmorType::Heavy))
+                .with_durability(40)));
         biblioteca.content.add_item(Item::new("libro", "un libro de nigromancia, escrito en un idioma antiguo y bastante bien conservado. Anotado en un margen, está el nombre de un mago llamado 'Ainiriand'"));
 
         pueblo.content.add_npc("guardia");
+        pueblo.content.add_npc("comerciante");
+        posada.content.add_npc("posadera");
+        posada.content.add_npc("mercenaria");
+        campo.content.add_npc("cazador");
         // Añadir grupos de NPCs a sus ubicaciones
         bosque.content.add_npc("goblins");
         ruinas.content.add_npc("orcos");
         laboratorio.content.add_npc("esqueletos");
         camara.content.add_npc("ratas");
         // Añadir conexiones
-        pueblo.add_connection("campo");
+        pueblo.add_exit(Direction::South, "campo");
+        pueblo.add_exit(Direction::East, "posada");
 
-        campo.add_connection("pueblo");
-        campo.add_connection("cueva");
+        posada.add_exit(Direction::West, "pueblo");
 
-        cueva.add_connection("campo");
-        cueva.add_connection("grieta"); // oculta
-        cueva.add_connection("bosque");
+        campo.add_exit(Direction::North, "pueblo");
+        campo.add_exit(Direction::West, "cueva");
+
+        cueva.add_exit(Direction::East, "campo");
+        cueva.add_exit(Direction::South, "grieta"); // oculta
+        cueva.add_exit(Direction::West, "bosque");
         
-        bosque.add_connection("cueva");
-        bosque.add_connection("ruinas");
+        bosque.add_exit(Direction::East, "cueva");
+        bosque.add_exit(Direction::North, "ruinas");
         
-        ruinas.add_connection("bosque");
+        ruinas.add_exit(Direction::South, "bosque");
+        ruinas.add_exit(Direction::Down, "camara"); // pozo, solo de bajada
 
         // Localizaciones de la Cueva
         // grieta
-        grieta.add_connection("cueva");
-        grieta.add_connection("corredor");
+        grieta.add_exit(Direction::North, "cueva");
+        grieta.add_exit(Direction::South, "corredor");
         // corredor
-        corredor.add_connection("grieta");
-        corredor.add_connection("puerta");
+        corredor.add_exit(Direction::North, "grieta");
+        corredor.add_exit(Direction::West, "puerta");
         // puerta
-        puerta.add_connection("corredor");
-        puerta.add_connection("camara");
+        puerta.add_exit(Direction::East, "corredor");
+        puerta.add_exit(Direction::West, "camara");
         // camara
-        camara.add_connection("puerta");
-        camara.add_connection("laboratorio");
+        camara.add_exit(Direction::East, "puerta");
+        camara.add_exit(Direction::North, "laboratorio");
         // laboratorio
-        laboratorio.add_connection("camara");
-        laboratorio.add_connection("biblioteca");
+        laboratorio.add_exit(Direction::South, "camara");
+        laboratorio.add_exit(Direction::West, "biblioteca");
+        laboratorio.add_exit(Direction::Up, "bosque"); // túnel, solo de salida
         // biblioteca
-        biblioteca.add_connection("laboratorio");
-        biblioteca.add_connection("tesoro");
+        biblioteca.add_exit(Direction::East, "laboratorio");
+        biblioteca.add_exit(Direction::Down, "tesoro");
         // tesoro
-        tesoro.add_connection("biblioteca");
+        tesoro.add_exit(Direction::Up, "biblioteca");
 
         // Añadir ubicaciones al mapa
         // localizaciones principales
         m.insert("cueva".to_string(), cueva);
         m.insert("campo".to_string(), campo);
         m.insert("pueblo".to_string(), pueblo);
+        m.insert("posada".to_string(), posada);
         m.insert("bosque".to_string(), bosque);
         m.insert("ruinas".to_string(), ruinas);
 
@@ -416,14 +939,44 @@ lazy_static! {
 
     pub static ref ITEMS: HashMap<String, Item> = {
         let mut m = HashMap::new();
-        m.insert("espada".to_string(), Item::new_equipment("espada", "una espada de acero", true, EquipmentType::Weapon(WeaponType::Medium)));
-        m.insert("daga".to_string(), Item::new_equipment("daga", "una daga ligera", true, EquipmentType::Weapon(WeaponType::Light)));
-        m.insert("hacha".to_string(), Item::new_equipment("hacha", "un hacha de batalla", true, EquipmentType::Weapon(WeaponType::Heavy)));
-        m.insert("escudo".to_string(), Item::new_equipment("escudo", "un escudo de madera", true, EquipmentType::Shield));
-        m.insert("armadura".to_string(), Item::new_equipment("armadura", "una armadura de cuero", true, EquipmentType::Armor(ArmorType::Light)));
-        m.insert("armadura_pesada".to_string(), Item::new_equipment("armadura_pesada", "una armadura de placas", true, EquipmentType::Armor(ArmorType::Heavy)));
-        m.insert("antorcha".to_string(), Item::new("antorcha", "una antorcha encendida"));
-        m.insert("llave".to_string(), Item::new("llave", "una llave de hierro"));
+        m.insert("espada".to_string(), Item::new_equipment("espada", "una espada de acero", true, EquipmentType::Weapon(WeaponType::Medium))
+            .with_durability(30));
+        m.insert("daga".to_string(), Item::new_equipment("daga", "una daga ligera", true, EquipmentType::Weapon(WeaponType::Light))
+            .with_durability(20));
+        m.insert("hacha".to_string(), Item::new_equipment("hacha", "un hacha de batalla", true, EquipmentType::Weapon(WeaponType::Heavy))
+            .with_damage_dice(1)
+            .with_durability(25));
+        m.insert("escudo".to_string(), Item::new_equipment("escudo", "un escudo de madera", true, EquipmentType::Shield)
+            .with_durability(15));
+        m.insert("armadura".to_string(), Item::new_equipment("armadura", "una armadura de cuero", true, EquipmentType::Armor(ArmorType::Light))
+            .with_durability(25));
+        m.insert("armadura_pesada".to_string(), Item::new_equipment("armadura_pesada", "una armadura de placas", true, EquipmentType::Armor(ArmorType::Heavy))
+            .with_rarity(Rarity::Uncommon)
+            .with_durability(40));
+        m.insert("antorcha".to_string(), Item::new("antorcha", "una antorcha").with_value(2).with_fuel(20));
+        m.insert("farol".to_string(), Item::new("farol", "un farol de aceite con cristales ahumados").with_weight(2).with_value(8).with_fuel(60));
+        m.insert("cuerda".to_string(), Item::new("cuerda", "una cuerda de cáñamo").with_weight(3).with_value(3));
+        m.insert("llave".to_string(), Item::new("llave", "una llave de hierro").with_value(5));
+
+        // Botín del hechicero oscuro
+        m.insert("varita".to_string(), Item::new_equipment("varita", "una varita de ébano con runas plateadas", true, EquipmentType::Weapon(WeaponType::Light))
+            .with_attack(1)
+            .with_damage_dice(2)
+            .with_bonus_vs(NPCTag::Undead, 2)
+            .magical()
+            .with_rarity(Rarity::Rare)
+            .with_durability(12)
+            .with_value(120));
+        m.insert("tunica".to_string(), Item::new_equipment("tunica", "una túnica bordada con símbolos arcanos", true, EquipmentType::Armor(ArmorType::Light))
+            .with_defense(2)
+            .magical()
+            .with_rarity(Rarity::Rare)
+            .with_weight(2)
+            .with_value(100));
+        m.insert("llave_maestra".to_string(), Item::new("llave_maestra", "una llave maestra que se adapta a cualquier cerradura")
+            .magical()
+            .with_rarity(Rarity::Legendary)
+            .with_value(250));
         m
     };
 
@@ -432,25 +985,151 @@ lazy_static! {
         
         // Crear NPCs
         let mut guardia = NPC::new("guardia", "una guardia de aspecto amable, armada con una lanza y armadura ligera de cuero", "pueblo", true)
+            .with_faction(Faction::Village)
             .with_attitude(Attitude::Friendly)
-            .with_level(3);
+            .with_level(3)
+            .with_dialogue_tree(DialogueTree::new("inicio")
+                .with_node(DialogueNode::new("inicio", "Bienvenido a Woodspring. ¿En qué puedo ayudarte?")
+                    .with_response(Response::new("¿Qué se sabe del bosque?").leads_to("bosque"))
+                    .with_response(Response::new("Hemos acabado con los goblins del bosque.")
+                        .requires(Condition::Defeated("goblins".to_string()))
+                        .requires(Condition::NotFlag("recompensa_guardia".to_string()))
+                        .leads_to("recompensa"))
+                    .with_response(Response::new("¿Podemos ayudar en algo más?")
+                        .requires(Condition::Flag("recompensa_guardia".to_string()))
+                        .leads_to("consejo"))
+                    .with_response(Response::new("Nada, solo estamos de paso.")))
+                .with_node(DialogueNode::new("consejo", "Al fondo de la cueva hay una grieta, y dicen que tras ella una puerta de piedra que nadie ha logrado abrir. Si descubrís qué esconde, os recompensaré. Y no entréis nunca sin luz.")
+                    .with_response(Response::new("Lo investigaremos.")
+                        .requires(Condition::QuestAvailable("secreto_cueva".to_string()))
+                        .with_effect(Effect::StartQuest("secreto_cueva".to_string())))
+                    .with_response(Response::new("Lo tendremos en cuenta.")))
+                .with_node(DialogueNode::new("bosque", "Ten cuidado en el bosque, dicen que hay criaturas extrañas. Una banda de goblins asalta a todo el que se acerca a las ruinas.")
+                    .with_response(Response::new("Nos encargaremos de ellos.")
+                        .requires(Condition::QuestAvailable("goblins_bosque".to_string()))
+                        .with_effect(Effect::StartQuest("goblins_bosque".to_string()))
+                        .leads_to("inicio"))
+                    .with_response(Response::new("Gracias por el aviso.")))
+                .with_node(DialogueNode::new("recompensa", "¿De verdad? ¡Woodspring está en deuda con vosotros! Tomad esta cuerda, en la cueva os hará más falta que a mí.")
+                    .with_response(Response::new("Gracias.")
+                        .with_effect(Effect::GiveItem("cuerda".to_string()))
+                        .with_effect(Effect::SetFlag("recompensa_guardia".to_string())))));
         guardia.add_dialogue("Bienvenido a Woodspring. ¿En qué puedo ayudarte?");
         guardia.add_dialogue("Ten cuidado en el bosque, dicen que hay criaturas extrañas.");
         guardia.add_tag(NPCTag::Human);
         guardia.add_tag(NPCTag::Guard);
         m.insert("guardia".to_string(), guardia);
 
+        let mut comerciante = NPC::new("comerciante", "un comerciante rechoncho tras el mostrador de su pequeño comercio", "pueblo", true)
+            .with_faction(Faction::Village)
+            .with_attitude(Attitude::Friendly)
+            .with_level(1)
+            .with_dialogue_tree(DialogueTree::new("inicio")
+                .with_node(DialogueNode::new("inicio", "¡Pasad, pasad! Tengo de todo para el aventurero prudente.")
+                    .with_response(Response::new("¿Qué tienes a la venta?").leads_to("mercancia"))
+                    .with_response(Response::new("Mira esta moneda de plata que hemos encontrado.")
+                        .requires(Condition::HasItem("moneda".to_string()))
+                        .leads_to("moneda"))
+                    .with_response(Response::new("Tus precios son un robo.")
+                        .requires(Condition::Attitude(Attitude::Friendly))
+                        .leads_to("ofendido"))
+                    .with_response(Response::new("Siento lo de antes, tus precios son justos.")
+                        .requires(Condition::Attitude(Attitude::Neutral))
+                        .leads_to("disculpa"))
+                    .with_response(Response::new("¿Buscas algo en particular?")
+                        .requires(Condition::QuestAvailable("libro_nigromante".to_string()))
+                        .leads_to("encargo"))
+                    .with_response(Response::new("Seguimos buscando tu libro.")
+                        .requires(Condition::QuestActive("libro_nigromante".to_string()))
+                        .leads_to("paciencia"))
+                    .with_response(Response::new("¿Qué tal el libro?")
+                        .requires(Condition::QuestCompleted("libro_nigromante".to_string()))
+                        .leads_to("agradecido"))
+                    .with_response(Response::new("Adiós.")))
+                .with_node(DialogueNode::new("encargo", "Colecciono libros raros. Dicen que bajo la cueva hay una biblioteca olvidada; traedme un libro de allí y os pagaré cuarenta monedas.")
+                    .with_response(Response::new("Trato hecho.")
+                        .with_effect(Effect::StartQuest("libro_nigromante".to_string())))
+                    .with_response(Response::new("No nos interesa.").leads_to("inicio")))
+                .with_node(DialogueNode::new("paciencia", "Sin prisa. Recordad: la biblioteca está en algún lugar bajo la cueva.")
+                    .with_response(Response::new("Volveremos.")))
+                .with_node(DialogueNode::new("agradecido", "Una joya. Ya tengo comprador en la capital. Si encontráis más, ya sabéis dónde estoy.")
+                    .with_response(Response::new("Adiós.")))
+                .with_node(DialogueNode::new("mercancia", "Antorchas, cuerdas, armas y alguna armadura. Echad un vistazo a la mercancía cuando queráis.")
+                    .with_response(Response::new("Volvamos a lo de antes.").leads_to("inicio"))
+                    .with_response(Response::new("Lo pensaremos.")))
+                .with_node(DialogueNode::new("moneda", "Lleva el sello del viejo templo de las ruinas. Dicen que los sacerdotes dejaban ofrendas así en su altar.")
+                    .with_response(Response::new("Interesante.").leads_to("inicio")))
+                .with_node(DialogueNode::new("ofendido", "¿Un robo? Muy bien, desde ahora pagaréis lo que vale cada cosa, ni una moneda menos.")
+                    .with_response(Response::new("Como quieras.")
+                        .with_effect(Effect::SetAttitude(Attitude::Neutral))))
+                .with_node(DialogueNode::new("disculpa", "Hmm... Está bien, acepto vuestras disculpas. Volvéis a ser bienvenidos.")
+                    .with_response(Response::new("Gracias.")
+                        .with_effect(Effect::SetAttitude(Attitude::Friendly)))));
+        comerciante.add_dialogue("¡Pasad, pasad! Tengo de todo para el aventurero prudente.");
+        comerciante.add_tag(NPCTag::Human);
+        comerciante.add_tag(NPCTag::Merchant);
+        m.insert("comerciante".to_string(), comerciante);
+
+        let mut posadera = NPC::new("posadera", "una posadera de brazos fuertes y sonrisa franca, secando jarras tras la barra", "posada", true)
+            .with_faction(Faction::Village)
+            .with_attitude(Attitude::Friendly)
+            .with_level(1);
+        posadera.add_dialogue("Bienvenidos al Roble Viejo. Hay camas limpias y siempre algo que contar junto al fuego.");
+        posadera.add_tag(NPCTag::Human);
+        m.insert("posadera".to_string(), posadera);
+
+        // Aventureros dispuestos a unirse al grupo
+        let mut mercenaria = NPC::new("mercenaria", "una mercenaria curtida, con una cicatriz en la mejilla, que ofrece su espada al mejor postor", "posada", true)
+            .with_faction(Faction::Village)
+            .with_attitude(Attitude::Friendly)
+            .with_level(2)
+            .with_recruitment(Recruitment::new("Brenna", Class::Fighter, 2, 20, 5, CombatBehaviour::Aggressive)
+                .with_equipment("espada")
+                .with_equipment("escudo"));
+        mercenaria.add_dialogue("Veinte monedas y mi espada es vuestra. Pagad a tiempo y no os fallaré.");
+        mercenaria.add_tag(NPCTag::Human);
+        m.insert("mercenaria".to_string(), mercenaria);
+
+        let mut cazador = NPC::new("cazador", "un cazador elfo de mirada desconfiada, que vigila el linde del bosque", "campo", true)
+            .with_faction(Faction::Village)
+            .with_attitude(Attitude::Neutral)
+            .with_level(2)
+            .with_recruitment(Recruitment::new("Thalion", Class::Elf, 1, 0, 4, CombatBehaviour::Cautious)
+                .with_equipment("daga"));
+        cazador.add_dialogue("No me uno a desconocidos. Demostradme que se puede confiar en vosotros.");
+        cazador.add_tag(NPCTag::Elf);
+        m.insert("cazador".to_string(), cazador);
+
         // Grupo de goblins en el bosque
         let mut goblins = NPC::new("goblins", "un grupo de goblins", "bosque", true)
+            .with_faction(Faction::Goblins)
             .with_attitude(Attitude::Hostile)
             .with_level(3)
-            .with_count(4);
+            .with_count(4)
+            .with_dialogue_tree(DialogueTree::new("inicio")
+                .with_node(DialogueNode::new("inicio", "¡Alto, humanos! Este bosque es de los goblins. ¿Venís a morir o a pagar?")
+                    .with_response(Response::new("Pagaremos diez monedas por cruzar en paz.")
+                        .requires(Condition::Gold(10))
+                        .leads_to("pago"))
+                    .with_response(Response::new("Los orcos de las ruinas son también nuestros enemigos.")
+                        .requires(Condition::NotFlag("goblins_orcos".to_string()))
+                        .leads_to("orcos"))
+                    .with_response(Response::new("Venimos a acabar con vosotros.")
+                        .with_effect(Effect::ChangeReputation(Faction::Goblins, -1))))
+                .with_node(DialogueNode::new("pago", "Jejeje... Oro brillante. Podéis pasar, pero no olvidaremos vuestras caras.")
+                    .with_response(Response::new("Trato hecho.")
+                        .with_effect(Effect::PayGold(10))
+                        .with_effect(Effect::ChangeReputation(Faction::Goblins, 3))))
+                .with_node(DialogueNode::new("orcos", "Grrr... Los orcos nos echaron de las ruinas. Si acabáis con ellos, los goblins os dejarán en paz.")
+                    .with_response(Response::new("Cuenta con ello.")
+                        .with_effect(Effect::SetFlag("goblins_orcos".to_string())))));
         goblins.add_tag(NPCTag::Goblin);
         goblins.add_tag(NPCTag::Monster);
         m.insert("goblins".to_string(), goblins);
 
         // Grupo de orcos en las ruinas
         let mut orcos = NPC::new("orcos", "un grupo de orcos", "ruinas", true)
+            .with_faction(Faction::Orcs)
             .with_attitude(Attitude::Hostile)
             .with_level(4)
             .with_count(7);
@@ -472,26 +1151,59 @@ lazy_static! {
         esqueletos.add_tag(NPCTag::Undead);
         esqueletos.add_tag(NPCTag::Monster);
         m.insert("esqueletos".to_string(), esqueletos);
+
+        // Merodeadores errantes: no tienen sala propia, acuden al ruido de una cerradura forzada
+        let mut merodeadores = NPC::new("merodeadores", "una banda de merodeadores atraídos por el estruendo", "", true)
+            .with_attitude(Attitude::Hostile)
+            .with_level(2)
+            .with_count(3);
+        merodeadores.add_tag(NPCTag::Monster);
+        m.insert("merodeadores".to_string(), merodeadores);
         
         m
     };
 
     pub static ref PASSAGES: HashMap<String, Passage> = {
-        let mut m = HashMap::new();
-
-        // Pasajes de la cueva
-        m.insert("grieta".to_string(), Passage::new("grieta", "una grieta estrecha en la pared trasera de la cueva. Parece que se puede atravesar si encuentras una antorcha.", "cueva", "grieta", false));
-        m.insert("corredor".to_string(), Passage::new("corredor", "un estrecho corredor, en completa oscuridad, es difícil ver lo que hay delante. Parece que se puede atravesar si encuentras una antorcha.", "grieta", "corredor", false).with_item("antorcha"));
-        m.insert("puerta".to_string(), Passage::new("puerta", "una puerta de piedra con símbolos grabados", "puerta", "camara", true)
-            .with_riddle(
-                "Soy alto cuando soy joven y bajo cuando soy viejo. ¿Qué soy?",
-                "vela"
-            ));
-        m.insert("laboratorio".to_string(), Passage::new("laboratorio", "un pasillo que conduce al laboratorio", "camara", "laboratorio", true));
-        m.insert("biblioteca".to_string(), Passage::new("biblioteca", "un pasillo que conduce a la biblioteca", "laboratorio", "biblioteca", true));
-        m.insert("tesoro".to_string(), Passage::new("tesoro", "una trampilla que conduce a una pequeña sala de tesoros", "biblioteca", "tesoro", true).with_key("llave"));
-
-        m
+        let passages = vec![
+            // Puertas del pueblo
+            Passage::new("porton", "el portón de la empalizada de Woodspring", "pueblo", "campo", true)
+                .with_condition(Requirement::TimeOfDay(TimeOfDay::Day), "El portón de Woodspring está atrancado; la guardia no lo abre hasta el amanecer."),
+
+            // Pasajes de la cueva
+            Passage::new("grieta", "una grieta estrecha en la pared trasera de la cueva. Parece que se puede atravesar si encuentras una antorcha.", "cueva", "grieta", false)
+                .with_condition(Requirement::Light, "Al otro lado de la grieta reina la oscuridad. Sin una luz encendida no veis dónde pisar.")
+                .with_condition(Requirement::MaxPartySize(5), "La grieta es demasiado estrecha para un grupo tan numeroso; alguien tendría que quedarse atrás."),
+            Passage::new("corredor", "un estrecho corredor, en completa oscuridad, es difícil ver lo que hay delante. Parece que se puede atravesar si encuentras una antorcha.", "grieta", "corredor", false)
+                .with_condition(Requirement::Light, "El corredor está en completa oscuridad. Necesitáis una luz encendida para avanzar."),
+            Passage::new("puerta_piedra", "una puerta de piedra con símbolos grabados", "puerta", "camara", true)
+                .with_puzzle("acertijo")
+                .with_puzzle("simbolos"),
+            Passage::new("pasillo_laboratorio", "un pasillo que conduce al laboratorio", "camara", "laboratorio", true)
+                .with_trap(Trap::new(TrapKind::CollapsingCeiling, 3)),
+            Passage::new("pasillo_biblioteca", "un pasillo que conduce a la biblioteca", "laboratorio", "biblioteca", true),
+            Passage::new("trampilla", "una trampilla que conduce a una pequeña sala de tesoros", "biblioteca", "tesoro", true)
+                .with_lock(Lock::new(5).with_key("llave"))
+                .with_trap(Trap::new(TrapKind::Dart, 4)),
+
+            // Atajos de un solo sentido
+            Passage::new("pozo", "un pozo seco bajo el altar, que se hunde hasta las entrañas de la tierra", "ruinas", "camara", true)
+                .one_way()
+                .with_condition(Requirement::State(Condition::HasItem("cuerda".to_string())), "Sin una cuerda no hay forma segura de bajar por el pozo.")
+                .with_effect(PassageEffect::ConsumeItem("cuerda".to_string()))
+                .with_traverse_text("Atáis la cuerda a una columna y descendéis uno a uno. Abajo, la cuerda queda colgando fuera de vuestro alcance."),
+            Passage::new("tunel", "un túnel de mineros apuntalado con vigas podridas", "laboratorio", "bosque", true)
+                .one_way()
+                .with_condition(Requirement::State(Condition::NotFlag("tunel_derrumbado".to_string())), "El túnel se ha derrumbado; ya no se puede pasar.")
+                .with_condition(Requirement::Trait(CharacterTrait::Dwarf), "Las vigas crujen al menor roce; sin alguien que entienda de minas no os atrevéis a cruzar.")
+                .with_effect(PassageEffect::SetFlag("tunel_derrumbado".to_string()))
+                .with_effect(PassageEffect::Damage(1))
+                .with_traverse_text("El enano os guía entre las vigas. Al salir al bosque, el techo del túnel se desploma a vuestra espalda y os alcanza una lluvia de piedras."),
+        ];
+
+        // Cada pasaje se guarda por su propio tag, no por el de su destino
+        passages.into_iter()
+            .map(|passage| (passage.base.tag.clone(), passage))
+            .collect()
     };
 }
 
@@ -504,6 +1216,14 @@ pub fn find_item(tag: &str) -> Option<&'static Item> {
     ITEMS.get(tag)
 }
 
+// Crea una nueva instancia de un objeto del catálogo, con su propio identificador
+pub fn spawn_item(tag: &str) -> Option<Item> {
+    find_item(tag).map(|template| Item {
+        id: next_item_id(),
+        ..template.clone()
+    })
+}
+
 pub fn find_npc(tag: &str) -> Option<&'static NPC> {
     NPCS.get(tag)
 }
@@ -531,13 +1251,17 @@ pub fn get_npcs_in_location(location_tag: &str) -> Vec<&'static NPC> {
         .collect()
 }
 
+// Pasaje que lleva de una ubicación a otra; los de un solo sentido no sirven para volver
+pub fn find_passage_between(from: &str, to: &str) -> Option<&'static Passage> {
+    PASSAGES.values().find(|passage| {
+        (passage.from == from && passage.to == to) ||
+        (!passage.one_way && passage.from == to && passage.to == from)
+    })
+}
+
 // Función para obtener todos los pasajes desde una ubicación
 pub fn get_passages_from_location(location_tag: &str) -> Vec<&'static Passage> {
     PASSAGES.values()
         .filter(|passage| passage.from == location_tag)
         .collect()
 }
-
-pub fn find_item_in_location<'a>(location: &'a Location, item_tag: &str) -> Option<&'a Item> {
-    location.content.items.iter().find(|item| item.base.tag == item_tag)
-}
@@ -1,51 +1,51 @@
-use crate::models::character::Character;
-use crate::models::object::{Location, Item, NPC, Passage, find_location, find_npc, find_item_in_location, find_passage, find_item, PASSAGES, Attitude, NPCTag};
-use crate::models::character::{Equipment, EquipmentType, WeaponType, ArmorType, Class, parse_new_character};
-use std::collections::{HashMap, HashSet};
+use crate::models::character::{Character, CharacterTrait};
+use crate::models::object::{Location, Item, NPC, Passage, Chest, Lock, find_location, find_npc, find_passage, find_passage_between, find_item, spawn_item, get_hidden_items_in_location, PASSAGES, Attitude, Faction, NPCTag, Requirement, PassageEffect, TimeOfDay, Direction};
+use crate::models::shop::{Shop, find_shop};
+use crate::models::inn::{Inn, find_inn};
+use crate::models::dialogue::{Condition, Effect, Response};
+use crate::models::trap::Trap;
+use crate::models::mode::{GameMode, Combat, CombatAction};
+use crate::models::map::{MapRoom, MapExit, render_ascii, render_dot};
+use crate::models::puzzle::{Puzzle, PuzzleKind, find_puzzle, puzzles_at};
+use crate::models::quest::{Quest, Objective, Reward, QUESTS, find_quest};
+use crate::models::character::{Equipment, EquipmentType, WeaponType, ArmorType, Class, SocialAction, Companion, MAX_LOYALTY, parse_new_character};
+use crate::parsexec::shortcuts::Shortcuts;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::cell::Cell;
 use rand::Rng;
 use std::io::{self, Write};
 
 use super::enemy;
 
-pub trait InputReader {
-    fn read_line(&mut self) -> String;
-}
-
-pub struct StdInputReader;
-
-impl InputReader for StdInputReader {
-    fn read_line(&mut self) -> String {
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).unwrap();
-        input
-    }
-}
-
-pub struct TestInputReader {
-    input: String,
+pub trait DiceRoller {
+    fn roll_1d6(&mut self) -> u8;
+    fn roll_1d100(&mut self) -> u8;
 }
 
-impl TestInputReader {
-    pub fn new(input: String) -> Self {
-        Self { input }
-    }
+thread_local! {
+    // Tiradas hechas hasta ahora: si una orden ha tirado dados, no se puede deshacer
+    static DICE_ROLLED: Cell<u32> = const { Cell::new(0) };
 }
 
-impl InputReader for TestInputReader {
-    fn read_line(&mut self) -> String {
-        self.input.clone()
-    }
+fn count_roll() {
+    DICE_ROLLED.with(|rolls| rolls.set(rolls.get() + 1));
 }
 
-pub trait DiceRoller {
-    fn roll_1d6(&mut self) -> u8;
+pub fn dice_rolled() -> u32 {
+    DICE_ROLLED.with(|rolls| rolls.get())
 }
 
 pub struct RealDiceRoller;
 impl DiceRoller for RealDiceRoller {
     fn roll_1d6(&mut self) -> u8 {
+        count_roll();
         rand::thread_rng().gen_range(1..=6)
     }
+
+    fn roll_1d100(&mut self) -> u8 {
+        count_roll();
+        rand::thread_rng().gen_range(1..=100)
+    }
 }
 
 pub struct MockDiceRoller {
@@ -55,24 +55,136 @@ impl DiceRoller for MockDiceRoller {
     fn roll_1d6(&mut self) -> u8 {
         self.value
     }
+
+    fn roll_1d100(&mut self) -> u8 {
+        self.value
+    }
+}
+
+// Pasajes, salas y cofres: lo que puede tener cerradura o trampa
+enum Fixture {
+    Passage(&'static Passage),
+    Room(&'static Location),
+    Chest(&'static Location, &'static Chest),
+}
+
+impl Fixture {
+    // Identificador con el que se recuerda su estado: abierta, trampa descubierta...
+    fn id(&self) -> String {
+        match self {
+            Fixture::Passage(passage) => format!("pasaje:{}", passage.base.tag),
+            Fixture::Room(location) => format!("sala:{}", location.base.tag),
+            Fixture::Chest(location, chest) => chest_id(location, chest),
+        }
+    }
+
+    fn lock(&self) -> Option<&'static Lock> {
+        match self {
+            Fixture::Passage(passage) => passage.lock.as_ref(),
+            Fixture::Room(location) => location.content.lock.as_ref(),
+            Fixture::Chest(_, chest) => chest.lock.as_ref(),
+        }
+    }
+
+    fn trap(&self) -> Option<&'static Trap> {
+        match self {
+            Fixture::Passage(passage) => passage.trap.as_ref(),
+            Fixture::Room(location) => location.content.trap.as_ref(),
+            Fixture::Chest(_, chest) => chest.trap.as_ref(),
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Fixture::Passage(passage) => &passage.base.description,
+            Fixture::Room(location) => &location.base.description,
+            Fixture::Chest(_, chest) => &chest.base.description,
+        }
+    }
+
+    // Un pasaje se nombra por su tag o por la ubicación a la que lleva desde aquí
+    fn matches(&self, target_tag: &str, here: &str) -> bool {
+        match self {
+            Fixture::Passage(passage) => passage.base.tag == target_tag ||
+                (passage.from == here && passage.to == target_tag) ||
+                (passage.to == here && passage.from == target_tag),
+            Fixture::Room(location) => location.base.tag == target_tag,
+            Fixture::Chest(_, chest) => chest.base.tag == target_tag,
+        }
+    }
+}
+
+// Dónde buscar aquello a lo que se refiere una orden: "coger" busca en la sala, "soltar" en las mochilas...
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    Room,       // Objetos que se pueden coger aquí
+    Carried,    // Objetos que lleva el grupo, en la mochila o equipados
+    Npcs,       // NPCs presentes
+    Places,     // Ubicaciones conectadas que se conocen
+    Destinations, // Ubicaciones conectadas que se conocen y las ya visitadas, estén donde estén
+    Fixtures,   // Cofres, pasajes y salas al alcance
+    Party,      // Miembros del grupo
+    Wares,      // Lo que vende el comerciante de aquí
+}
+
+fn chest_id(location: &Location, chest: &Chest) -> String {
+    format!("cofre:{}:{}", location.base.tag, chest.base.tag)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Player {
     characters: Vec<Character>,
     pub current_location: Option<String>,  // Tag de la ubicación actual
-    pub inventory: Vec<Item>,           // Items en el inventario
     pub search_attempts: HashMap<String, u32>,  // Sala -> número de intentos
-    pub discovered_items: HashSet<String>,    // Tags de items descubiertos
-    pub dropped_items: HashMap<String, String>, // tag -> ubicación donde se soltó
-    pub picked_items: HashSet<String>,        // Tags de items recogidos
+    pub discovered_items: HashSet<u32>,       // Ids de items descubiertos
+    pub dropped_items: HashMap<String, Vec<Item>>, // ubicación -> items soltados allí
+    pub picked_items: HashSet<u32>,           // Ids de items de las salas ya recogidos
     pub discovered_locations: HashSet<String>, // Tags de localizaciones descubiertas
+    pub visited_locations: HashSet<String>,    // Tags de localizaciones en las que ha estado el grupo
     pub defeated_npcs: HashSet<String>,        // Tags de NPCs derrotados
-    pub current_combat_enemies: Option<u8>,    // Número de enemigos restantes en el combate actual
+    pub mode: GameMode,                        // Explorando, en combate, conversando o ante un enigma
     pub encounters_won: u32,                   // Número de encuentros superados (excluyendo Vermin)
     pub leveled_up_last_time: Option<String>,  // Nombre del personaje que subió de nivel por última vez
+    pub gold: u32,                             // Monedas de oro del grupo
+    pub turns: u32,                            // Turnos transcurridos desde el inicio de la partida
+    pub shop_stock: HashMap<String, HashMap<String, u32>>, // Comerciante -> (objeto -> cantidad)
+    pub shop_restocked_at: HashMap<String, u32>, // Comerciante -> turno de la última reposición
+    pub rumors_heard: u32,                     // Rumores escuchados en la posada
+    pub flags: HashSet<String>,                // Marcas activadas por conversaciones y sucesos
+    pub npc_attitudes: HashMap<String, Attitude>, // NPC -> actitud actual si ha cambiado
+    pub active_quests: Vec<String>,            // Misiones aceptadas, en el orden en que se aceptaron
+    pub completed_quests: Vec<String>,         // Misiones terminadas
+    pub solved_puzzles: HashSet<String>,       // Enigmas ya resueltos
+    pub puzzle_failures: HashMap<String, u32>, // Enigma -> intentos fallidos, para dar pistas
+    pub reputation: HashMap<Faction, i32>,     // Bando -> reputación del grupo (0 neutral)
+    pub recruited_npcs: HashSet<String>,       // NPCs que viajan con el grupo como compañeros
+    pub unlocked: HashSet<String>,             // Cerraduras de pasajes, salas y cofres ya abiertas
+    pub opened_chests: HashSet<String>,        // Cofres abiertos cuyo contenido está a la vista
+    pub spawned_npcs: HashMap<String, String>, // NPC errante -> ubicación a la que ha acudido
+    pub magic_light: u32,                      // Turnos que le quedan al hechizo de luz
+    pub detected_traps: HashSet<String>,       // Trampas descubiertas (por pasaje, sala o cofre)
+    pub cleared_traps: HashSet<String>,        // Trampas desarmadas o que ya han saltado
+    pub last_referent: Option<String>,         // Lo último que se nombró, para "cógelo", "ábrela"...
+    pub pending_disambiguation: Option<(String, Vec<String>)>, // Orden a medias ("coger {}") y tags entre los que elegir
+    undo_stack: Vec<Player>,                   // Estado antes de cada orden que se puede deshacer, la última al final
+    pub command_history: Vec<String>,          // Órdenes escritas durante la sesión, en orden
+    pub shortcuts: Shortcuts,                  // Alias y macros del jugador, que no se deshacen
 }
 
+const MAX_COMPANIONS: usize = 2;
+
+const STARTING_GOLD: u32 = 30;
+
+// Turnos que dura el hechizo de luz
+const MAGIC_LIGHT_TURNS: u32 = 10;
+
+// Órdenes que se pueden deshacer seguidas
+const MAX_UNDO: usize = 20;
+
+// Cada turno es una hora: el día empieza en el turno 0 de cada 24 y anochece en el 18
+const TURNS_PER_DAY: u32 = 24;
+const NIGHTFALL: u32 = 18;
+
 impl Player {
     pub fn new(characters: Vec<Character>) -> Self {
         // Verificar nombres duplicados
@@ -86,16 +198,249 @@ impl Player {
         Self { 
             characters, 
             current_location: None,
-            inventory: Vec::new(),
             search_attempts: HashMap::new(),
             discovered_items: HashSet::new(),
             dropped_items: HashMap::new(),
             picked_items: HashSet::new(),
             discovered_locations: HashSet::new(),
+            visited_locations: HashSet::new(),
             defeated_npcs: HashSet::new(),
-            current_combat_enemies: None,
+            mode: GameMode::Exploration,
             encounters_won: 0,
             leveled_up_last_time: None,
+            gold: STARTING_GOLD,
+            turns: 0,
+            shop_stock: HashMap::new(),
+            shop_restocked_at: HashMap::new(),
+            rumors_heard: 0,
+            flags: HashSet::new(),
+            npc_attitudes: HashMap::new(),
+            active_quests: Vec::new(),
+            completed_quests: Vec::new(),
+            solved_puzzles: HashSet::new(),
+            puzzle_failures: HashMap::new(),
+            reputation: HashMap::new(),
+            recruited_npcs: HashSet::new(),
+            unlocked: HashSet::new(),
+            opened_chests: HashSet::new(),
+            spawned_npcs: HashMap::new(),
+            magic_light: 0,
+            detected_traps: HashSet::new(),
+            cleared_traps: HashSet::new(),
+            last_referent: None,
+            pending_disambiguation: None,
+            undo_stack: Vec::new(),
+            command_history: Vec::new(),
+            shortcuts: Shortcuts::default(),
+        }
+    }
+
+    // Guarda el estado actual para poder volver a él con `deshacer`
+    pub fn save_undo_point(&mut self) {
+        let undo_stack = std::mem::take(&mut self.undo_stack);
+        let history = std::mem::take(&mut self.command_history);
+        let shortcuts = std::mem::take(&mut self.shortcuts);
+        let snapshot = self.clone();
+        self.undo_stack = undo_stack;
+        self.command_history = history;
+        self.shortcuts = shortcuts;
+
+        self.undo_stack.push(snapshot);
+        if self.undo_stack.len() > MAX_UNDO {
+            self.undo_stack.remove(0);
+        }
+    }
+
+    // Lo que ha dependido de los dados no tiene vuelta atrás, ni nada de lo anterior
+    pub fn forget_undo_points(&mut self) {
+        self.undo_stack.clear();
+    }
+
+    pub fn execute_undo(&mut self) -> bool {
+        if self.in_combat() {
+            println!("No se puede deshacer nada en mitad de un combate.");
+            return false;
+        }
+        let snapshot = match self.undo_stack.pop() {
+            Some(snapshot) => snapshot,
+            None => {
+                println!("No hay nada que deshacer. Lo que ha dependido de los dados no tiene vuelta atrás.");
+                return false;
+            }
+        };
+        let undo_stack = std::mem::take(&mut self.undo_stack);
+        let history = std::mem::take(&mut self.command_history);
+        let shortcuts = std::mem::take(&mut self.shortcuts);
+        *self = snapshot;
+        self.undo_stack = undo_stack;
+        self.command_history = history;
+        self.shortcuts = shortcuts;
+        println!("Deshecho. Volvéis al turno {}.", self.turns);
+        true
+    }
+
+    // Las últimas órdenes de la sesión, numeradas desde la primera
+    pub fn execute_history(&self, count: usize) {
+        if self.command_history.is_empty() {
+            println!("Todavía no has dado ninguna orden.");
+            return;
+        }
+        let start = self.command_history.len().saturating_sub(count);
+        println!("Últimas órdenes:");
+        for (i, command) in self.command_history.iter().enumerate().skip(start) {
+            println!("{:>3}. {}", i + 1, command);
+        }
+    }
+
+    pub fn living_characters(&self) -> usize {
+        self.characters.iter().filter(|character| character.is_alive()).count()
+    }
+
+    pub fn is_party_defeated(&self) -> bool {
+        self.living_characters() == 0
+    }
+
+    pub fn advance_turn(&mut self) {
+        self.turns += 1;
+        self.burn_lights();
+        for character in self.characters.iter_mut().filter(|character| character.is_alive()) {
+            let was_poisoned = character.is_poisoned();
+            let lost = character.tick_status_effects();
+            if lost > 0 {
+                println!("El veneno hace mella en {}: pierde {} PV.", character.name, lost);
+            }
+            if was_poisoned && !character.is_poisoned() {
+                println!("El veneno deja de hacer efecto en {}.", character.name);
+            }
+        }
+    }
+
+    // Las luces encendidas gastan combustible cada turno y se apagan al agotarlo
+    fn burn_lights(&mut self) {
+        for character in &mut self.characters {
+            for item in character.pack.iter_mut().filter(|item| item.lit) {
+                let fuel = item.fuel.unwrap_or(0).saturating_sub(1);
+                item.fuel = Some(fuel);
+                if fuel == 0 {
+                    item.lit = false;
+                    println!("{} de {} se apaga: no le queda combustible.", item.base.description, character.name);
+                }
+            }
+        }
+        if self.magic_light > 0 {
+            self.magic_light -= 1;
+            if self.magic_light == 0 {
+                println!("La luz mágica se desvanece.");
+            }
+        }
+    }
+
+    pub fn has_light(&self) -> bool {
+        self.magic_light > 0 || self.characters.iter().any(|character| character.is_alive() && character.carries_light())
+    }
+
+    fn has_darkvision(&self) -> bool {
+        self.characters.iter().any(|character| character.is_alive() && character.has_darkvision())
+    }
+
+    // Oscuro de verdad: la ubicación no tiene luz propia y el grupo no lleva ninguna
+    pub fn is_dark_here(&self) -> bool {
+        self.current_location.as_ref()
+            .and_then(|tag| find_location(tag))
+            .is_some_and(|location| location.is_dark)
+            && !self.has_light()
+    }
+
+    // encender: prende una fuente de luz que lleve alguien del grupo
+    pub fn execute_light(&mut self, item_tag: &str) -> bool {
+        for character in self.characters.iter_mut().filter(|character| character.is_alive()) {
+            if let Some(item) = character.pack.iter_mut().find(|item| item.base.tag == item_tag) {
+                match item.fuel {
+                    None => println!("{} no sirve para alumbrar.", item.base.description),
+                    Some(_) if item.lit => println!("{} ya está encendido.", item.base.description),
+                    Some(0) => println!("{} no tiene combustible.", item.base.description),
+                    Some(fuel) => {
+                        item.lit = true;
+                        println!("{} enciende {} (le quedan {} turnos de luz).", character.name, item.base.description, fuel);
+                        return true;
+                    }
+                }
+                return false;
+            }
+        }
+        println!("Nadie lleva '{}' en la mochila.", item_tag);
+        false
+    }
+
+    // apagar: guarda el combustible que le quede para más tarde
+    pub fn execute_extinguish(&mut self, item_tag: &str) -> bool {
+        for character in &mut self.characters {
+            if let Some(item) = character.pack.iter_mut().find(|item| item.base.tag == item_tag && item.lit) {
+                item.lit = false;
+                println!("{} apaga {}.", character.name, item.base.description);
+                return true;
+            }
+        }
+        println!("No hay ningún '{}' encendido.", item_tag);
+        false
+    }
+
+    // conjurar luz: un lanzador de conjuros gasta un hechizo para alumbrar al grupo
+    pub fn execute_cast_light(&mut self) -> bool {
+        let caster = self.characters.iter_mut()
+            .find(|character| character.is_alive() && character.can_cast_spells() && character.spell_slots > 0);
+        match caster {
+            Some(caster) => {
+                caster.spell_slots -= 1;
+                self.magic_light = MAGIC_LIGHT_TURNS;
+                println!("{} conjura una luz que flota sobre el grupo durante {} turnos.", caster.name, MAGIC_LIGHT_TURNS);
+                true
+            },
+            None => {
+                println!("Nadie en el grupo puede lanzar ahora un hechizo de luz.");
+                false
+            }
+        }
+    }
+
+    // La actitud parte de la del NPC (o la que haya quedado tras una conversación)
+    // y se desplaza un paso por cada 3 puntos de reputación con su bando
+    pub fn attitude_of(&self, npc: &NPC) -> Attitude {
+        let attitude = self.npc_attitudes.get(&npc.base.tag).cloned().unwrap_or_else(|| npc.attitude.clone());
+        match &npc.faction {
+            Some(faction) => attitude.shifted((self.reputation_with(faction) / 3).clamp(-2, 2)),
+            None => attitude,
+        }
+    }
+
+    pub fn reputation_with(&self, faction: &Faction) -> i32 {
+        self.reputation.get(faction).copied().unwrap_or(0)
+    }
+
+    pub fn change_reputation(&mut self, faction: &Faction, amount: i32) {
+        if amount == 0 {
+            return;
+        }
+        let reputation = self.reputation.entry(faction.clone()).or_insert(0);
+        *reputation += amount;
+        if amount > 0 {
+            println!("Vuestra reputación con {} mejora ({:+}).", faction, reputation);
+        } else {
+            println!("Vuestra reputación con {} empeora ({:+}).", faction, reputation);
+        }
+    }
+
+    // Derrotar a un grupo enfada a su bando y alivia a los que sufrían sus ataques
+    fn record_defeat(&mut self, npc: &NPC) {
+        if let Some(faction) = &npc.faction {
+            self.change_reputation(faction, -3);
+            if matches!(faction, Faction::Goblins | Faction::Orcs) {
+                self.change_reputation(&Faction::Village, 1);
+            }
+            if faction == &Faction::Orcs && self.flags.contains("goblins_orcos") {
+                println!("Los goblins celebran la caída de los orcos.");
+                self.change_reputation(&Faction::Goblins, 3);
+            }
         }
     }
 
@@ -106,8 +451,27 @@ impl Player {
                 // En su lugar, podríamos mantener un registro de salas visitadas en el Player
                 self.search_attempts.insert(tag.clone(), 0);
             }
+            self.visited_locations.insert(tag.clone());
         }
         self.current_location = location_tag;
+        self.notice_hidden_items();
+    }
+
+    // Un personaje perspicaz ve los objetos ocultos sin necesidad de buscarlos
+    fn notice_hidden_items(&mut self) {
+        let observer = match self.characters.iter().find(|c| c.is_alive() && c.has_trait(&CharacterTrait::Perceptive)) {
+            Some(observer) => observer,
+            None => return,
+        };
+        let location = match self.current_location.as_ref().and_then(|tag| find_location(tag)) {
+            Some(location) => location,
+            None => return,
+        };
+        for item in get_hidden_items_in_location(location) {
+            if self.discovered_items.insert(item.id) {
+                println!("{} se fija en algo que otros pasarían por alto: {}.", observer.name, item.base.description);
+            }
+        }
     }
 
     pub fn execute_look(&self) {
@@ -115,32 +479,32 @@ impl Player {
             if let Some(location) = find_location(location_tag) {
                 println!();
                 println!("Estás en {}:", location.base.description);
-                println!("- {}", location.base.long_description);
-
-                // Obtener items visibles en la ubicación actual que no han sido recogidos ni soltados en otro lugar
-                let visible_items: Vec<_> = location.content.items.iter()
-                    .filter(|item| {
-                        let is_visible = item.base.visible || self.discovered_items.contains(&item.base.tag);
-                        let not_picked = !self.picked_items.contains(&item.base.tag);
-                        let not_dropped_elsewhere = !self.dropped_items.contains_key(&item.base.tag);
-                        is_visible && not_picked && not_dropped_elsewhere
-                    })
-                    .collect();
+                // A oscuras solo quien ve en la oscuridad distingue algo, y sin detalles
+                if self.is_dark_here() {
+                    match self.characters.iter().find(|c| c.is_alive() && c.has_darkvision()) {
+                        Some(seer) => println!("- Todo está a oscuras, pero {} distingue las formas en la penumbra.", seer.name),
+                        None => {
+                            println!("- Está oscuro como boca de lobo. Sin luz no distinguís nada a vuestro alrededor.");
+                            println!();
+                            println!("Puedes ir a:");
+                            println!("{}", &self.show_available_locations());
+                            return;
+                        }
+                    }
+                } else {
+                    println!("- {}", location.base.long_description);
+                }
+
+                // Obtener items visibles en la ubicación actual que no han sido recogidos
+                let visible_items = self.available_items(location);
 
                 // Obtener NPCs visibles en la ubicación actual
-                let visible_npcs: Vec<_> = location.content.npcs.iter()
-                    .filter_map(|npc_tag| find_npc(npc_tag))
+                let visible_npcs: Vec<_> = self.npcs_at(location_tag).into_iter()
                     .filter(|npc| npc.base.visible)
                     .collect();
 
-                // Obtener items soltados en la ubicación actual
-                let dropped_items: Vec<_> = self.dropped_items.iter()
-                    .filter(|(_, loc_tag)| loc_tag.as_str() == location_tag.as_str())
-                    .filter_map(|(item_tag, _)| find_item(item_tag))
-                    .collect();
-
                 // Mostrar items y npc en la ubicación solo si hay visibles
-                let has_visible_items = !visible_items.is_empty() || !dropped_items.is_empty();
+                let has_visible_items = !visible_items.is_empty() || !location.content.chests.is_empty();
                 let has_visible_npcs = !visible_npcs.is_empty();
 
                 if has_visible_items || has_visible_npcs {
@@ -149,16 +513,22 @@ impl Player {
                     for item in visible_items {
                         println!("- {}", item.base.description);
                     }
-                    for item in dropped_items {
-                        println!("- {}", item.base.description);
+                    for chest in &location.content.chests {
+                        let state = if self.opened_chests.contains(&chest_id(location, chest)) {
+                            " (abierto)"
+                        } else if self.is_locked(&Fixture::Chest(location, chest)) {
+                            " (cerrado con llave)"
+                        } else {
+                            " (cerrado)"
+                        };
+                        println!("- {}{}", chest.base.description, state);
                     }
                     for npc in visible_npcs {
-                        let attitude = match npc.attitude {
+                        let attitude = match self.attitude_of(npc) {
                             Attitude::Hostile => {
-                                let remaining = if let Some(remaining) = self.current_combat_enemies {
-                                    remaining
-                                } else {
-                                    npc.count
+                                let remaining = match &self.mode {
+                                    GameMode::Combat(combat) if combat.enemies.contains(&npc.base.tag) => combat.remaining,
+                                    _ => npc.count,
                                 };
                                 format!(" (hostil, nivel {}, x{})", npc.level, remaining)
                             },
@@ -169,6 +539,27 @@ impl Player {
                     }
                 }
 
+                // Trampas descubiertas que siguen activas
+                for fixture in self.fixtures_here() {
+                    if matches!(fixture, Fixture::Room(room) if room.base.tag != *location_tag) {
+                        continue;
+                    }
+                    if let Some(trap) = self.armed_trap(&fixture) {
+                        if self.detected_traps.contains(&fixture.id()) {
+                            println!();
+                            println!("¡Cuidado! Hay {} en {}.", trap.kind, fixture.description());
+                        }
+                    }
+                }
+
+                // Enigmas aún sin resolver
+                for puzzle in puzzles_at(location_tag) {
+                    if !self.solved_puzzles.contains(&puzzle.tag) {
+                        println!();
+                        println!("{}", puzzle.prompt);
+                    }
+                }
+
                 // Mostrar las ubicaciones disponibles a las que el jugador puede ir
                 println!();
                 println!("Puedes ir a:");
@@ -177,818 +568,2637 @@ impl Player {
         }
     }
 
-    pub fn execute_go(&mut self, location_tag: Option<&str>) -> String {
+    pub fn execute_go(&mut self, location_tag: Option<&str>, dice: &mut dyn DiceRoller) -> String {
         // Verificar si hay enemigos hostiles en la ubicación actual
         if self.has_hostile_npcs() {
             return "¡No puedes huir! Hay enemigos hostiles aquí.".to_string();
         }
 
-        match location_tag {
-            Some(tag) => {
-                if let Some(current_location) = self.current_location.as_ref() {
-                    if let Some(location) = find_location(current_location) {
-                        // Verificar si la ubicación destino está conectada
-                        if location.connections.contains(&tag.to_string()) {
-                            if let Some(destination) = find_location(tag) {
-                                // Buscar pasaje en el mapa de pasajes
-                                let passage = PASSAGES.values().find(|p| 
-                                    (p.from == *current_location && p.to == tag) || 
-                                    (p.from == tag && p.to == *current_location)
-                                );
-
-                                // Si encontramos un pasaje con requisitos especiales, verificarlos
-                                if let Some(passage) = passage {
-                                    // Verificar si el pasaje requiere un objeto
-                                    if passage.requires_item {
-                                        if let Some(item_tag) = &passage.item_tag {
-                                            if !self.has_item(item_tag) {
-                                                return format!("Necesitas un objeto concreto para pasar por {}.", passage.base.description);
-                                            }
-                                        }
-                                    }
-
-                                    // Verificar si el pasaje requiere una llave
-                                    if passage.requires_key {
-                                        if let Some(key_tag) = &passage.key_tag {
-                                            if !self.has_item(key_tag) {
-                                                return format!("Necesitas una llave para pasar por {}.", passage.base.description);
-                                            }
-                                        }
-                                    }
-
-                                    // Verificar si el pasaje tiene un acertijo
-                                    if passage.has_riddle {
-                                        if let (Some(riddle), Some(answer)) = (&passage.riddle, &passage.riddle_answer) {
-                                            println!("\n{}", riddle);
-                                            println!("Escribe tu respuesta (o 'cancelar' para volver):");
-
-                                            let mut input = String::new();
-                                            match std::io::stdin().read_line(&mut input) {
-                                                Ok(_) => {
-                                                    let input = input.trim().to_lowercase();
-                                                    if input == "cancelar" {
-                                                        return "Has decidido no intentar resolver el acertijo.".to_string();
-                                                    }
-                                                    if input != answer.to_lowercase() {
-                                                        return "Respuesta incorrecta. La puerta permanece cerrada.".to_string();
-                                                    }
-                                                    // Solo si la respuesta es correcta, permitimos el paso
-                                                }
-                                                Err(_) => {
-                                                    return "Error al leer la respuesta. La puerta permanece cerrada.".to_string();
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-
-                                // Si llegamos aquí, el jugador puede pasar
-                                self.set_current_location(Some(tag.to_string()));
-                                self.execute_look();
-                                "".to_string()
-                            } else {
-                                return format!("No existe la ubicación '{}'.", tag);
-                            }
-                        } else {
-                            return self.show_default_locations();
-                        }
-                    } else {
-                        return format!("No existe la ubicación actual '{}'.", current_location);
-                    }
-                } else {
-                    // Si no hay ubicación actual, permitir moverse a cualquier ubicación válida
-                    if find_location(tag).is_some() {
-                        self.set_current_location(Some(tag.to_string()));
-                        self.execute_look();
-                        "".to_string()
-                    } else {
-                        return format!("No existe la ubicación '{}'.", tag);
-                    }
+        if let Some(reason) = self.travel_blocked_by_load() {
+            return reason;
+        }
+
+        // Una dirección ("norte", "n"...) lleva a la ubicación que queda hacia allí, si se conoce
+        let location_tag = match location_tag.and_then(Direction::from_word) {
+            Some(direction) => {
+                let exit = self.current_location.as_ref()
+                    .and_then(|tag| find_location(tag))
+                    .and_then(|location| location.exit_towards(direction))
+                    .filter(|tag| find_location(tag).is_some_and(|location| location.base.visible) || self.discovered_locations.contains(*tag));
+                match exit {
+                    Some(tag) => Some(tag),
+                    None => return format!("No hay ninguna salida hacia {}.", direction),
                 }
-            }
+            },
+            None => location_tag,
+        };
+
+        let tag = match location_tag {
+            Some(tag) => tag,
+            None => return self.show_default_locations(),
+        };
+        let current_location = match self.current_location.clone() {
+            Some(current_location) => current_location,
             None => {
-                return self.show_default_locations();
+                // Si no hay ubicación actual, permitir moverse a cualquier ubicación válida
+                if find_location(tag).is_none() {
+                    return format!("No existe la ubicación '{}'.", tag);
+                }
+                self.set_current_location(Some(tag.to_string()));
+                self.execute_look();
+                return "".to_string();
+            },
+        };
+        let location = match find_location(&current_location) {
+            Some(location) => location,
+            None => return format!("No existe la ubicación actual '{}'.", current_location),
+        };
+
+        // Un lugar conocido que no está al lado se alcanza viajando por el camino más corto
+        if !location.connections.contains(&tag.to_string()) {
+            if self.visited_locations.contains(tag) {
+                self.execute_travel(tag, dice);
+                return "".to_string();
             }
+            return self.show_default_locations();
         }
-    }
 
-    fn show_default_locations(&self) -> String {
-        let mut response = String::new();
-        response.push_str("\nPuedes ir a:\n");
-        response.push_str(&self.show_available_locations());
-        response
+        match self.step_to(&current_location, tag, dice) {
+            Ok(()) => {
+                self.execute_look();
+                "".to_string()
+            },
+            Err(reason) => reason,
+        }
     }
 
-    pub fn execute_take(&mut self, item_tag: &str) -> bool {
-        if let Some(location_tag) = &self.current_location {
-            if let Some(location) = find_location(location_tag) {
-                if let Some(item) = find_item_in_location(location, item_tag) {
-                    if (item.base.visible || self.discovered_items.contains(&item.base.tag)) && !self.picked_items.contains(&item.base.tag) {
-                        self.inventory.push(item.clone());
-                        self.picked_items.insert(item.base.tag.clone());
-                        println!("Has cogido {} y lo has añadido a tu inventario.", item.base.description);
-                        return true;
-                    }
-                }
-                println!("No hay ningún objeto con ese nombre aquí.");
-                if !location.content.items.is_empty() {
-                    println!("\nPuedes coger:");
-                    for (i, item) in location.content.items.iter().enumerate() {
-                        if (item.base.visible || self.discovered_items.contains(&item.base.tag)) && !self.picked_items.contains(&item.base.tag) {
-                            if i == location.content.items.len() - 1 {
-                                println!("- {} [{}].", item.base.description, item.base.tag);
-                            } else {
-                                println!("- {} [{}],", item.base.description, item.base.tag);
-                            }
-                        }
-                    }
-                } else {
-                    println!("No hay nada que puedas coger aquí.");
-                }
-            }
+    // Cruza a una ubicación conectada con la actual, si el camino está abierto
+    fn step_to(&mut self, current_location: &str, tag: &str, dice: &mut dyn DiceRoller) -> Result<(), String> {
+        // Por un pasaje de un solo sentido no se puede volver
+        if let Some(passage) = PASSAGES.values().find(|p| p.one_way && p.from == tag && p.to == current_location) {
+            return Err(format!("Por {} no hay vuelta atrás: solo se puede recorrer en un sentido.", passage.base.description));
         }
-        false
-    }
 
-    pub fn execute_inventory(&self) {
-        if self.inventory.is_empty() {
-            println!("Tu inventario está vacío.");
-        } else {
-            // Primero mostrar objetos no equipados
-            let mut non_equipped_items: Vec<&Item> = Vec::new();
-
-            for item in self.inventory.iter() {
-                let is_equipped = if item.is_equipment {
-                    let mut equipped = false;
-                    for character in &self.characters {
-                        if let Some(equipment) = item.to_equipment() {
-                            match equipment.equipment_type {
-                                EquipmentType::Basic => (),
-                                EquipmentType::Weapon(_) => {
-                                    if let Some(weapon) = &character.weapon {
-                                        if weapon.name == item.base.description {
-                                            equipped = true;
-                                        }
-                                    }
-                                },
-                                EquipmentType::Shield => {
-                                    if let Some(shield) = &character.shield {
-                                        if shield.name == item.base.description {
-                                            equipped = true;
-                                        }
-                                    }
-                                },
-                                EquipmentType::Armor(_) => {
-                                    if let Some(armor) = &character.armor {
-                                        if armor.name == item.base.description {
-                                            equipped = true;
-                                        }
-                                    }
-                                },
-                                EquipmentType::Bow => {
-                                    if let Some(bow) = &character.bow {
-                                        if bow.name == item.base.description {
-                                            equipped = true;
-                                        }
-                                    }
-                                },
-                            }
-                        }
-                    }
-                    equipped
-                } else {
-                    false
-                };
+        let destination = find_location(tag).ok_or_else(|| format!("No existe la ubicación '{}'.", tag))?;
 
-                if !is_equipped {
-                    non_equipped_items.push(item);
-                }
-            }
+        // Buscar el pasaje que lleva hasta allí en este sentido
+        let passage = find_passage_between(current_location, tag);
 
-            if !non_equipped_items.is_empty() {
-                println!("Objetos en el inventario:");
-                for (i, item) in non_equipped_items.iter().enumerate() {
-                    if i == non_equipped_items.len() - 1 {
-                        println!("- {}.", item.base.description);
-                    } else {
-                        println!("- {},", item.base.description);
-                    }
-                }
+        // Si encontramos un pasaje con requisitos especiales, verificarlos
+        if let Some(passage) = passage {
+            // Verificar las condiciones del pasaje, cada una con su motivo
+            if let Some((_, reason)) = passage.conditions.iter().find(|(requirement, _)| !self.meets_requirement(requirement)) {
+                return Err(reason.clone());
             }
 
-            // Luego mostrar objetos equipados
-            let mut has_equipped = false;
-            for character in &self.characters {
-                if character.weapon.is_some() || character.shield.is_some() || 
-                   character.armor.is_some() || character.bow.is_some() {
-                    if !has_equipped {
-                        println!("\nObjetos equipados:");
-                        has_equipped = true;
-                    }
-                    if let Some(weapon) = &character.weapon {
-                        println!("- {} (equipado por {} - {})", weapon.name, character.name, character.class);
-                    }
-                    if let Some(shield) = &character.shield {
-                        println!("- {} (equipado por {} - {})", shield.name, character.name, character.class);
-                    }
-                    if let Some(armor) = &character.armor {
-                        println!("- {} (equipado por {} - {})", armor.name, character.name, character.class);
-                    }
-                    if let Some(bow) = &character.bow {
-                        println!("- {} (equipado por {} - {})", bow.name, character.name, character.class);
-                    }
-                }
+            // Verificar si el pasaje está cerrado con llave
+            if let Some(reason) = self.check_lock(&Fixture::Passage(passage), dice) {
+                return Err(reason);
             }
 
-            if non_equipped_items.is_empty() && !has_equipped {
-                println!("Tu inventario está vacío.");
+            // Verificar si quedan enigmas por resolver para abrir el pasaje
+            let pending: Vec<&str> = passage.puzzles.iter()
+                .filter(|tag| !self.solved_puzzles.contains(*tag))
+                .filter_map(|tag| find_puzzle(tag))
+                .map(|puzzle| puzzle.title.as_str())
+                .collect();
+            if !pending.is_empty() {
+                return Err(format!("{} sigue sellada. Antes hay que resolver: {}.", passage.base.description, pending.join(", ")));
             }
         }
-    }
-
-    pub fn execute_status(&self) {
-        println!("Estado del grupo:");
-        println!("=================");
 
-        for character in &self.characters {
-            println!(
-                "- {} ({}, nivel {}): {} PV/{} PV",
-                character.name,
-                character.class,
-                character.level,
-                character.hit_points,
-                character.max_hit_points
-            );
+        // Verificar si la puerta de la sala está cerrada
+        if let Some(reason) = self.check_lock(&Fixture::Room(destination), dice) {
+            return Err(reason);
         }
 
-        println!("====================");
-        println!("XP acumulados: {}/10", self.encounters_won);
+        // Si llegamos aquí, el jugador puede pasar
+        if let Some(passage) = passage {
+            self.traverse(passage);
+            self.trigger_trap(&Fixture::Passage(passage), dice);
+        }
+        self.apply_travel_fatigue(dice);
+        self.set_current_location(Some(tag.to_string()));
+        self.trigger_trap(&Fixture::Room(destination), dice);
+        Ok(())
     }
 
-    pub fn has_item(&self, tag: &str) -> bool {
-        self.inventory.iter().any(|item| item.base.tag == tag)
+    // Lo que el grupo sabe del mundo: las ubicaciones visitadas, con sus salidas hacia las que
+    // se ven o se han descubierto, y esas otras ubicaciones aún sin explorar
+    fn map_rooms(&self) -> Vec<MapRoom> {
+        let visited: HashSet<&String> = self.visited_locations.iter().chain(self.current_location.as_ref()).collect();
+        let is_known = |tag: &String| visited.contains(tag) || self.discovered_locations.contains(tag) ||
+            find_location(tag).is_some_and(|location| location.base.visible);
+
+        let mut tags: Vec<&String> = visited.iter().copied()
+            .chain(self.discovered_locations.iter())
+            .chain(visited.iter()
+                .filter_map(|tag| find_location(tag))
+                .flat_map(|location| location.connections.iter())
+                .filter(|tag| is_known(tag)))
+            .filter(|tag| find_location(tag).is_some())
+            .collect();
+        // Orden estable, empezando por donde está el grupo, para que el mapa no cambie de una vez a otra
+        tags.sort();
+        tags.dedup();
+        tags.sort_by_key(|tag| self.current_location.as_ref() != Some(*tag));
+
+        tags.into_iter()
+            .filter_map(|tag| find_location(tag))
+            .map(|location| {
+                let tag = &location.base.tag;
+                let is_visited = visited.contains(tag);
+                let exits = location.exits.iter()
+                    .filter(|_| is_visited)
+                    .filter(|(_, to)| is_known(to))
+                    .map(|(direction, to)| {
+                        let passage = find_passage_between(tag, to);
+                        let locked = passage.is_some_and(|passage| self.is_locked(&Fixture::Passage(passage))) ||
+                            find_location(to).is_some_and(|destination| self.is_locked(&Fixture::Room(destination)));
+                        MapExit {
+                            direction: *direction,
+                            to: to.clone(),
+                            locked,
+                            one_way: passage.is_some_and(|passage| passage.one_way),
+                        }
+                    })
+                    .collect();
+                MapRoom {
+                    tag: tag.clone(),
+                    visited: is_visited,
+                    current: self.current_location.as_ref() == Some(tag),
+                    hostile: is_visited && self.npcs_at(tag).iter().any(|npc| self.attitude_of(npc) == Attitude::Hostile),
+                    exits,
+                }
+            })
+            .collect()
     }
 
-    pub fn execute_search(&mut self) -> bool {
-        if let Some(location_tag) = &self.current_location {
-            if let Some(location) = find_location(location_tag) {
-                // Obtener el número de intentos en esta sala
-                let attempts = self.search_attempts.entry(location_tag.clone()).or_insert(0);
-                *attempts += 1;
-
-                // Calcular probabilidad de éxito
-                let mut success_chance = 50; // Base 50%
+    pub fn execute_map(&self) -> bool {
+        let rooms = self.map_rooms();
+        if rooms.is_empty() {
+            println!("Todavía no sabéis dónde estáis.");
+            return false;
+        }
+        println!("{}", render_ascii(&rooms));
+        true
+    }
 
-                // Bonus por intentos previos (máximo 5 intentos)
-                let attempts_bonus = (*attempts).min(5) * 5;
-                success_chance += attempts_bonus as i32;
+    // Exporta el mapa en formato DOT, para verlo con Graphviz (`dot -Tpng mapa.dot -o mapa.png`)
+    pub fn execute_map_export(&self, path: &str) -> bool {
+        let rooms = self.map_rooms();
+        if rooms.is_empty() {
+            println!("Todavía no sabéis dónde estáis.");
+            return false;
+        }
+        match std::fs::write(path, render_dot(&rooms)) {
+            Ok(()) => {
+                println!("Mapa exportado a {}.", path);
+                true
+            },
+            Err(reason) => {
+                println!("No se pudo exportar el mapa a {}: {}", path, reason);
+                false
+            },
+        }
+    }
 
-                // Bonus por tener antorcha
-                if self.has_item("antorcha") {
-                    success_chance += 20;
-                }
+    // Si el grupo sabe que puede ir de una ubicación a otra vecina: el pasaje se recorre en ese
+    // sentido y no exige nada que ahora no se cumpla. Las cerraduras no cuentan, que pueden abrirse
+    // con la llave al llegar
+    fn knows_way(&self, from: &str, to: &str) -> bool {
+        if !self.visited_locations.contains(to) {
+            return false;
+        }
+        if PASSAGES.values().any(|p| p.one_way && p.from == to && p.to == from) {
+            return false;
+        }
+        match find_passage_between(from, to) {
+            Some(passage) => passage.conditions.iter().all(|(requirement, _)| self.meets_requirement(requirement)) &&
+                passage.puzzles.iter().all(|tag| self.solved_puzzles.contains(tag)),
+            None => true,
+        }
+    }
 
-                // Asegurar que la probabilidad esté entre 5% y 95%
-                success_chance = success_chance.max(5).min(95);
+    // Camino más corto (sin contar el origen) hasta una ubicación ya visitada, pasando solo por
+    // ubicaciones visitadas
+    pub fn route_to(&self, destination: &str) -> Option<Vec<String>> {
+        let start = self.current_location.clone()?;
+        let mut previous: HashMap<String, String> = HashMap::new();
+        let mut pending = VecDeque::from([start.clone()]);
+        while let Some(tag) = pending.pop_front() {
+            if tag == destination {
+                let mut route = vec![tag];
+                while let Some(step) = previous.get(route.last()?) {
+                    if *step == start {
+                        break;
+                    }
+                    route.push(step.clone());
+                }
+                route.reverse();
+                return Some(route);
+            }
+            for next in &find_location(&tag)?.connections {
+                if *next != start && !previous.contains_key(next) && self.knows_way(&tag, next) {
+                    previous.insert(next.clone(), tag.clone());
+                    pending.push_back(next.clone());
+                }
+            }
+        }
+        None
+    }
 
-                // Generar número aleatorio
-                let mut rng = rand::thread_rng();
-                let roll = rng.gen_range(1..=100);
+    // Recorre etapa a etapa el camino hasta un lugar conocido. Cada etapa cuesta un turno, y el
+    // viaje se detiene ante enemigos, trampas o un paso que no se puede cruzar
+    pub fn execute_travel(&mut self, destination_tag: &str, dice: &mut dyn DiceRoller) -> bool {
+        // Un lugar al lado se alcanza sin más, aunque aún no se haya estado allí
+        let is_next = self.current_location.as_ref()
+            .and_then(|tag| find_location(tag))
+            .is_some_and(|location| location.connections.contains(&destination_tag.to_string()));
+        if is_next {
+            let response = self.execute_go(Some(destination_tag), dice);
+            if !response.is_empty() {
+                println!("{}", response);
+            }
+            return response.is_empty();
+        }
 
-                if roll <= success_chance {
-                    let mut found_something = false;
+        let destination = match find_location(destination_tag) {
+            Some(destination) if self.visited_locations.contains(destination_tag) => destination,
+            _ => {
+                println!("No conocéis ningún lugar llamado '{}'.", destination_tag);
+                return false;
+            },
+        };
+        if self.current_location.as_deref() == Some(destination_tag) {
+            println!("Ya estáis en {}.", destination.base.description);
+            return false;
+        }
+        if self.has_hostile_npcs() {
+            println!("¡No puedes huir! Hay enemigos hostiles aquí.");
+            return false;
+        }
+        if let Some(reason) = self.travel_blocked_by_load() {
+            println!("{}", reason);
+            return false;
+        }
+        let route = match self.route_to(destination_tag) {
+            Some(route) => route,
+            None => {
+                println!("No conocéis ningún camino abierto hasta {}.", destination.base.description);
+                return false;
+            },
+        };
 
-                    // Buscar items ocultos en la sala
-                    let hidden_items: Vec<_> = location.content.items.iter()
-                        .filter(|item| {
-                            !item.base.visible && !self.discovered_items.contains(&item.base.tag)
-                        })
-                        .collect();
+        println!("El grupo se pone en camino hacia {} ({} etapas).", destination.base.description, route.len());
+        for (stage, tag) in route.iter().enumerate() {
+            // La primera etapa es el turno de la propia orden
+            if stage > 0 {
+                self.advance_turn();
+            }
+            let current_location = self.current_location.clone().unwrap_or_default();
+            let traps_seen = self.detected_traps.len() + self.cleared_traps.len();
+            if let Err(reason) = self.step_to(&current_location, tag, dice) {
+                println!("{}", reason);
+                break;
+            }
+            if self.is_party_defeated() {
+                return true;
+            }
+            if let Some(location) = find_location(tag) {
+                println!("- {}", location.base.description);
+            }
+            if self.detected_traps.len() + self.cleared_traps.len() > traps_seen {
+                println!("La trampa obliga al grupo a detenerse.");
+                break;
+            }
+            if self.has_hostile_npcs() {
+                println!("¡Enemigos a la vista! El grupo se detiene.");
+                break;
+            }
+        }
+        self.execute_look();
+        true
+    }
 
-                    // Buscar localizaciones ocultas en la sala
-                    let hidden_locations: Vec<_> = location.connections.iter()
-                        .filter_map(|tag| find_location(tag))
-                        .filter(|loc| !loc.base.visible && !self.discovered_locations.contains(&loc.base.tag))
-                        .collect();
+    fn meets_requirement(&self, requirement: &Requirement) -> bool {
+        match requirement {
+            Requirement::State(condition) => self.meets_condition(None, condition),
+            Requirement::TimeOfDay(time) => self.time_of_day() == *time,
+            Requirement::MaxPartySize(size) => self.living_characters() <= *size,
+            Requirement::Trait(character_trait) => self.characters.iter()
+                .any(|character| character.is_alive() && character.has_trait(character_trait)),
+            Requirement::Light => self.has_light(),
+        }
+    }
 
-                    for item in hidden_items {
-                        println!("Has descubierto {}", item.base.description);
-                        self.discovered_items.insert(item.base.tag.clone());
-                        found_something = true;
+    // Efectos de cruzar un pasaje: cuerdas que se quedan atrás, derrumbes, marcas...
+    fn traverse(&mut self, passage: &Passage) {
+        if let Some(text) = &passage.traverse_text {
+            println!("{}", text);
+        }
+        for effect in &passage.effects {
+            match effect {
+                PassageEffect::ConsumeItem(item_tag) => {
+                    if let Some(carrier) = self.carrier_of(item_tag) {
+                        self.characters[carrier].take_from_pack(item_tag);
                     }
-                    for location in hidden_locations {
-                        println!("Has descubierto {}", location.base.description);
-                        self.discovered_locations.insert(location.base.tag.clone());
-                        found_something = true;
+                },
+                PassageEffect::Damage(damage) => {
+                    for character in self.characters.iter_mut().filter(|character| character.is_alive()) {
+                        character.take_damage(*damage);
+                        println!("{} pierde {} PV.", character.name, damage);
+                        if !character.is_alive() {
+                            println!("¡{} ha caído!", character.name);
+                        }
                     }
+                },
+                PassageEffect::SetFlag(flag) => {
+                    self.flags.insert(flag.clone());
+                },
+            }
+        }
+    }
 
-                    // Reiniciar contador de intentos para esta sala
-                    self.search_attempts.remove(location_tag);
-                    return found_something;
-                } else {
-                    println!("No encuentras nada especial...");
-                }
+    pub fn time_of_day(&self) -> TimeOfDay {
+        if self.turns % TURNS_PER_DAY < NIGHTFALL {
+            TimeOfDay::Day
+        } else {
+            TimeOfDay::Night
+        }
+    }
+
+    // Carga de cada personaje: su equipo puesto más su mochila
+    pub fn character_loads(&self) -> Vec<u32> {
+        self.characters.iter().map(|character| character.load()).collect()
+    }
+
+    pub fn encumbered_characters(&self) -> Vec<bool> {
+        self.characters.iter().map(|character| character.is_encumbered()).collect()
+    }
+
+    // Con más del doble de su capacidad, un personaje no puede ni moverse
+    fn travel_blocked_by_load(&self) -> Option<String> {
+        self.character_loads().iter()
+            .zip(&self.characters)
+            .find(|(load, character)| **load > character.class.carrying_capacity() * 2)
+            .map(|(load, character)| format!(
+                "{} lleva demasiado peso para viajar ({}/{}). Soltad algo antes de seguir.",
+                character.name, load, character.class.carrying_capacity()
+            ))
+    }
+
+    // Los personajes sobrecargados pueden agotarse por el camino (nunca por debajo de 1 PV)
+    fn apply_travel_fatigue(&mut self, dice: &mut dyn DiceRoller) {
+        let encumbered = self.encumbered_characters();
+        for (character, is_encumbered) in self.characters.iter_mut().zip(encumbered) {
+            if is_encumbered && character.hit_points > 1 && dice.roll_1d6() == 1 {
+                character.hit_points -= 1;
+                println!("{} acusa el peso de la carga y pierde 1 PV por el agotamiento.", character.name);
             }
         }
-        false
     }
 
-    pub fn execute_drop(&mut self, item_tag: &str) -> bool {
-        if let Some(location_tag) = &self.current_location {
-            if let Some(location) = find_location(location_tag) {
-                if let Some(index) = self.inventory.iter().position(|item| item.base.tag == item_tag) {
-                    // Remover el item del inventario
-                    let item = self.inventory.remove(index);
-                    // Añadir el item a la ubicación actual
-                    self.picked_items.remove(&item.base.tag);
-                    // Actualizar la ubicación del item
-                    self.dropped_items.insert(item.base.tag.clone(), location_tag.clone());
-                    println!("Has soltado {}.", item.base.description);
+    fn show_default_locations(&self) -> String {
+        let mut response = String::new();
+        response.push_str("\nPuedes ir a:\n");
+        response.push_str(&self.show_available_locations());
+        response
+    }
+
+    // Items que se pueden ver y coger en una ubicación: los de la sala aún no recogidos y los soltados allí
+    fn available_items<'a>(&'a self, location: &'a Location) -> Vec<&'a Item> {
+        // Los objetos ocultos solo aparecen una vez descubiertos
+        let room_items = location.content.items.iter()
+            .chain(get_hidden_items_in_location(location).into_iter()
+                .filter(|item| self.discovered_items.contains(&item.id)))
+            .chain(location.content.chests.iter()
+                .filter(|chest| self.opened_chests.contains(&chest_id(location, chest)))
+                .flat_map(|chest| chest.items.iter()))
+            .chain(puzzles_at(&location.base.tag).into_iter()
+                .filter(|puzzle| self.solved_puzzles.contains(&puzzle.tag))
+                .filter_map(|puzzle| puzzle.reward.as_ref()))
+            .filter(|item| {
+                let is_visible = item.base.visible || self.discovered_items.contains(&item.id);
+                is_visible && !self.picked_items.contains(&item.id)
+            });
+        let dropped_items = self.dropped_items.get(&location.base.tag).into_iter().flatten();
+        room_items.chain(dropped_items).collect()
+    }
+
+    // Quien recoge un objeto: el personaje indicado o, si no, el vivo con más capacidad libre
+    fn choose_bearer(&self, character_name: Option<&str>) -> Option<usize> {
+        match character_name {
+            Some(name) => {
+                let index = self.find_character(name);
+                if index.is_none() {
+                    println!("No se encontró ningún personaje llamado '{}'.", name);
+                }
+                index
+            },
+            None => self.characters.iter()
+                .enumerate()
+                .filter(|(_, character)| character.is_alive())
+                .max_by_key(|(_, character)| character.class.carrying_capacity() as i64 - character.load() as i64)
+                .map(|(index, _)| index),
+        }
+    }
+
+    pub fn find_character(&self, name: &str) -> Option<usize> {
+        self.characters.iter().position(|c| c.name.to_lowercase() == name.to_lowercase())
+    }
+
+    // Índice del primer personaje vivo que lleva el objeto
+    pub fn carrier_of(&self, item_tag: &str) -> Option<usize> {
+        self.characters.iter().position(|character| character.is_alive() && character.carries(item_tag))
+    }
+
+    pub fn execute_take(&mut self, item_tag: &str, character_name: Option<&str>) -> bool {
+        if let Some(location_tag) = self.current_location.clone() {
+            if let Some(location) = find_location(&location_tag) {
+                // Primero los objetos que el grupo soltó aquí, luego los de la sala
+                let dropped_index = self.dropped_items.get(&location_tag)
+                    .and_then(|dropped| dropped.iter().position(|item| item.base.tag == item_tag));
+                let room_item = self.available_items(location).into_iter()
+                    .find(|item| item.base.tag == item_tag)
+                    .cloned();
+
+                if dropped_index.is_some() || room_item.is_some() {
+                    let bearer = match self.choose_bearer(character_name) {
+                        Some(bearer) => bearer,
+                        None => return false,
+                    };
+                    let item = match dropped_index {
+                        Some(index) => self.dropped_items.get_mut(&location_tag).unwrap().remove(index),
+                        None => {
+                            let item = room_item.unwrap();
+                            self.picked_items.insert(item.id);
+                            item
+                        }
+                    };
+                    let character = &mut self.characters[bearer];
+                    println!("{} coge {} y lo guarda en su mochila.", character.name, item.base.description);
+                    character.pack.push(item);
+                    self.warn_if_encumbered();
                     return true;
+                }
+
+                println!("No hay ningún objeto con ese nombre aquí.");
+                let available_items = self.available_items(location);
+                if !available_items.is_empty() {
+                    println!("\nPuedes coger:");
+                    for (i, item) in available_items.iter().enumerate() {
+                        if i == available_items.len() - 1 {
+                            println!("- {} [{}].", item.base.description, item.base.tag);
+                        } else {
+                            println!("- {} [{}],", item.base.description, item.base.tag);
+                        }
+                    }
                 } else {
-                    println!("No tienes ese objeto en tu inventario.");
+                    println!("No hay nada que puedas coger aquí.");
                 }
             }
         }
         false
     }
 
-    fn show_available_locations(&self) -> String {
-        let mut response = String::new();
-        let mut has_connections = false;
-        if let Some(current_location) = &self.current_location {
-            if let Some(location) = find_location(&current_location) {
-                for connection in &location.connections {
-                    if let Some(connected_location) = find_location(connection) {
-                        if connected_location.base.visible || self.discovered_locations.contains(connection) {
-                            response.push_str(&format!("- {} ({})\n", connected_location.base.description, connection));
-                            has_connections = true;
-                        }
-                    }
+    pub fn execute_give(&mut self, item_tag: &str, character_name: &str) -> bool {
+        let receiver = match self.find_character(character_name) {
+            Some(index) => index,
+            None => {
+                println!("No se encontró ningún personaje llamado '{}'.", character_name);
+                return false;
+            }
+        };
+
+        let giver = self.characters.iter()
+            .enumerate()
+            .position(|(index, character)| index != receiver && character.pack.iter().any(|item| item.base.tag == item_tag));
+        match giver {
+            Some(giver) => {
+                let item = self.characters[giver].take_from_pack(item_tag).unwrap();
+                println!("{} le da {} a {}.", self.characters[giver].name, item.base.description, self.characters[receiver].name);
+                self.characters[receiver].pack.push(item);
+                if self.characters[receiver].is_encumbered() {
+                    println!("{} va sobrecargado.", self.characters[receiver].name);
                 }
+                true
+            },
+            None => {
+                if self.characters[receiver].carries(item_tag) {
+                    println!("{} ya lleva ese objeto.", self.characters[receiver].name);
+                } else if self.has_item(item_tag) {
+                    println!("Ese objeto está equipado; desequípalo antes de darlo.");
+                } else {
+                    println!("Nadie en el grupo lleva ese objeto en la mochila.");
+                }
+                false
             }
         }
-        if !has_connections {
-            response.push_str("No hay salidas disponibles desde aquí.");
+    }
+
+    fn warn_if_encumbered(&self) {
+        if self.encumbered_characters().iter().any(|&encumbered| encumbered) {
+            println!("El grupo va sobrecargado: los personajes con exceso de peso sufrirán penalizaciones.");
         }
-        response
     }
 
-    pub fn execute_attack(&mut self, target_tag: &str) {        
+    // Sin nombre muestra el inventario de todo el grupo; con nombre, solo el de ese personaje
+    pub fn execute_inventory(&self, character_name: Option<&str>) {
+        println!("Oro del grupo: {}", self.gold);
+        let characters: Vec<&Character> = match character_name {
+            Some(name) => match self.find_character(name) {
+                Some(index) => vec![&self.characters[index]],
+                None => {
+                    println!("No se encontró ningún personaje llamado '{}'.", name);
+                    return;
+                }
+            },
+            None => self.characters.iter().collect(),
+        };
 
-        let mut round = 0;
-        let mut total_enemies_defeated = 0;
+        for character in characters {
+            let capacity = character.class.carrying_capacity();
+            let load = character.load();
+            let status = if load > capacity * 2 {
+                " (no puede moverse)"
+            } else if load > capacity {
+                " (sobrecargado)"
+            } else {
+                ""
+            };
+            println!("{} ({}) - carga {}/{}{}:", character.name, character.class, load, capacity, status);
 
-        // Obtener NPCs hostiles en la ubicación actual
-        if let Some(location_tag) = &self.current_location {
-            if let Some(location) = find_location(location_tag) {
-                let hostile_npcs: Vec<&NPC> = location.content.npcs.iter()
-                    .filter_map(|npc_tag| find_npc(npc_tag))
-                    .filter(|npc| npc.attitude == Attitude::Hostile && !self.defeated_npcs.contains(&npc.base.tag))
-                    .collect();
+            let equipped = character.equipped_items();
+            if character.pack.is_empty() && equipped.is_empty() {
+                println!("  No lleva nada.");
+            }
+            for equipment in equipped {
+                println!("  - {} [{}] ({}) (equipado)", equipment.name(), equipment.item.base.tag, equipment.item.stats.summary());
+            }
+            for item in &character.pack {
+                println!("  - {} [{}] ({})", item.base.description, item.base.tag, item.stats.summary());
+            }
+        }
+    }
 
-                if hostile_npcs.is_empty() {
-                    self.current_combat_enemies = None;
-                    println!("No hay enemigos para atacar aquí.");
-                    return;
-                }
+    pub fn execute_status(&self) {
+        println!("Estado del grupo:");
+        println!("=================");
 
-                // Si es una acción de combate (2-4), procesar la acción
-                if target_tag == "2" {
-                    // Huir
-                    self.current_combat_enemies = None;
-                    println!("Has huido del combate.");
-                    return;
-                } else if target_tag == "3" {
-                    println!("Función de usar objetos aún no implementada.");
-                    return;
-                } else if target_tag == "4" {
-                    return self.execute_status();
-                }
+        for character in &self.characters {
+            println!(
+                "- {} ({}, nivel {}): {} PV/{} PV",
+                character.name,
+                character.class,
+                character.level,
+                character.hit_points,
+                character.max_hit_points
+            );
+            if !character.is_alive() {
+                println!("  Ha caído. Puedes contratar a un sustituto en la posada.");
+            }
+            if character.max_spell_slots() > 0 {
+                println!("  Hechizos: {}/{}", character.spell_slots, character.max_spell_slots());
+            }
+            for effect in &character.status_effects {
+                println!("  Estado: {}", effect);
+            }
+            if character.is_poisoned() {
+                println!("  Descansar en la posada cura el veneno.");
+            }
+            if let Some(companion) = &character.companion {
+                println!("  Compañero {}: lealtad {}/{}", companion.behaviour, companion.loyalty, MAX_LOYALTY);
+            }
+            println!("  {}", character.proficiency_rules());
+        }
 
-                // Obtener el número de enemigos restantes
-                let mut enemies_remaining = if target_tag == "continuar" || target_tag == "1" {
-                    if let Some(remaining) = self.current_combat_enemies {
-                        remaining
-                    } else {
-                        println!("No hay un combate en curso.");
-                        return;
-                    }
-                } else {
-                    hostile_npcs.iter().map(|npc| npc.count).sum()
-                };
+        println!("====================");
+        println!("XP acumulados: {}/10", self.encounters_won);
+        println!("Es {} (turno {}).", self.time_of_day(), self.turns);
+        println!("Oro: {}", self.gold);
+        for faction in [Faction::Village, Faction::Goblins, Faction::Orcs] {
+            println!("Reputación con {}: {:+}", faction, self.reputation_with(&faction));
+        }
+    }
 
-                // Bucle de combate que continúa hasta que no queden enemigos
-                loop {
-                    // Ejecutar una ronda de combate
-                    let remaining = self.execute_combat_round(&hostile_npcs[0], enemies_remaining, &mut round);
+    pub fn has_item(&self, tag: &str) -> bool {
+        self.carrier_of(tag).is_some()
+    }
 
-                    // Calcular cuántos enemigos fueron derrotados en esta ronda
-                    let enemies_defeated_this_round = enemies_remaining - remaining;
-                    total_enemies_defeated += enemies_defeated_this_round;
+    // La llave maestra abre cualquier cerradura, pero es mágica: solo la usa un lanzador de conjuros
+    pub fn key_carrier_for(&self, key_tag: &str) -> Option<usize> {
+        self.carrier_of(key_tag).or_else(|| self.master_key_carrier())
+    }
 
-                    println!("Enemigos derrotados en esta ronda: {}", enemies_defeated_this_round);
-                    println!("Total de enemigos derrotados: {}", total_enemies_defeated);
+    fn master_key_carrier(&self) -> Option<usize> {
+        self.characters.iter().position(|c| c.is_alive() && c.has_trait(&CharacterTrait::Spellcaster) && c.carries("llave_maestra"))
+    }
 
-                    // Actualizar el número de enemigos restantes
-                    enemies_remaining = remaining;
+    // Lo que hay al alcance desde la ubicación actual: sus cofres, los pasajes que salen de ella,
+    // las salas contiguas y la propia sala
+    fn fixtures_here(&self) -> Vec<Fixture> {
+        let location = match self.current_location.as_ref().and_then(|tag| find_location(tag)) {
+            Some(location) => location,
+            None => return Vec::new(),
+        };
+        let chests = location.content.chests.iter().map(|chest| Fixture::Chest(location, chest));
+        let passages = PASSAGES.values()
+            .filter(|passage| passage.from == location.base.tag || passage.to == location.base.tag)
+            .map(Fixture::Passage);
+        let rooms = location.connections.iter()
+            .filter_map(|tag| find_location(tag))
+            .chain(std::iter::once(location))
+            .map(Fixture::Room);
+        chests.chain(passages).chain(rooms).collect()
+    }
 
-                    // Si no quedan enemigos, terminar el combate
-                    if remaining == 0 {
-                        break;
-                    }
+    // Tag y descripción de todo lo que se puede nombrar en una orden, según dónde se busque
+    pub fn referents(&self, scope: Scope) -> Vec<(String, String)> {
+        let location = match self.current_location.as_ref().and_then(|tag| find_location(tag)) {
+            Some(location) => location,
+            None => return Vec::new(),
+        };
+        let named = |tag: &str, description: &str| (tag.to_string(), description.to_string());
+        match scope {
+            Scope::Room => self.available_items(location).into_iter()
+                .map(|item| named(&item.base.tag, &item.base.description))
+                .collect(),
+            Scope::Carried => self.characters.iter()
+                .flat_map(|character| character.pack.iter()
+                    .chain(character.equipped_items().into_iter().map(|equipment| &equipment.item)))
+                .map(|item| named(&item.base.tag, &item.base.description))
+                .collect(),
+            Scope::Npcs => self.npcs_at(&location.base.tag).into_iter()
+                .map(|npc| named(&npc.base.tag, &npc.base.description))
+                .collect(),
+            Scope::Places => location.connections.iter()
+                .filter_map(|tag| find_location(tag))
+                .filter(|place| place.base.visible || self.discovered_locations.contains(&place.base.tag))
+                .map(|place| named(&place.base.tag, &place.base.description))
+                .collect(),
+            Scope::Destinations => {
+                let mut places = self.referents(Scope::Places);
+                places.extend(self.visited_locations.iter()
+                    .filter(|tag| !location.connections.contains(*tag))
+                    .filter_map(|tag| find_location(tag))
+                    .map(|place| named(&place.base.tag, &place.base.description)));
+                places
+            },
+            Scope::Fixtures => location.content.chests.iter()
+                .map(|chest| named(&chest.base.tag, &chest.base.description))
+                .chain(PASSAGES.values()
+                    .filter(|passage| passage.from == location.base.tag || passage.to == location.base.tag)
+                    .map(|passage| named(&passage.base.tag, &passage.base.description)))
+                .chain(self.referents(Scope::Places))
+                .collect(),
+            Scope::Party => self.characters.iter()
+                .map(|character| named(&character.name.to_lowercase(), &format!("{} ({})", character.name, character.class)))
+                .collect(),
+            Scope::Wares => self.merchant_here()
+                .map(|(_, shop)| shop.full_stock().into_keys()
+                    .filter_map(|tag| find_item(&tag))
+                    .map(|item| named(&item.base.tag, &item.base.description))
+                    .collect())
+                .unwrap_or_default(),
+        }
+    }
 
-                    // Si quedan enemigos, mostrar opciones y guardar el estado
-                    self.current_combat_enemies = Some(remaining);
-                    println!("¿Qué quieres hacer?");
-                    println!("1. Continuar el combate");
-                    println!("2. Huir");
-                    println!("3. Usar un objeto");
-                    println!("4. Ver estado detallado");
-
-                    // Esperar la entrada del usuario
-                    let mut input = String::new();
-                    match std::io::stdin().read_line(&mut input) {
-                        Ok(_) => {
-                            let choice = input.trim();
-                            if choice == "2" {
-                                // Huir
-                                self.current_combat_enemies = None;
-                                println!("Has huido del combate.");
-                                return;
-                            } else if choice == "3" {
-                                println!("Función de usar objetos aún no implementada.");
-                                // Continuar el combate después de usar un objeto
-                            } else if choice == "4" {
-                                self.execute_status();
-                                // Continuar el combate después de ver el estado
-                            } else if choice != "1" {
-                                println!("Opción no válida. Continuando el combate...");
-                            }
-                        }
-                        Err(_) => {
-                            println!("Error al leer la entrada. Continuando el combate...");
-                        }
-                    }
-                }
+    // Busca en la ubicación actual un cofre (con o sin cerradura), un pasaje cerrado o una sala cerrada con ese nombre
+    fn lock_target(&self, target_tag: &str) -> Option<Fixture> {
+        let here = self.current_location.clone()?;
+        self.fixtures_here().into_iter()
+            .filter(|fixture| matches!(fixture, Fixture::Chest(..)) || fixture.lock().is_some())
+            .find(|fixture| fixture.matches(target_tag, &here))
+    }
 
-                // Combate terminado exitosamente
-                self.current_combat_enemies = None;
-                for npc in hostile_npcs {
-                    self.defeated_npcs.insert(npc.base.tag.clone());
-                    if !npc.has_tag(&NPCTag::Vermin) {
-                        self.encounters_won += 1;
-                    }
+    fn is_locked(&self, target: &Fixture) -> bool {
+        target.lock().is_some() && !self.unlocked.contains(&target.id())
+    }
+
+    // Abre la cerradura si alguien lleva su llave (o la llave maestra)
+    fn open_with_key(&mut self, target: &Fixture, dice: &mut dyn DiceRoller) -> bool {
+        let carrier = match target.lock().and_then(|lock| lock.key.as_ref()) {
+            Some(key_tag) => self.key_carrier_for(key_tag),
+            None => self.master_key_carrier(),
+        };
+        match carrier {
+            Some(carrier) => {
+                println!("{} abre la cerradura con su llave.", self.characters[carrier].name);
+                self.unlock_target(target, dice);
+                true
+            },
+            None => {
+                if let Some(carrier) = self.carrier_of("llave_maestra") {
+                    println!("{} lleva la llave maestra, pero solo un lanzador de conjuros sabe hacerla funcionar.", self.characters[carrier].name);
                 }
+                false
+            },
+        }
+    }
+
+    // Al pasar por un pasaje o entrar en una sala cerrada: la llave abre, si no se queda cerrada
+    fn check_lock(&mut self, target: &Fixture, dice: &mut dyn DiceRoller) -> Option<String> {
+        if !self.is_locked(target) || self.open_with_key(target, dice) {
+            return None;
+        }
+        let difficulty = target.lock().map(|lock| lock.difficulty).unwrap_or(0);
+        Some(format!("{} está cerrada con llave (dificultad {}). Podéis intentar abrirla o forzarla.", target.description(), difficulty))
+    }
+
+    fn unlock_target(&mut self, target: &Fixture, dice: &mut dyn DiceRoller) {
+        self.unlocked.insert(target.id());
+        if let Fixture::Chest(location, chest) = target {
+            self.open_chest(location, chest, dice);
+        }
+    }
+
+    fn open_chest(&mut self, location: &'static Location, chest: &'static Chest, dice: &mut dyn DiceRoller) {
+        if !self.opened_chests.insert(chest_id(location, chest)) {
+            println!("{} ya está abierto.", chest.base.description);
+            return;
+        }
+        self.trigger_trap(&Fixture::Chest(location, chest), dice);
+        if chest.items.is_empty() {
+            println!("Abres {}, pero está vacío.", chest.base.description);
+        } else {
+            println!("Abres {}. Dentro hay:", chest.base.description);
+            for item in &chest.items {
+                println!("- {}", item.base.description);
+            }
+        }
+    }
+
+    // Lo que ya no tiene cerradura que abrir: un cofre se abre sin más, una puerta ya está abierta
+    fn open_unlocked(&mut self, target: &Fixture, dice: &mut dyn DiceRoller) {
+        match target {
+            Fixture::Chest(location, chest) => self.open_chest(location, chest, dice),
+            _ => println!("{} ya está abierta.", target.description()),
+        }
+    }
+
+    // abrir: con la llave si alguien la lleva, si no forzando la cerradura con maña (ladrón o ganzúas)
+    pub fn execute_open(&mut self, target_tag: &str, dice: &mut dyn DiceRoller) -> bool {
+        let target = match self.lock_target(target_tag) {
+            Some(target) => target,
+            None => {
+                println!("No hay nada llamado '{}' que abrir aquí.", target_tag);
+                return false;
+            }
+        };
+        if !self.is_locked(&target) {
+            self.open_unlocked(&target, dice);
+            return true;
+        }
+        if self.open_with_key(&target, dice) {
+            return true;
+        }
+
+        let picker = self.characters.iter()
+            .enumerate()
+            .filter(|(_, character)| character.is_alive())
+            .filter_map(|(index, character)| character.lockpick_bonus().map(|bonus| (index, bonus)))
+            .max_by_key(|(_, bonus)| *bonus);
+        let (picker, bonus) = match picker {
+            Some(picker) => picker,
+            None => {
+                println!("{} está cerrada y nadie tiene la llave ni sabe forzar una cerradura.", target.description());
+                return false;
+            }
+        };
+        let difficulty = target.lock().map(|lock| lock.difficulty).unwrap_or(0);
+        let roll = dice.roll_1d6() as i32;
+        println!("{} trastea con la cerradura: {} + {} = {} contra dificultad {}.",
+                 self.characters[picker].name, roll, bonus, roll + bonus, difficulty);
+
+        // Un 1 siempre falla, y quien usa ganzúas las parte dentro de la cerradura
+        if roll == 1 {
+            if self.characters[picker].take_from_pack("ganzuas").is_some() {
+                println!("¡Las ganzúas de {} se parten dentro de la cerradura!", self.characters[picker].name);
+            } else {
+                println!("La cerradura se resiste.");
+            }
+            return false;
+        }
+        if roll + bonus >= difficulty {
+            println!("¡Clic! La cerradura cede.");
+            self.unlock_target(&target, dice);
+            true
+        } else {
+            println!("La cerradura se resiste.");
+            false
+        }
+    }
+
+    // forzar: el más fuerte echa abajo la cerradura. Cuesta más que abrirla con maña y siempre hace ruido
+    pub fn execute_force(&mut self, target_tag: &str, dice: &mut dyn DiceRoller) -> bool {
+        let target = match self.lock_target(target_tag) {
+            Some(target) => target,
+            None => {
+                println!("No hay nada llamado '{}' que forzar aquí.", target_tag);
+                return false;
+            }
+        };
+        if !self.is_locked(&target) {
+            self.open_unlocked(&target, dice);
+            return true;
+        }
+
+        let strongest = match self.characters.iter()
+            .enumerate()
+            .filter(|(_, character)| character.is_alive())
+            .max_by_key(|(_, character)| character.force_bonus()) {
+            Some((index, _)) => index,
+            None => return false,
+        };
+        let bonus = self.characters[strongest].force_bonus();
+        let difficulty = target.lock().map(|lock| lock.difficulty + 1).unwrap_or(0);
+        let roll = dice.roll_1d6() as i32;
+        println!("{} arremete contra {}: {} + {} = {} contra dificultad {}.",
+                 self.characters[strongest].name, target.description(), roll, bonus, roll + bonus, difficulty);
+
+        let success = roll != 1 && roll + bonus >= difficulty;
+        if success {
+            println!("¡La cerradura salta en pedazos con un crujido!");
+            self.unlock_target(&target, dice);
+        } else {
+            println!("La cerradura aguanta los golpes.");
+        }
+        self.make_noise(dice);
+        success
+    }
+
+    // Trampa aún activa en un pasaje, sala o cofre
+    fn armed_trap(&self, fixture: &Fixture) -> Option<&'static Trap> {
+        fixture.trap().filter(|_| !self.cleared_traps.contains(&fixture.id()))
+    }
+
+    // Al cruzar, entrar o abrir: una trampa descubierta se esquiva (salvo la de un cofre, que está
+    // en la propia cerradura) y un personaje perspicaz puede verla justo a tiempo
+    fn trigger_trap(&mut self, fixture: &Fixture, dice: &mut dyn DiceRoller) {
+        let trap = match self.armed_trap(fixture) {
+            Some(trap) => trap,
+            None => return,
+        };
+        let is_chest = matches!(fixture, Fixture::Chest(..));
+        if !is_chest && !self.detected_traps.contains(&fixture.id()) {
+            let observer = self.characters.iter()
+                .find(|c| c.is_alive() && c.has_trait(&CharacterTrait::Perceptive));
+            if let Some(observer) = observer {
+                if dice.roll_1d6() as i32 + observer.trap_sense(&trap.kind) >= trap.difficulty {
+                    println!("{} se detiene en seco: ha visto {} en {}.", observer.name, trap.kind, fixture.description());
+                    self.detected_traps.insert(fixture.id());
+                }
+            }
+        }
+        if !is_chest && self.detected_traps.contains(&fixture.id()) {
+            println!("Sabiendo dónde está {}, el grupo pasa de largo con cuidado.", trap.kind);
+            return;
+        }
+        let leader = self.characters.iter().position(|c| c.is_alive());
+        self.spring_trap(fixture, leader);
+    }
+
+    // La trampa salta: daña a todo el grupo o solo a la víctima, y queda gastada
+    fn spring_trap(&mut self, fixture: &Fixture, victim: Option<usize>) {
+        let trap = match self.armed_trap(fixture) {
+            Some(trap) => trap,
+            None => return,
+        };
+        println!("{}", trap.kind.sprung_text());
+        self.cleared_traps.insert(fixture.id());
+        for (index, character) in self.characters.iter_mut().enumerate() {
+            let is_victim = if trap.kind.hits_whole_party() { character.is_alive() } else { Some(index) == victim };
+            if !is_victim {
+                continue;
+            }
+            character.take_damage(trap.kind.damage());
+            println!("{} pierde {} PV.", character.name, trap.kind.damage());
+            if let Some(effect) = trap.kind.status_effect() {
+                println!("{} queda {}.", character.name, effect);
+                character.status_effects.push(effect);
+            }
+            if !character.is_alive() {
+                println!("¡{} ha caído!", character.name);
+            }
+        }
+    }
+
+    // Al buscar con éxito: quien mejor intuye las trampas tira 1d6 + su bonus por cada una
+    fn detect_traps(&mut self, dice: &mut dyn DiceRoller) -> bool {
+        let here = match self.current_location.clone() {
+            Some(here) => here,
+            None => return false,
+        };
+        let mut found = false;
+        for fixture in self.fixtures_here() {
+            // De las salas, solo la propia: las contiguas se registran al entrar en ellas
+            if matches!(fixture, Fixture::Room(location) if location.base.tag != here) {
+                continue;
+            }
+            let trap = match self.armed_trap(&fixture) {
+                Some(trap) if !self.detected_traps.contains(&fixture.id()) => trap,
+                _ => continue,
+            };
+            let searcher = self.characters.iter()
+                .filter(|c| c.is_alive())
+                .max_by_key(|c| c.trap_sense(&trap.kind));
+            if let Some(searcher) = searcher {
+                if dice.roll_1d6() as i32 + searcher.trap_sense(&trap.kind) >= trap.difficulty {
+                    println!("¡{} descubre {} en {}!", searcher.name, trap.kind, fixture.description());
+                    self.detected_traps.insert(fixture.id());
+                    found = true;
+                }
+            }
+        }
+        found
+    }
+
+    // desarmar: un ladrón con ganzúas inutiliza una trampa ya descubierta; con un 1 le salta encima
+    pub fn execute_disarm(&mut self, target_tag: Option<&str>, dice: &mut dyn DiceRoller) -> bool {
+        let here = self.current_location.clone().unwrap_or_default();
+        let fixture = self.fixtures_here().into_iter()
+            .filter(|fixture| self.armed_trap(fixture).is_some() && self.detected_traps.contains(&fixture.id()))
+            .find(|fixture| target_tag.is_none_or(|tag| fixture.matches(tag, &here)));
+        let fixture = match fixture {
+            Some(fixture) => fixture,
+            None => {
+                println!("No habéis descubierto ninguna trampa que desarmar ahí.");
+                return false;
+            }
+        };
+        let trap = self.armed_trap(&fixture).unwrap();
+        let disarmer = match self.characters.iter().position(|c| c.is_alive() && c.can_disarm()) {
+            Some(disarmer) => disarmer,
+            None => {
+                println!("Hace falta un pícaro con ganzúas para desarmar {}.", trap.kind);
+                return false;
+            }
+        };
+        let bonus = self.characters[disarmer].lockpick_bonus().unwrap_or(0);
+        let roll = dice.roll_1d6() as i32;
+        println!("{} trabaja con cuidado sobre {}: {} + {} = {} contra dificultad {}.",
+                 self.characters[disarmer].name, trap.kind, roll, bonus, roll + bonus, trap.difficulty);
+        if roll == 1 {
+            println!("¡Un mal movimiento!");
+            self.spring_trap(&fixture, Some(disarmer));
+            return false;
+        }
+        if roll + bonus >= trap.difficulty {
+            println!("La trampa queda inutilizada.");
+            self.cleared_traps.insert(fixture.id());
+            true
+        } else {
+            println!("No consigue desarmarla, pero tampoco la hace saltar.");
+            false
+        }
+    }
+
+    // Primer enigma sin resolver de la ubicación actual que se resuelve de esta forma
+    fn pending_puzzle(&self, is_kind: impl Fn(&PuzzleKind) -> bool) -> Option<&'static Puzzle> {
+        puzzles_at(self.current_location.as_ref()?).into_iter()
+            .find(|puzzle| is_kind(&puzzle.kind) && !self.solved_puzzles.contains(&puzzle.tag))
+    }
+
+    // Resuelve el enigma o apunta el fallo; tras varios fallos el grupo cae en alguna pista
+    fn attempt_puzzle(&mut self, puzzle: &'static Puzzle, attempt: &str) -> bool {
+        if puzzle.check(attempt) {
+            self.solved_puzzles.insert(puzzle.tag.clone());
+            self.puzzle_failures.remove(&puzzle.tag);
+            self.end_puzzle();
+            println!("{}", puzzle.solved_text);
+            return true;
+        }
+        let failures = self.puzzle_failures.entry(puzzle.tag.clone()).or_insert(0);
+        *failures += 1;
+        println!("No ocurre nada.");
+        if let Some(hint) = puzzle.hint_after(*failures) {
+            println!("Pista: {}", hint);
+        }
+        // Mientras se sigue probando basta con escribir el siguiente intento
+        if self.mode != GameMode::Puzzle(puzzle.tag.clone()) {
+            println!("Puedes seguir probando escribiendo solo tu intento, o dejarlo con cualquier otra orden.");
+            self.mode = GameMode::Puzzle(puzzle.tag.clone());
+        }
+        false
+    }
+
+    pub fn end_puzzle(&mut self) {
+        if matches!(self.mode, GameMode::Puzzle(_)) {
+            self.mode = GameMode::Exploration;
+        }
+    }
+
+    // responder: contesta al acertijo de la ubicación actual
+    pub fn execute_answer(&mut self, answer: &str) -> bool {
+        match self.pending_puzzle(|kind| matches!(kind, PuzzleKind::Riddle(_))) {
+            Some(puzzle) => self.attempt_puzzle(puzzle, answer),
+            None => {
+                println!("Aquí no hay ningún acertijo que responder.");
+                false
+            }
+        }
+    }
+
+    // pulsar: acciona los símbolos o palancas en el orden indicado
+    pub fn execute_press(&mut self, sequence: &str) -> bool {
+        match self.pending_puzzle(|kind| matches!(kind, PuzzleKind::Sequence(_))) {
+            Some(puzzle) => self.attempt_puzzle(puzzle, sequence),
+            None => {
+                println!("Aquí no hay nada que pulsar.");
+                false
+            }
+        }
+    }
+
+    // colocar: deja un objeto del grupo en su sitio; si no es el que se espera, se recupera
+    pub fn execute_place(&mut self, item_tag: &str) -> bool {
+        let puzzle = match self.pending_puzzle(|kind| matches!(kind, PuzzleKind::Placement(_))) {
+            Some(puzzle) => puzzle,
+            None => {
+                println!("Aquí no hay dónde colocar nada.");
+                return false;
+            }
+        };
+        let holder = match self.characters.iter().position(|character| character.is_alive() && character.pack.iter().any(|item| item.base.tag == item_tag)) {
+            Some(holder) => holder,
+            None => {
+                println!("Nadie lleva '{}' en la mochila.", item_tag);
+                return false;
+            }
+        };
+        println!("{} coloca {}.", self.characters[holder].name, item_tag);
+        let solved = self.attempt_puzzle(puzzle, item_tag);
+        if solved {
+            self.characters[holder].take_from_pack(item_tag);
+        } else {
+            println!("{} lo recoge de nuevo.", self.characters[holder].name);
+        }
+        solved
+    }
+
+    // El estruendo puede atraer a los merodeadores a la ubicación actual
+    fn make_noise(&mut self, dice: &mut dyn DiceRoller) {
+        println!("El estruendo resuena a lo lejos...");
+        if dice.roll_1d6() > 2 {
+            return;
+        }
+        if let (Some(location_tag), Some(npc)) = (self.current_location.clone(), find_npc("merodeadores")) {
+            self.defeated_npcs.remove(&npc.base.tag);
+            self.spawned_npcs.insert(npc.base.tag.clone(), location_tag);
+            println!("¡Aparece {}!", npc.base.description);
+        }
+    }
+
+    pub fn execute_search(&mut self, dice: &mut dyn DiceRoller) -> bool {
+        if let Some(location_tag) = self.current_location.clone() {
+            if let Some(location) = find_location(&location_tag) {
+                // A oscuras solo puede registrar quien ve en la oscuridad, y a duras penas
+                let in_darkness = self.is_dark_here();
+                if in_darkness && !self.has_darkvision() {
+                    println!("Está demasiado oscuro para registrar nada. Necesitáis luz.");
+                    return false;
+                }
+
+                // Obtener el número de intentos en esta sala
+                let attempts = self.search_attempts.entry(location_tag.clone()).or_insert(0);
+                *attempts += 1;
+
+                // Calcular probabilidad de éxito
+                let mut success_chance = 50; // Base 50%
+
+                // Bonus por intentos previos (máximo 5 intentos)
+                let attempts_bonus = (*attempts).min(5) * 5;
+                success_chance += attempts_bonus as i32;
+
+                // Bonus por llevar una luz encendida: quien la lleva ilumina los rincones
+                if let Some(carrier) = self.characters.iter().find(|c| c.is_alive() && c.carries_light()) {
+                    println!("{} alumbra los rincones.", carrier.name);
+                    success_chance += 20;
+                }
+
+                // Un personaje sigiloso sabe dónde esconderían algo los de su oficio
+                if let Some(searcher) = self.characters.iter().find(|c| c.is_alive() && c.has_trait(&CharacterTrait::Stealthy)) {
+                    println!("{} registra los escondrijos con ojo experto.", searcher.name);
+                    success_chance += 20;
+                }
+
+                if in_darkness {
+                    println!("Registráis a tientas, guiados por quien ve en la oscuridad.");
+                    success_chance -= 20;
+                }
+
+                // Asegurar que la probabilidad esté entre 5% y 95%
+                success_chance = success_chance.max(5).min(95);
+
+                let roll = dice.roll_1d100() as i32;
+
+                if roll <= success_chance {
+                    let mut found_something = false;
+
+                    // Buscar items ocultos en la sala
+                    let hidden_items: Vec<_> = get_hidden_items_in_location(location).into_iter()
+                        .filter(|item| !self.discovered_items.contains(&item.id))
+                        .collect();
+
+                    // Buscar localizaciones ocultas en la sala
+                    let hidden_locations: Vec<_> = location.connections.iter()
+                        .filter_map(|tag| find_location(tag))
+                        .filter(|loc| !loc.base.visible && !self.discovered_locations.contains(&loc.base.tag))
+                        .collect();
+
+                    for item in hidden_items {
+                        println!("Has descubierto {}", item.base.description);
+                        self.discovered_items.insert(item.id);
+                        found_something = true;
+                    }
+                    for location in hidden_locations {
+                        println!("Has descubierto {}", location.base.description);
+                        self.discovered_locations.insert(location.base.tag.clone());
+                        found_something = true;
+                    }
+                    if self.detect_traps(dice) {
+                        found_something = true;
+                    }
+
+                    if !found_something {
+                        println!("Registráis la zona a fondo, pero aquí no queda nada por descubrir.");
+                    }
+
+                    // Reiniciar contador de intentos para esta sala
+                    self.search_attempts.remove(&location_tag);
+                    return found_something;
+                } else {
+                    println!("No encuentras nada especial...");
+                }
+            }
+        }
+        false
+    }
+
+    pub fn execute_drop(&mut self, item_tag: &str) -> bool {
+        if let Some(location_tag) = &self.current_location {
+            let holder = self.characters.iter().position(|character| character.pack.iter().any(|item| item.base.tag == item_tag));
+            if let Some(holder) = holder {
+                // Sacar el item de la mochila y dejarlo en la ubicación actual
+                let item = self.characters[holder].take_from_pack(item_tag).unwrap();
+                println!("{} suelta {}.", self.characters[holder].name, item.base.description);
+                self.dropped_items.entry(location_tag.clone()).or_default().push(item);
+                return true;
+            } else if self.has_item(item_tag) {
+                println!("Tienes que desequipar ese objeto antes de soltarlo.");
+            } else {
+                println!("No tienes ese objeto en tu inventario.");
+            }
+        }
+        false
+    }
+
+    fn show_available_locations(&self) -> String {
+        let mut response = String::new();
+        let mut has_connections = false;
+        if let Some(current_location) = &self.current_location {
+            if let Some(location) = find_location(&current_location) {
+                for connection in &location.connections {
+                    if let Some(connected_location) = find_location(connection) {
+                        if connected_location.base.visible || self.discovered_locations.contains(connection) {
+                            let direction = location.exits.iter()
+                                .find(|(_, tag)| tag == connection)
+                                .map(|(direction, _)| format!(", hacia {}", direction))
+                                .unwrap_or_default();
+                            response.push_str(&format!("- {} ({}){}\n", connected_location.base.description, connection, direction));
+                            has_connections = true;
+                        }
+                    }
+                }
+            }
+        }
+        if !has_connections {
+            response.push_str("No hay salidas disponibles desde aquí.");
+        }
+        response
+    }
+
+    pub fn in_combat(&self) -> bool {
+        matches!(self.mode, GameMode::Combat(_))
+    }
+
+    // atacar: empieza un combate contra los hostiles de aquí (o contra un NPC concreto) y juega la primera ronda.
+    // En mitad de un combate, juega la siguiente
+    pub fn execute_attack(&mut self, target_tag: &str, dice: &mut dyn DiceRoller) -> bool {
+        if self.in_combat() {
+            return self.execute_combat_action(CombatAction::Fight, dice);
+        }
+
+        // Atacar a un NPC concreto lo vuelve hostil aunque no lo fuera
+        let target = match target_tag {
+            "" => None,
+            tag => match self.npc_here(tag) {
+                Some(npc) => Some(npc),
+                None => {
+                    println!("No hay nadie llamado '{}' aquí.", tag);
+                    return false;
+                }
+            },
+        };
+        if let Some(npc) = target {
+            self.provoke(npc);
+        }
+
+        // Obtener NPCs hostiles en la ubicación actual
+        let hostile_npcs: Vec<&NPC> = match &self.current_location {
+            Some(location_tag) => self.npcs_at(location_tag).into_iter()
+                .filter(|npc| self.attitude_of(npc) == Attitude::Hostile)
+                .filter(|npc| target.is_none_or(|target| target.base.tag == npc.base.tag))
+                .collect(),
+            None => Vec::new(),
+        };
+        if hostile_npcs.is_empty() {
+            println!("No hay enemigos para atacar aquí.");
+            return false;
+        }
+
+        let enemies = hostile_npcs.iter().map(|npc| npc.base.tag.clone()).collect();
+        let remaining = hostile_npcs.iter().map(|npc| npc.count).sum();
+        self.mode = GameMode::Combat(Combat::new(enemies, remaining));
+        self.execute_combat_action(CombatAction::Fight, dice)
+    }
+
+    // Un paso del combate en curso: una ronda, huir, usar un objeto o mirar el estado del grupo
+    pub fn execute_combat_action(&mut self, action: CombatAction, dice: &mut dyn DiceRoller) -> bool {
+        let mut combat = match &self.mode {
+            GameMode::Combat(combat) => combat.clone(),
+            _ => {
+                println!("No hay un combate en curso.");
+                return false;
+            }
+        };
+
+        match action {
+            CombatAction::Fight => {},
+            CombatAction::Flee => {
+                self.mode = GameMode::Exploration;
+                println!("Has huido del combate.");
+                return true;
+            },
+            CombatAction::UseItem => {
+                println!("Función de usar objetos aún no implementada.");
+                self.show_combat_menu();
+                return false;
+            },
+            CombatAction::Status => {
+                self.execute_status();
+                self.show_combat_menu();
+                return true;
+            },
+        }
+
+        // Se lucha contra el primer grupo de enemigos
+        let npc = match combat.enemies.first().and_then(|tag| find_npc(tag)) {
+            Some(npc) => npc,
+            None => {
+                self.mode = GameMode::Exploration;
+                return false;
+            }
+        };
+        let remaining = self.execute_combat_round(npc, combat.remaining, &mut combat.round, dice);
+
+        // Calcular cuántos enemigos fueron derrotados en esta ronda
+        let enemies_defeated_this_round = combat.remaining - remaining;
+        combat.defeated += enemies_defeated_this_round;
+        combat.remaining = remaining;
+        println!("Enemigos derrotados en esta ronda: {}", enemies_defeated_this_round);
+        println!("Total de enemigos derrotados: {}", combat.defeated);
+
+        // Si no quedan enemigos, terminar el combate
+        if remaining == 0 {
+            self.mode = GameMode::Exploration;
+            self.win_combat(&combat);
+            return true;
+        }
+
+        // Si ha caído todo el grupo, la aventura termina aquí
+        if self.is_party_defeated() {
+            self.mode = GameMode::Exploration;
+            println!("Todo el grupo ha caído. Vuestra aventura termina aquí.");
+            return true;
+        }
+
+        // Si quedan enemigos, el combate sigue a la espera de la próxima orden
+        self.mode = GameMode::Combat(combat);
+        self.show_combat_menu();
+        true
+    }
+
+    fn show_combat_menu(&self) {
+        println!("¿Qué quieres hacer?");
+        println!("1. Continuar el combate");
+        println!("2. Huir");
+        println!("3. Usar un objeto");
+        println!("4. Ver estado detallado");
+    }
+
+    fn win_combat(&mut self, combat: &Combat) {
+        for npc in combat.enemies.iter().filter_map(|tag| find_npc(tag)) {
+            self.defeated_npcs.insert(npc.base.tag.clone());
+            self.record_defeat(npc);
+            if !npc.has_tag(&NPCTag::Vermin) {
+                self.encounters_won += 1;
+            }
+        }
+
+        println!("¡Combate terminado! Has derrotado a {} enemigos en total.", combat.defeated);
+
+        self.check_level_up();
+    }
+
+    fn execute_combat_round(&mut self, npc: &NPC, enemies_remaining: u8, round: &mut u8, dice: &mut dyn DiceRoller) -> u8 {
+        *round += 1;
+        if round == &1 {
+            println!("¡Comienza el combate!");
+        }
+
+        // Fase de ataque de los aventureros
+        println!("Ataque de los aventureros:");
+        let mut enemies_defeated = 0;
+        let enemies_outnumbered = self.living_characters() > enemies_remaining as usize;
+        let encumbered = self.encumbered_characters();
+        let in_darkness = self.is_dark_here();
+
+        for (character, is_encumbered) in self.characters.iter_mut().zip(encumbered.iter()) {
+            if enemies_remaining == 0 || enemies_defeated == enemies_remaining {
+                break;
+            }
+            // Los caídos no participan en el combate
+            if !character.is_alive() {
+                continue;
+            }
+            if character.holds_back() {
+                println!("{} está malherido y se mantiene a cubierto.\n", character.name);
+                continue;
+            }
+
+            match character.get_equipment_attack_bonus(&npc.tags) {
+                None => {
+                    println!("{} ({}) no puede atacar porque no tiene un arma equipada.\n",
+                             character.name, character.class);
+                    continue;
+                },
+                Some(equipment_bonus) => {
+                    let attack_roll = dice.roll_1d6() as i32;
+                    let class_bonus = character.get_class_attack_bonus(enemies_outnumbered, &npc.tags) + character.companion_attack_bonus();
+                    let load_penalty = if *is_encumbered { 1 } else { 0 };
+                    // A oscuras se lucha a ciegas, salvo quien ve en la oscuridad
+                    let dark_penalty = if in_darkness && !character.has_darkvision() { 1 } else { 0 };
+                    let attack_total = attack_roll + class_bonus + equipment_bonus - load_penalty - dark_penalty;
+
+                    let mut penalties = String::new();
+                    if *is_encumbered {
+                        penalties.push_str(&format!(" - {} (sobrecarga)", load_penalty));
+                    }
+                    if dark_penalty > 0 {
+                        penalties.push_str(&format!(" - {} (oscuridad)", dark_penalty));
+                    }
+                    println!("{} (nivel {}) tira {} + {} + {}{} = {}\n",
+                             character.name, character.level, attack_roll, class_bonus, equipment_bonus, penalties, attack_total);
+
+                    if attack_roll == 1 {
+                        if let Some(weapon) = &character.weapon {
+                            if let Some(message) = character.wear_equipment(weapon.equipment_type.clone()) {
+                                println!("{}\n", message);
+                            }
+                        }
+                    }
+
+                    if attack_total >= npc.level as i32 {
+                        enemies_defeated += 1;
+                        println!("¡{} derrota a un {}!\n",
+                                 character.name, npc.base.tag);
+
+                        // Cada 6 en los dados de daño del arma derrota a otro enemigo del grupo
+                        for _ in 0..character.get_weapon_damage_dice() {
+                            if enemies_defeated < enemies_remaining && dice.roll_1d6() == 6 {
+                                enemies_defeated += 1;
+                                println!("¡El golpe de {} alcanza a otro {}!\n", character.name, npc.base.tag);
+                            }
+                        }
+                    } else {
+                        println!(
+                            "{} falla el ataque contra el {}.\n",
+                            character.name, npc.base.tag
+                        );
+                    }
+                }
+            }
+        }
+
+        let remaining_after_attack = enemies_remaining - enemies_defeated;
+
+        // Solo los enemigos que quedan pueden contraatacar
+        if remaining_after_attack > 0 {
+            println!("Contraataque de los enemigos:");
+            let enemies_that_can_attack = remaining_after_attack as usize;
+            let num_characters = self.living_characters();
+            let extra_enemies = if enemies_that_can_attack > num_characters {
+                enemies_that_can_attack - num_characters
+            } else {
+                0
+            };
+
+            let encumbered = self.encumbered_characters();
+            let living = self.characters.iter_mut().enumerate().filter(|(_, character)| character.is_alive());
+            for (target, (i, character)) in living.enumerate() {
+                let enemies_for_this_char = if target < extra_enemies {
+                    2
+                } else if target < enemies_that_can_attack {
+                    1
+                } else {
+                    0
+                };
+
+                for _ in 0..enemies_for_this_char {
+                    if !character.is_alive() {
+                        break;
+                    }
+                    let defense_roll = dice.roll_1d6() as i32;
+                    let equipment_defense_bonus = character.get_equipment_defense_bonus();
+                    let class_defense_bonus = character.get_class_defense_bonus(&npc.tags);
+                    let load_penalty = if encumbered[i] { 1 } else { 0 };
+                    let defense_total = defense_roll + equipment_defense_bonus + class_defense_bonus - load_penalty;
+
+                    if encumbered[i] {
+                        println!(
+                            "{} se defiende con {} + {} + {} - {} (sobrecarga) = {} contra el {}.\n",
+                            character.name, defense_roll, equipment_defense_bonus, class_defense_bonus, load_penalty, defense_total, npc.base.tag
+                        );
+                    } else {
+                        println!(
+                            "{} se defiende con {} + {} + {} = {} contra el {}.\n",
+                            character.name, defense_roll, equipment_defense_bonus, class_defense_bonus, defense_total, npc.base.tag
+                        );
+                    }
+
+                    if defense_roll == 1 {
+                        character.take_damage(1);
+                        println!(
+                            "¡Fallo crítico! {} recibe 1 punto de daño.\n",
+                            character.name
+                        );
+                        if let Some(message) = character.wear_equipment(EquipmentType::Armor(ArmorType::Light)) {
+                            println!("{}\n", message);
+                        }
+                    } else if defense_total > npc.level as i32 || defense_roll == 6 {
+                        println!(
+                            "{} esquiva el ataque del {}.\n",
+                            character.name, npc.base.tag
+                        );
+                    } else {
+                        character.take_damage(1);
+                        println!(
+                            "{} recibe 1 punto de daño del {}.\n",
+                            character.name, npc.base.tag
+                        );
+                    }
+
+                    if !character.is_alive() {
+                        println!("¡{} ha caído en combate!\n", character.name);
+                    }
+                }
+            }
+
+            // Mostrar el estado actual del grupo
+            println!("Estado del grupo después del contraataque:");
+            for character in &self.characters {
+                println!(
+                    "{} ({}, nivel {}): {} PV/{} PV\n",
+                    character.name,
+                    character.class,
+                    character.level,
+                    character.hit_points,
+                    character.max_hit_points
+                );
+            }
+        }
+
+        remaining_after_attack
+    }
+
+    // Al alcanzar el umbral de 10 encuentros el grupo no sigue hasta elegir quién sube de nivel
+    fn check_level_up(&mut self) {
+        if self.encounters_won < 10 || matches!(self.mode, GameMode::Combat(_) | GameMode::LevelUp) {
+            return;
+        }
+        println!("¡Has alcanzado 10 encuentros superados! Debes subir de nivel a un personaje para continuar.");
+        if self.level_up_candidates().is_empty() {
+            println!("No hay personajes disponibles para subir de nivel.");
+            self.encounters_won = 0;
+            return;
+        }
+        self.mode = GameMode::LevelUp;
+        self.show_level_up_menu();
+    }
+
+    // Todos menos el último que subió de nivel, que solo repite si los demás ya están en nivel 5
+    fn level_up_candidates(&self) -> Vec<usize> {
+        (0..self.characters.len())
+            .filter(|&index| self.leveled_up_last_time.as_ref() != Some(&self.characters[index].name))
+            .collect()
+    }
+
+    fn show_level_up_menu(&self) {
+        println!("Personajes disponibles para subir de nivel:");
+        for (number, &index) in self.level_up_candidates().iter().enumerate() {
+            let character = &self.characters[index];
+            println!("{}. {} (nivel {})", number + 1, character.name, character.level);
+        }
+        println!("Escribe el nombre del personaje que quieres subir de nivel, o su número:");
+    }
+
+    // Sube de nivel al personaje elegido, por su nombre o su número en la lista
+    pub fn execute_level_up(&mut self, choice: &str, dice: &mut dyn DiceRoller) -> bool {
+        if self.mode != GameMode::LevelUp {
+            println!("Ahora mismo nadie puede subir de nivel.");
+            return false;
+        }
+        let choice = choice.trim().to_lowercase();
+        let index = match choice.parse::<usize>() {
+            Ok(number) => number.checked_sub(1).and_then(|number| self.level_up_candidates().get(number).copied()),
+            Err(_) => self.characters.iter().position(|character| character.name.to_lowercase() == choice),
+        };
+        let index = match index {
+            Some(index) => index,
+            None => {
+                println!("No se encontró ningún personaje con ese nombre. Inténtalo de nuevo.");
+                self.show_level_up_menu();
+                return false;
+            }
+        };
+
+        // Verificar si el personaje ya subió de nivel y si los demás están en nivel 5
+        let repeats = self.leveled_up_last_time.as_ref() == Some(&self.characters[index].name);
+        let others_at_max = self.characters.iter().enumerate()
+            .filter(|(other, _)| *other != index)
+            .all(|(_, other)| other.level >= 5);
+        if repeats && !others_at_max {
+            println!("Este personaje ya ha subido de nivel anteriormente. Por favor, elige otro personaje.");
+            self.show_level_up_menu();
+            return false;
+        }
+
+        let character = &mut self.characters[index];
+        let level_roll = dice.roll_1d6();
+        if level_roll > character.level as u8 {
+            character.level += 1;
+            character.max_hit_points += 1;
+            character.hit_points = character.max_hit_points;
+            self.leveled_up_last_time = Some(character.name.clone());
+            println!("¡{} ha subido al nivel {}!", character.name, character.level);
+        } else {
+            println!("¡{} no ha subido de nivel! ¡Más suerte la próxima vez!", character.name);
+        }
+        self.encounters_won = 0;
+        self.mode = GameMode::Exploration;
+        true
+    }
+
+    pub fn has_hostile_npcs(&self) -> bool {
+        if let Some(location_tag) = &self.current_location {
+            if let Some(location) = find_location(location_tag) {
+                return self.npcs_at(&location.base.tag).iter()
+                    .any(|npc| self.attitude_of(npc) == Attitude::Hostile);
+            }
+        }
+        false
+    }
+
+    // Devuelve el índice del personaje y el resto de argumentos (tipo de equipo u objeto)
+    fn parse_equipment_args<'a>(&self, args: &[&'a str]) -> Option<(usize, &'a str)> {
+        if args.len() == 1 {
+            return Some((0, args[0]));
+        }
+        // Buscar el personaje por nombre
+        if let Some(index) = self.find_character(args[0]) {
+            Some((index, args[1]))
+        } else {
+            println!("No se encontró ningún personaje con ese nombre.");
+            println!("\nPersonajes disponibles:");
+            for character in &self.characters {
+                println!("- {} ({})", character.name, character.class);
+            }
+            None
+        }
+    }
+
+    fn parse_equipment_slot(word: &str) -> Option<EquipmentType> {
+        match word.to_lowercase().as_str() {
+            "arma" | "weapon" => Some(EquipmentType::Weapon(WeaponType::Medium)),
+            "escudo" | "shield" => Some(EquipmentType::Shield),
+            "armadura" | "armor" => Some(EquipmentType::Armor(ArmorType::Light)),
+            "arco" | "bow" => Some(EquipmentType::Bow),
+            _ => None,
+        }
+    }
+
+    pub fn execute_equip(&mut self, args: &[&str]) -> bool {
+        if args.is_empty() {
+            println!("¿Qué quieres equipar?");
+            println!("Comandos válidos:");
+            println!("- equipar [nombre_personaje] [tipo_equipo|objeto]");
+            println!("- equipar [tipo_equipo|objeto]");
+            println!("\nTipos de equipo disponibles:");
+            println!("- arma");
+            println!("- escudo");
+            println!("- armadura");
+            println!("- arco");
+            return false;
+        }
+
+        let (character_index, target) = match self.parse_equipment_args(args) {
+            Some(parsed) => parsed,
+            None => return false,
+        };
+
+        // El objetivo puede ser el tag de un objeto concreto o un tipo de equipo
+        let slot = Self::parse_equipment_slot(target);
+        let matches = |item: &Item| {
+            item.is_equipment && match &slot {
+                Some(slot) => item.equipment_type.as_ref().is_some_and(|et| et.same_slot(slot)),
+                None => item.base.tag == target,
+            }
+        };
+
+        // Primero en la mochila del propio personaje; si no, otro compañero se lo pasa
+        let holder = std::iter::once(character_index)
+            .chain(0..self.characters.len())
+            .find(|&index| self.characters[index].pack.iter().any(matches));
+
+        if let Some(holder) = holder {
+            let item_index = self.characters[holder].pack.iter().position(matches).unwrap();
+            let item = self.characters[holder].pack.remove(item_index);
+            if holder != character_index {
+                println!("{} le pasa {} a {}.", self.characters[holder].name, item.base.description, self.characters[character_index].name);
+            }
+            let character = &mut self.characters[character_index];
+            if let Some(equipment) = item.to_equipment() {
+                match character.equip(equipment) {
+                    Ok(Some(unequipped)) => character.pack.push(Item::from_equipment(unequipped)),
+                    Ok(None) => (),
+                    Err(reason) => {
+                        println!("{}", reason);
+                        self.characters[holder].pack.push(item);
+                        return false;
+                    }
+                }
+                println!("{} se equipa con {}.", character.name, item.base.description);
+            }
+            true
+        } else {
+            println!("No tienes ese objeto en tu inventario.");
+            println!("\nObjetos equipables disponibles:");
+            for item in self.characters.iter().flat_map(|c| c.pack.iter()).filter(|i| i.is_equipment) {
+                if let Some(et) = &item.equipment_type {
+                    match et {
+                        EquipmentType::Basic => println!("- {} [{}] (objeto básico)", item.base.description, item.base.tag),
+                        EquipmentType::Bow => println!("- {} [{}] (arco)", item.base.description, item.base.tag),
+                        EquipmentType::Weapon(_) => println!("- {} [{}] (arma)", item.base.description, item.base.tag),
+                        EquipmentType::Shield => println!("- {} [{}] (escudo)", item.base.description, item.base.tag),
+                        EquipmentType::Armor(_) => println!("- {} [{}] (armadura)", item.base.description, item.base.tag),
+                    }
+                }
+            }
+            false
+        }
+    }
+
+    pub fn execute_unequip(&mut self, args: &[&str]) -> bool {
+        if args.is_empty() {
+            println!("¿Qué quieres desequipar?");
+            println!("Comandos válidos:");
+            println!("- desequipar [nombre_personaje] [tipo_equipo]");
+            println!("- desequipar [tipo_equipo]");
+            println!("\nTipos de equipo disponibles:");
+            println!("- arma");
+            println!("- escudo");
+            println!("- armadura");
+            println!("- arco");
+            return false;
+        }
+
+        let (character_index, target) = match self.parse_equipment_args(args) {
+            Some(parsed) => parsed,
+            None => return false,
+        };
+
+        let equipment_type = match Self::parse_equipment_slot(target) {
+            Some(equipment_type) => equipment_type,
+            None => {
+                println!("Tipo de equipo no válido.");
+                println!("Tipos disponibles: arma, escudo, armadura, arco");
+                return false;
+            }
+        };
+
+        let character = &mut self.characters[character_index];
+        if let Some(equipment) = character.unequip(equipment_type) {
+            println!("{} se quita {} y lo guarda en su mochila.", character.name, equipment.name());
+            character.pack.push(Item::from_equipment(equipment));
+            true
+        } else {
+            println!("No tienes ese tipo de equipo equipado.");
+            false
+        }
+    }
+
+    // Comerciante con tienda en la ubicación actual
+    fn merchant_here(&self) -> Option<(&'static NPC, &'static Shop)> {
+        self.npcs_at(self.current_location.as_ref()?).into_iter()
+            .filter(|npc| npc.has_tag(&NPCTag::Merchant))
+            .find_map(|npc| find_shop(&npc.base.tag).map(|shop| (npc, shop)))
+    }
+
+    // La mercancía se repone por completo cada cierto número de turnos
+    fn restock_if_needed(&mut self, shop: &Shop) {
+        let restocked_at = self.shop_restocked_at.get(&shop.npc_tag).copied();
+        let needs_restock = match restocked_at {
+            Some(turn) => self.turns.saturating_sub(turn) >= shop.restock_turns,
+            None => true,
+        };
+        if needs_restock {
+            if restocked_at.is_some() {
+                println!("El comerciante ha repuesto su mercancía.");
+            }
+            self.shop_stock.insert(shop.npc_tag.clone(), shop.full_stock());
+            self.shop_restocked_at.insert(shop.npc_tag.clone(), self.turns);
+        }
+    }
+
+    fn open_shop(&mut self) -> Option<(&'static NPC, &'static Shop, Attitude)> {
+        let (npc, shop) = match self.merchant_here() {
+            Some(found) => found,
+            None => {
+                println!("No hay ningún comerciante aquí.");
+                return None;
+            }
+        };
+        let attitude = self.attitude_of(npc);
+        if attitude == Attitude::Hostile {
+            println!("{} se niega a comerciar contigo.", npc.base.description);
+            return None;
+        }
+        self.restock_if_needed(shop);
+        Some((npc, shop, attitude))
+    }
+
+    pub fn execute_list_wares(&mut self) -> bool {
+        let (npc, shop, attitude) = match self.open_shop() {
+            Some(opened) => opened,
+            None => return false,
+        };
+
+        println!("Mercancía de {}:", npc.base.description);
+        let stock = &self.shop_stock[&shop.npc_tag];
+        let mut has_wares = false;
+        for (item_tag, _) in &shop.stock {
+            let quantity = stock.get(item_tag).copied().unwrap_or(0);
+            if let (Some(item), true) = (find_item(item_tag), quantity > 0) {
+                if let Some(price) = Shop::sell_price(item.value, &attitude) {
+                    println!("- {} [{}]: {} de oro (quedan {})", item.base.description, item_tag, price, quantity);
+                    has_wares = true;
+                }
+            }
+        }
+        if !has_wares {
+            println!("No le queda nada que vender. Vuelve más tarde.");
+        }
+        println!("Tienes {} de oro.", self.gold);
+        true
+    }
+
+    pub fn execute_buy(&mut self, item_tag: &str, character_name: Option<&str>) -> bool {
+        let (_, shop, attitude) = match self.open_shop() {
+            Some(opened) => opened,
+            None => return false,
+        };
+
+        let quantity = self.shop_stock[&shop.npc_tag].get(item_tag).copied().unwrap_or(0);
+        let item = match (spawn_item(item_tag), quantity > 0) {
+            (Some(item), true) => item,
+            _ => {
+                println!("El comerciante no tiene ese objeto a la venta.");
+                return false;
+            }
+        };
+        let price = match Shop::sell_price(item.value, &attitude) {
+            Some(price) => price,
+            None => return false,
+        };
+        if self.gold < price {
+            println!("No tienes suficiente oro: {} cuesta {} y tienes {}.", item.base.description, price, self.gold);
+            return false;
+        }
+        let bearer = match self.choose_bearer(character_name) {
+            Some(bearer) => bearer,
+            None => return false,
+        };
+
+        self.gold -= price;
+        if let Some(stock) = self.shop_stock.get_mut(&shop.npc_tag) {
+            stock.insert(item_tag.to_string(), quantity - 1);
+        }
+        let character = &mut self.characters[bearer];
+        println!("Compras {} por {} de oro. {} lo guarda en su mochila.", item.base.description, price, character.name);
+        character.pack.push(item);
+        self.warn_if_encumbered();
+        true
+    }
+
+    pub fn execute_sell(&mut self, item_tag: &str) -> bool {
+        let (_, shop, attitude) = match self.open_shop() {
+            Some(opened) => opened,
+            None => return false,
+        };
+
+        let holder = self.characters.iter().position(|character| character.pack.iter().any(|item| item.base.tag == item_tag));
+        let holder = match holder {
+            Some(holder) => holder,
+            None => {
+                if self.has_item(item_tag) {
+                    println!("Tienes que desequipar ese objeto antes de venderlo.");
+                } else {
+                    println!("No tienes ese objeto en tu inventario.");
+                }
+                return false;
+            }
+        };
+
+        let item = self.characters[holder].take_from_pack(item_tag).unwrap();
+        let price = Shop::buy_price(item.value, &attitude).unwrap_or(0);
+        self.gold += price;
+        println!("Vendes {} por {} de oro. Ahora tienes {}.", item.base.description, price, self.gold);
+
+        // Lo que está en el catálogo pasa a formar parte de la mercancía hasta la próxima reposición
+        if find_item(item_tag).is_some() {
+            if let Some(stock) = self.shop_stock.get_mut(&shop.npc_tag) {
+                *stock.entry(item_tag.to_string()).or_insert(0) += 1;
+            }
+        }
+        true
+    }
+
+    fn inn_here(&self) -> Option<&'static Inn> {
+        let inn = find_inn(self.current_location.as_ref()?)?;
+        if self.defeated_npcs.contains(&inn.innkeeper) {
+            return None;
+        }
+        Some(inn)
+    }
+
+    fn open_inn(&self) -> Option<&'static Inn> {
+        let inn = self.inn_here();
+        if inn.is_none() {
+            println!("Aquí no hay ninguna posada.");
+        }
+        inn
+    }
+
+    pub fn execute_rest(&mut self) -> bool {
+        let inn = match self.open_inn() {
+            Some(inn) => inn,
+            None => return false,
+        };
+
+        let price = inn.rest_price * self.living_characters() as u32;
+        if self.gold < price {
+            println!("No tienes suficiente oro: una noche para el grupo cuesta {} y tienes {}.", price, self.gold);
+            return false;
+        }
+
+        self.gold -= price;
+        // Solo los vivos se benefician del descanso
+        for character in self.characters.iter_mut().filter(|character| character.is_alive()) {
+            character.rest();
+        }
+        println!("Pagáis {} de oro y pasáis la noche en la posada. El grupo recupera todos sus puntos de vida y hechizos.", price);
+        // Se despiertan al amanecer del día siguiente
+        self.turns = (self.turns / TURNS_PER_DAY + 1) * TURNS_PER_DAY;
+        println!("Os quedan {} de oro.", self.gold);
+        self.change_companion_loyalty(1);
+        true
+    }
+
+    pub fn execute_rumors(&mut self) -> bool {
+        let inn = match self.open_inn() {
+            Some(inn) => inn,
+            None => return false,
+        };
+
+        match inn.rumor(self.rumors_heard) {
+            Some(rumor) => {
+                println!("La {} baja la voz: \"{}\"", inn.innkeeper, rumor);
+                self.rumors_heard += 1;
+                true
+            }
+            None => {
+                println!("Hoy no corre ningún rumor por la posada.");
+                false
+            }
+        }
+    }
+
+    // Un nuevo aventurero ocupa el lugar del primer personaje caído y hereda sus pertenencias
+    pub fn execute_hire(&mut self, input: &str) -> bool {
+        let inn = match self.open_inn() {
+            Some(inn) => inn,
+            None => return false,
+        };
+
+        let fallen = match self.characters.iter().position(|character| !character.is_alive() && !character.is_companion()) {
+            Some(fallen) => fallen,
+            None => {
+                println!("No necesitas contratar a nadie: todo el grupo sigue con vida.");
+                return false;
+            }
+        };
+        if self.gold < inn.hire_price {
+            println!("No tienes suficiente oro: contratar a un aventurero cuesta {} y tienes {}.", inn.hire_price, self.gold);
+            return false;
+        }
+
+        let (fallen_character, inherited) = match self.replace_character(fallen, input) {
+            Some(replaced) => replaced,
+            None => return false,
+        };
+
+        self.gold -= inn.hire_price;
+        let hired = &self.characters[fallen];
+        println!("Pagas {} de oro y {} ({}) se une al grupo en lugar de {}.", inn.hire_price, hired.name, hired.class, fallen_character.name);
+        if inherited > 0 {
+            println!("{} recoge las pertenencias de {}.", hired.name, fallen_character.name);
+        }
+        self.warn_if_encumbered();
+        true
+    }
+
+    // Un aventurero veterano puede retirarse en la posada; otro de nivel 1 ocupa su puesto
+    pub fn execute_retire(&mut self, name: &str, replacement: &str) -> bool {
+        if self.open_inn().is_none() {
+            return false;
+        }
+        let index = match self.find_character(name) {
+            Some(index) if self.characters[index].is_companion() => {
+                println!("Los compañeros no se retiran: si ya no los necesitas, despídelos.");
+                return false;
+            }
+            Some(index) => index,
+            None => {
+                println!("No se encontró ningún personaje llamado '{}'.", name);
+                return false;
+            }
+        };
+
+        let (retired, inherited) = match self.replace_character(index, replacement) {
+            Some(replaced) => replaced,
+            None => return false,
+        };
+        let newcomer = &self.characters[index];
+        println!("{} se retira a descansar en la posada. {} ({}) ocupa su lugar en el grupo.", retired.name, newcomer.name, newcomer.class);
+        if inherited > 0 {
+            println!("{} recibe el equipo de {}.", newcomer.name, retired.name);
+        }
+        self.warn_if_encumbered();
+        true
+    }
+
+    // Un aventurero nuevo ocupa el puesto indicado y hereda las pertenencias de quien deja el grupo.
+    // Devuelve el personaje sustituido y cuántos objetos ha heredado el nuevo
+    fn replace_character(&mut self, index: usize, input: &str) -> Option<(Character, usize)> {
+        let existing_names: HashSet<String> = self.characters.iter()
+            .enumerate()
+            .filter(|(i, _)| *i != index)
+            .map(|(_, c)| c.name.clone())
+            .collect();
+        let mut newcomer = match parse_new_character(input.to_string(), &existing_names) {
+            Ok(character) => character,
+            Err(e) => {
+                println!("{}", e);
+                return None;
+            }
+        };
+
+        // Todo aventurero llega con una daga propia
+        if let Some(dagger) = spawn_item("daga") {
+            if !matches!(dagger.to_equipment().map(|equipment| newcomer.equip(equipment)), Some(Ok(_))) {
+                newcomer.pack.push(dagger);
+            }
+        }
+
+        let mut replaced = std::mem::replace(&mut self.characters[index], newcomer);
+        let belongings: Vec<Item> = [
+            replaced.weapon.take(),
+            replaced.shield.take(),
+            replaced.armor.take(),
+            replaced.bow.take(),
+        ].into_iter()
+            .flatten()
+            .map(Item::from_equipment)
+            .chain(replaced.pack.drain(..))
+            .collect();
+        let inherited = belongings.len();
+        self.characters[index].pack.extend(belongings);
+        Some((replaced, inherited))
+    }
+
+    pub fn execute_sheet(&self, name: &str) -> bool {
+        let character = match self.find_character(name) {
+            Some(index) => &self.characters[index],
+            None => {
+                println!("No se encontró ningún personaje llamado '{}'.", name);
+                return false;
+            }
+        };
+
+        println!("Ficha de {}", character.name);
+        println!("=================");
+        println!("Clase: {}    Nivel: {}", character.class, character.level);
+        println!("PV: {}/{}", character.hit_points, character.max_hit_points);
+        if character.max_spell_slots() > 0 {
+            println!("Hechizos: {}/{}", character.spell_slots, character.max_spell_slots());
+        }
+        for effect in &character.status_effects {
+            println!("Estado: {}", effect);
+        }
+        if let Some(companion) = &character.companion {
+            println!("Compañero {}: lealtad {}/{}", companion.behaviour, companion.loyalty, MAX_LOYALTY);
+        }
+        let mut traits: Vec<String> = character.traits.iter().map(|t| t.to_string()).collect();
+        traits.sort();
+        println!("Rasgos: {}", traits.join(", "));
+
+        println!("Equipo:");
+        for (slot, equipment) in [("Arma", &character.weapon), ("Escudo", &character.shield), ("Armadura", &character.armor), ("Arco", &character.bow)] {
+            match equipment {
+                Some(equipment) => println!("- {}: {} [{}]", slot, equipment.name(), equipment.item.stats.summary()),
+                None => println!("- {}: nada", slot),
+            }
+        }
+        println!("Carga: {}/{}{}", character.load(), character.class.carrying_capacity(),
+                 if character.is_encumbered() { " (sobrecargado)" } else { "" });
+        println!("{}", character.proficiency_rules());
+
+        println!("Bonificaciones (ataque / defensa):");
+        for tag in [NPCTag::Goblin, NPCTag::Orc, NPCTag::Undead, NPCTag::Vermin, NPCTag::Troll] {
+            let (attack, defense) = character.bonuses_against(&tag);
+            let attack = attack.map_or("sin arma".to_string(), |attack| format!("{:+}", attack));
+            println!("- contra {}: {} / {:+}", tag, attack, defense);
+        }
+        true
+    }
+
+    // Los nombres indicados pasan al frente en ese orden; el resto les sigue como estaba
+    pub fn execute_reorder(&mut self, names: &[&str]) -> bool {
+        let mut front = Vec::new();
+        for name in names {
+            match self.find_character(name) {
+                Some(index) if !front.contains(&index) => front.push(index),
+                Some(_) => {
+                    println!("{} aparece más de una vez.", name);
+                    return false;
+                }
+                None => {
+                    println!("No se encontró ningún personaje llamado '{}'.", name);
+                    return false;
+                }
+            }
+        }
+
+        let order: Vec<usize> = front.iter().copied()
+            .chain((0..self.characters.len()).filter(|index| !front.contains(index)))
+            .collect();
+        let mut characters: Vec<Option<Character>> = self.characters.drain(..).map(Some).collect();
+        self.characters = order.iter().filter_map(|index| characters[*index].take()).collect();
+
+        let names: Vec<&str> = self.characters.iter().map(|character| character.name.as_str()).collect();
+        println!("Orden de marcha: {}", names.join(", "));
+        true
+    }
+
+    pub fn execute_rename(&mut self, name: &str, new_name: &str) -> bool {
+        let index = match self.find_character(name) {
+            Some(index) => index,
+            None => {
+                println!("No se encontró ningún personaje llamado '{}'.", name);
+                return false;
+            }
+        };
+        let new_name = new_name.trim();
+        let other_names: HashSet<String> = self.characters.iter().enumerate()
+            .filter(|(i, _)| *i != index)
+            .map(|(_, c)| c.name.clone())
+            .collect();
+        if let Err(reason) = Character::validate_name(new_name, &other_names) {
+            println!("{}.", reason);
+            return false;
+        }
+        let character = &mut self.characters[index];
+        println!("{} pasa a llamarse {}.", character.name, new_name);
+        character.set_name(new_name.to_string(), &other_names);
+        true
+    }
+
+    // NPC presente en la ubicación actual con el que se puede interactuar
+    fn npc_here(&self, npc_tag: &str) -> Option<&'static NPC> {
+        self.npcs_at(self.current_location.as_ref()?).into_iter()
+            .find(|npc| npc.base.tag == npc_tag)
+    }
+
+    // NPCs que siguen en la ubicación: ni derrotados ni unidos al grupo
+    pub fn npcs_at(&self, location_tag: &str) -> Vec<&'static NPC> {
+        find_location(location_tag)
+            .map(|location| location.content.npcs.iter()
+                .chain(self.spawned_npcs.iter()
+                    .filter(|(_, spawned_at)| **spawned_at == location.base.tag)
+                    .map(|(tag, _)| tag))
+                .filter(|tag| !self.defeated_npcs.contains(*tag) && !self.recruited_npcs.contains(*tag))
+                .filter_map(|tag| find_npc(tag))
+                .collect())
+            .unwrap_or_default()
+    }
+
+    // Sin NPC delante (al cruzar un pasaje) no hay actitud que comprobar
+    fn meets_condition(&self, npc: Option<&NPC>, condition: &Condition) -> bool {
+        match condition {
+            Condition::HasItem(item_tag) => self.has_item(item_tag),
+            Condition::Flag(flag) => self.flags.contains(flag),
+            Condition::NotFlag(flag) => !self.flags.contains(flag),
+            Condition::Defeated(npc_tag) => self.defeated_npcs.contains(npc_tag),
+            Condition::Attitude(attitude) => npc.is_some_and(|npc| &self.attitude_of(npc) == attitude),
+            Condition::QuestAvailable(quest_tag) => !self.active_quests.contains(quest_tag) && !self.completed_quests.contains(quest_tag),
+            Condition::QuestActive(quest_tag) => self.active_quests.contains(quest_tag),
+            Condition::QuestCompleted(quest_tag) => self.completed_quests.contains(quest_tag),
+            Condition::Gold(amount) => self.gold >= *amount,
+        }
+    }
+
+    // Respuestas del nodo actual cuyas condiciones se cumplen, en el orden en que se muestran
+    fn available_responses(&self, npc: &'static NPC, node_id: &str) -> Vec<&'static Response> {
+        npc.dialogue_tree.as_ref()
+            .and_then(|tree| tree.node(node_id))
+            .map(|node| node.responses.iter()
+                .filter(|response| response.conditions.iter().all(|condition| self.meets_condition(Some(npc), condition)))
+                .collect())
+            .unwrap_or_default()
+    }
+
+    fn show_dialogue_node(&mut self, npc: &'static NPC, node_id: &str) {
+        let node = match npc.dialogue_tree.as_ref().and_then(|tree| tree.node(node_id)) {
+            Some(node) => node,
+            None => {
+                self.end_dialogue();
+                return;
+            }
+        };
 
-                println!("¡Combate terminado! Has derrotado a {} enemigos en total.", total_enemies_defeated);
+        println!("{}: \"{}\"", npc.base.tag, node.text);
+        let responses = self.available_responses(npc, node_id);
+        if responses.is_empty() {
+            self.end_dialogue();
+            return;
+        }
+        for (i, response) in responses.iter().enumerate() {
+            println!("{}. {}", i + 1, response.text);
+        }
+        self.mode = GameMode::Dialogue { npc: npc.base.tag.clone(), node: node_id.to_string() };
+    }
 
-                // Verificar si se ha alcanzado el umbral de 10 encuentros
-                if self.encounters_won >= 10 {
-                    let mut input_reader = StdInputReader;
-                    self.handle_level_up(&mut input_reader, &mut RealDiceRoller);
+    fn apply_effect(&mut self, npc: &NPC, effect: &Effect) {
+        match effect {
+            Effect::GiveItem(item_tag) => {
+                if let (Some(item), Some(bearer)) = (spawn_item(item_tag), self.choose_bearer(None)) {
+                    let character = &mut self.characters[bearer];
+                    println!("{} recibe {}.", character.name, item.base.description);
+                    character.pack.push(item);
+                    self.warn_if_encumbered();
                 }
             }
+            Effect::SetFlag(flag) => {
+                self.flags.insert(flag.clone());
+            }
+            Effect::SetAttitude(attitude) => {
+                self.npc_attitudes.insert(npc.base.tag.clone(), attitude.clone());
+            }
+            Effect::StartQuest(quest_tag) => {
+                self.start_quest(quest_tag);
+            }
+            Effect::PayGold(amount) => {
+                let paid = (*amount).min(self.gold);
+                self.gold -= paid;
+                println!("Entregáis {} de oro. Os quedan {}.", paid, self.gold);
+            }
+            Effect::ChangeReputation(faction, amount) => {
+                self.change_reputation(faction, *amount);
+            }
         }
     }
 
-    fn execute_combat_round(&mut self, npc: &NPC, enemies_remaining: u8, round: &mut u8) -> u8 {
-        *round += 1;
-        if round == &1 {
-            println!("¡Comienza el combate!");
+    pub fn start_quest(&mut self, quest_tag: &str) -> bool {
+        let quest = match find_quest(quest_tag) {
+            Some(quest) => quest,
+            None => return false,
+        };
+        if self.active_quests.contains(&quest.tag) || self.completed_quests.contains(&quest.tag) {
+            return false;
         }
+        self.active_quests.push(quest.tag.clone());
+        println!("Nueva misión: {}.", quest.title);
+        true
+    }
 
-        // Fase de ataque de los aventureros
-        println!("Ataque de los aventureros:");
-        let mut enemies_defeated = 0;
-        let enemies_outnumbered = self.characters.len() > enemies_remaining as usize;
-
-        for character in &mut self.characters {
-            if enemies_remaining == 0 || enemies_defeated == enemies_remaining {
-                break;
-            }
-
-            match character.get_equipment_attack_bonus() {
-                None => {
-                    println!("{} ({}) no puede atacar porque no tiene un arma equipada.\n",
-                             character.name, character.class);
-                    continue;
-                },
-                Some(equipment_bonus) => {
-                    let attack_roll = rand::thread_rng().gen_range(1..=6);
-                    let class_bonus = character.get_class_attack_bonus(enemies_outnumbered, &npc.tags);                    
-                    let attack_total = attack_roll + class_bonus + equipment_bonus as i32;
+    // Los objetos se entregan en mano: hay que estar junto a quien encargó la misión
+    fn objective_met(&self, quest: &Quest, objective: &Objective) -> bool {
+        match objective {
+            Objective::Defeat(npc_tag) => self.defeated_npcs.contains(npc_tag),
+            Objective::Fetch(item_tag) => self.has_item(item_tag) && self.npc_here(&quest.giver).is_some(),
+            Objective::Reach(location_tag) => self.visited_locations.contains(location_tag),
+            Objective::Puzzle(puzzle_tag) => self.solved_puzzles.contains(puzzle_tag),
+        }
+    }
 
-                    println!("{} (nivel {}) tira {} + {} + {} = {}\n",
-                             character.name, character.level, attack_roll, class_bonus, equipment_bonus, attack_total);
+    // Comprueba las misiones activas y entrega las recompensas de las que se han completado
+    pub fn update_quests(&mut self) {
+        let finished: Vec<&'static Quest> = self.active_quests.iter()
+            .filter_map(|quest_tag| QUESTS.get(quest_tag))
+            .filter(|quest| quest.objectives.iter().all(|objective| self.objective_met(quest, objective)))
+            .collect();
 
-                    if attack_total >= npc.level as i32 {
-                        enemies_defeated += 1;
-                        println!("¡{} derrota a un {}!\n",
-                                 character.name, npc.base.tag);
-                    } else {
-                        println!(
-                            "{} falla el ataque contra el {}.\n",
-                            character.name, npc.base.tag
-                        );
+        for quest in finished {
+            self.active_quests.retain(|quest_tag| quest_tag != &quest.tag);
+            self.completed_quests.push(quest.tag.clone());
+            println!("¡Misión completada: {}!", quest.title);
+
+            for objective in &quest.objectives {
+                if let Objective::Fetch(item_tag) = objective {
+                    if let Some(carrier) = self.carrier_of(item_tag) {
+                        let character = &mut self.characters[carrier];
+                        if let Some(item) = character.take_from_pack(item_tag) {
+                            println!("{} entrega {}.", character.name, item.base.description);
+                        }
                     }
                 }
             }
+            for reward in &quest.rewards {
+                self.grant_reward(reward);
+            }
+            self.change_companion_loyalty(1);
         }
 
-        let remaining_after_attack = enemies_remaining - enemies_defeated;
-
-        // Solo los enemigos que quedan pueden contraatacar
-        if remaining_after_attack > 0 {
-            println!("Contraataque de los enemigos:");
-            let enemies_that_can_attack = remaining_after_attack as usize;
-            let num_characters = self.characters.len();
-            let extra_enemies = if enemies_that_can_attack > num_characters {
-                enemies_that_can_attack - num_characters
-            } else {
-                0
-            };
-
-            for (i, character) in self.characters.iter_mut().enumerate() {
-                let enemies_for_this_char = if i < extra_enemies {
-                    2
-                } else if i < enemies_that_can_attack {
-                    1
-                } else {
-                    0
-                };
-
-                for _ in 0..enemies_for_this_char {
-                    let defense_roll = rand::thread_rng().gen_range(1..=6);
-                    let equipment_defense_bonus = character.get_equipment_defense_bonus();
-                    let class_defense_bonus = character.get_class_defense_bonus(&npc.tags);
-                    let defense_total = defense_roll + equipment_defense_bonus + class_defense_bonus;
-
-                    println!(
-                        "{} se defiende con {} + {} + {} = {} contra el {}.\n",
-                        character.name, defense_roll, equipment_defense_bonus, class_defense_bonus, defense_total, npc.base.tag
-                    );
+        self.check_level_up();
+    }
 
-                    if defense_roll == 1 {
-                        character.hit_points -= 1;
-                        println!(
-                            "¡Fallo crítico! {} recibe 1 punto de daño.\n",
-                            character.name
-                        );
-                    } else if defense_total > npc.level as i32 || defense_roll == 6 {
-                        println!(
-                            "{} esquiva el ataque del {}.\n",
-                            character.name, npc.base.tag
-                        );
-                    } else {
-                        character.hit_points -= 1;
-                        println!(
-                            "{} recibe 1 punto de daño del {}.\n",
-                            character.name, npc.base.tag
-                        );
-                    }
+    fn grant_reward(&mut self, reward: &Reward) {
+        match reward {
+            Reward::Gold(amount) => {
+                self.gold += amount;
+                println!("Recibís {} de oro.", amount);
+            }
+            Reward::Item(item_tag) => {
+                if let (Some(item), Some(bearer)) = (spawn_item(item_tag), self.choose_bearer(None)) {
+                    let character = &mut self.characters[bearer];
+                    println!("{} recibe {}.", character.name, item.base.description);
+                    character.pack.push(item);
+                    self.warn_if_encumbered();
                 }
             }
-
-            // Mostrar el estado actual del grupo
-            println!("Estado del grupo después del contraataque:");
-            for character in &self.characters {
-                println!(
-                    "{} ({}, nivel {}): {} PV/{} PV\n",
-                    character.name,
-                    character.class,
-                    character.level,
-                    character.hit_points,
-                    character.max_hit_points
-                );
+            Reward::Experience(amount) => {
+                self.encounters_won += amount;
+                println!("El grupo gana {} XP.", amount);
+            }
+            Reward::Reputation(faction, amount) => {
+                self.change_reputation(faction, *amount);
             }
         }
-
-        remaining_after_attack
     }
 
-    fn handle_level_up(&mut self, input_reader: &mut dyn InputReader, dice: &mut dyn DiceRoller) {
-        println!("¡Has alcanzado 10 encuentros superados! Debes subir de nivel a un personaje para continuar.");
-        loop {
-            println!("Personajes disponibles para subir de nivel:");
-            let available_characters: Vec<_> = self.characters.iter()
-                .filter(|c| {
-                    if let Some(last_leveled) = self.leveled_up_last_time.as_ref() {
-                        c.name != *last_leveled
-                    } else {
-                        true
-                    }
-                })
-                .collect();
+    pub fn execute_journal(&self) {
+        println!("Diario de misiones:");
+        println!("===================");
 
-            if available_characters.is_empty() {
-                println!("No hay personajes disponibles para subir de nivel.");
-                self.encounters_won = 0;
-                break;
+        if self.active_quests.is_empty() {
+            println!("No tienes misiones activas.");
+        }
+        for quest in self.active_quests.iter().filter_map(|quest_tag| find_quest(quest_tag)) {
+            println!("- {} (encargada por {})", quest.title, quest.giver);
+            println!("  {}", quest.description);
+            for objective in &quest.objectives {
+                let mark = if self.objective_met(quest, objective) { "x" } else { " " };
+                println!("  [{}] {}", mark, objective.describe());
             }
+            let rewards: Vec<String> = quest.rewards.iter().map(|reward| reward.describe()).collect();
+            println!("  Recompensa: {}", rewards.join(", "));
+        }
 
-            for character in &available_characters {
-                println!("- {} (nivel {})", character.name, character.level);
+        if !self.completed_quests.is_empty() {
+            println!("Misiones completadas:");
+            for quest in self.completed_quests.iter().filter_map(|quest_tag| find_quest(quest_tag)) {
+                println!("- {}", quest.title);
             }
+        }
+    }
 
-            println!("Escribe el nombre del personaje que quieres subir de nivel:");
-            let input = input_reader.read_line().trim().to_string();
+    pub fn execute_talk(&mut self, npc_tag: &str) -> bool {
+        if npc_tag.is_empty() {
+            println!("¿Con quién quieres hablar?");
+            return false;
+        }
+        let npc = match self.npc_here(npc_tag) {
+            Some(npc) => npc,
+            None => {
+                println!("No hay nadie llamado '{}' aquí.", npc_tag);
+                return false;
+            }
+        };
+        // Los hostiles solo atienden a razones si tienen algo que negociar
+        if self.attitude_of(npc) == Attitude::Hostile && npc.dialogue_tree.is_none() {
+            println!("El {} no parece dispuesto a hablar.", npc.base.tag);
+            return false;
+        }
 
-            // Verificar si el personaje ya subió de nivel y si los demás están en nivel 5
-            let can_level_up = if let Some(last_leveled) = self.leveled_up_last_time.as_ref() {
-                if input.to_lowercase() == last_leveled.to_lowercase() {
-                    // Verificar si todos los demás personajes están en nivel 5
-                    self.characters.iter()
-                        .filter(|other| other.name.to_lowercase() != input.to_lowercase())
-                        .all(|other| other.level >= 5)
-                } else {
-                    true
-                }
-            } else {
-                true
-            };
+        match &npc.dialogue_tree {
+            Some(tree) => self.show_dialogue_node(npc, &tree.start),
+            None => match npc.dialogue.first() {
+                Some(line) => println!("{}: \"{}\"", npc.base.tag, line),
+                None => println!("El {} no tiene nada que decir.", npc.base.tag),
+            },
+        }
+        true
+    }
 
-            if !can_level_up {
-                println!("Este personaje ya ha subido de nivel anteriormente. Por favor, elige otro personaje.");
-                continue;
+    pub fn execute_choice(&mut self, choice: u32) -> bool {
+        let (npc_tag, node_id) = match &self.mode {
+            GameMode::Dialogue { npc, node } => (npc.clone(), node.clone()),
+            _ => {
+                println!("No estás hablando con nadie.");
+                return false;
+            }
+        };
+        let npc = match find_npc(&npc_tag) {
+            Some(npc) => npc,
+            None => {
+                self.end_dialogue();
+                return false;
             }
+        };
 
-            if let Some(character) = self.characters.iter_mut()
-                .find(|c| c.name.to_lowercase() == input.to_lowercase()) {
-                // evaluate if the character levels up
-                let level_roll = dice.roll_1d6();
-                if level_roll > character.level as u8 {
-                    character.level += 1;
-                    character.max_hit_points += 1;
-                    character.hit_points = character.max_hit_points;
-                    self.leveled_up_last_time = Some(character.name.clone());
-                    println!("¡{} ha subido al nivel {}!", character.name, character.level);
-                } else {
-                    println!("¡{} no ha subido de nivel! ¡Más suerte la próxima vez!", character.name);
-                }
-                self.encounters_won = 0;
-                break;
-            } else {
-                println!("No se encontró ningún personaje con ese nombre. Inténtalo de nuevo.");
+        let responses = self.available_responses(npc, &node_id);
+        let response = match responses.get((choice as usize).wrapping_sub(1)) {
+            Some(response) => *response,
+            None => {
+                println!("Elige una de las respuestas numeradas.");
+                return false;
             }
-        }
-    }
+        };
 
-    pub fn has_hostile_npcs(&self) -> bool {
-        if let Some(location_tag) = &self.current_location {
-            if let Some(location) = find_location(location_tag) {
-                return !location.content.npcs.is_empty() && location.content.npcs.iter()
-                    .filter_map(|npc_tag| find_npc(npc_tag))
-                    .any(|npc| npc.attitude == Attitude::Hostile && !self.defeated_npcs.contains(&npc.base.tag));
+        for effect in &response.effects {
+            self.apply_effect(npc, effect);
+        }
+        match &response.next {
+            Some(next) => self.show_dialogue_node(npc, next),
+            None => {
+                self.end_dialogue();
+                println!("Termináis la conversación.");
             }
         }
-        false
+        true
     }
 
-    pub fn execute_equip(&mut self, args: &[&str]) -> bool {
-        if args.is_empty() {
-            println!("¿Qué quieres equipar?");
-            println!("Comandos válidos:");
-            println!("- equipar [nombre_personaje] [tipo_equipo]");
-            println!("- equipar [tipo_equipo]");
-            println!("\nTipos de equipo disponibles:");
-            println!("- arma (espada_inicial, hacha_inicial, daga_inicial)");
-            println!("- escudo (escudo_inicial)");
-            println!("- armadura (armadura_inicial)");
-            return false;
+    // Cambia la actitud del NPC un número de pasos respecto a la que tenga ahora
+    fn shift_attitude(&mut self, npc: &NPC, steps: i32) {
+        let attitude = self.npc_attitudes.get(&npc.base.tag).cloned().unwrap_or_else(|| npc.attitude.clone());
+        self.npc_attitudes.insert(npc.base.tag.clone(), attitude.shifted(steps));
+    }
+
+    // Atacar a quien no era hostil tiene consecuencias con todo su bando
+    fn provoke(&mut self, npc: &NPC) {
+        if self.attitude_of(npc) == Attitude::Hostile {
+            return;
         }
+        println!("¡Atacáis al {} sin previo aviso!", npc.base.tag);
+        self.npc_attitudes.insert(npc.base.tag.clone(), Attitude::Hostile);
+        self.change_companion_loyalty(-2);
+        if let Some(faction) = &npc.faction {
+            self.change_reputation(faction, -6);
+            println!("{} no olvidará esta traición.", faction);
+        }
+    }
 
-        let (character_index, equipment_type) = if args.len() == 1 {
-            (0, args[0])
-        } else {
-            // Buscar el personaje por nombre
-            if let Some(index) = self.characters.iter().position(|c| c.name.to_lowercase() == args[0].to_lowercase()) {
-                (index, args[1])
-            } else {
-                println!("No se encontró ningún personaje con ese nombre.");
-                println!("\nPersonajes disponibles:");
-                for character in &self.characters {
-                    println!("- {} ({})", character.name, character.class);
-                }
+    pub fn execute_social(&mut self, action: SocialAction, npc_tag: &str, offer: Option<u32>, dice: &mut dyn DiceRoller) -> bool {
+        let npc = match self.npc_here(npc_tag) {
+            Some(npc) => npc,
+            None => {
+                println!("No hay nadie llamado '{}' aquí.", npc_tag);
                 return false;
             }
         };
+        if npc.has_tag(&NPCTag::Vermin) || npc.has_tag(&NPCTag::Undead) {
+            println!("Los {} no atienden a razones.", npc.base.tag);
+            return false;
+        }
 
-        let equipment_type = match equipment_type.to_lowercase().as_str() {
-            "arma" | "weapon" => Some(EquipmentType::Weapon(WeaponType::Medium)),
-            "escudo" | "shield" => Some(EquipmentType::Shield),
-            "armadura" | "armor" => Some(EquipmentType::Armor(ArmorType::Light)),
-            _ => {
-                println!("Tipo de equipo no válido.");
-                println!("Tipos disponibles: arma, escudo, armadura");
-                None
+        // Un soborno necesita oro; cuanto más generoso, más fácil
+        let offer = offer.unwrap_or(5 * npc.level as u32);
+        if action == SocialAction::Bribe {
+            if offer == 0 {
+                println!("Tienes que ofrecer algo de oro.");
+                return false;
             }
-        };
+            if self.gold < offer {
+                println!("No tienes suficiente oro: ofreces {} y tienes {}.", offer, self.gold);
+                return false;
+            }
+        }
 
-        if let Some(equipment_type) = equipment_type {
-            let character = &mut self.characters[character_index];
-            let inventory = &mut self.inventory;
-
-            if let Some(item_index) = inventory.iter().position(|item| {
-                item.is_equipment && item.equipment_type.as_ref().map_or(false, |et| et == &equipment_type)
-            }) {
-                let item = inventory.remove(item_index);
-                if let Some(equipment) = item.to_equipment() {
-                    if let Some(unequipped) = character.equip(equipment) {
-                        inventory.push(Item::from_equipment(unequipped));
-                    }
+        // Habla en nombre del grupo quien mejor se le da
+        let speaker = match self.characters.iter()
+            .filter(|character| character.is_alive())
+            .max_by_key(|character| character.social_bonus(&action)) {
+            Some(speaker) => speaker,
+            None => return false,
+        };
+        let roll = dice.roll_1d6() as i32;
+        let class_bonus = speaker.social_bonus(&action);
+        let offer_bonus = if action == SocialAction::Bribe { (offer / 10).min(3) as i32 } else { 0 };
+        let total = roll + class_bonus + offer_bonus;
+        println!("{} intenta {} al {}: {} + {} + {} = {} contra nivel {}.",
+                 speaker.name, action, npc.base.tag, roll, class_bonus, offer_bonus, total, npc.level);
+
+        let success = roll != 1 && (roll == 6 || total > npc.level as i32);
+        match (&action, success) {
+            (SocialAction::Bribe, true) => {
+                self.gold -= offer;
+                println!("El {} acepta las {} monedas de oro.", npc.base.tag, offer);
+                self.shift_attitude(npc, 1);
+            }
+            (SocialAction::Bribe, false) => {
+                println!("El {} rechaza el soborno, ofendido.", npc.base.tag);
+                if let Some(faction) = &npc.faction {
+                    self.change_reputation(faction, -1);
                 }
-                true
-            } else {
-                println!("No tienes ese objeto en tu inventario.");
-                println!("\nObjetos equipables disponibles:");
-                for item in inventory.iter().filter(|i| i.is_equipment) {
-                    if let Some(et) = &item.equipment_type {
-                        match et {
-                            EquipmentType::Basic => println!("- {} (objeto básico)", item.base.description),
-                            EquipmentType::Bow => println!("- {} (arco)", item.base.description),
-                            EquipmentType::Weapon(_) => println!("- {} (arma)", item.base.description),
-                            EquipmentType::Shield => println!("- {} (escudo)", item.base.description),
-                            EquipmentType::Armor(_) => println!("- {} (armadura)", item.base.description),
-                        }
-                    }
+            }
+            (SocialAction::Intimidate, true) => {
+                println!("¡El {} huye despavorido!", npc.base.tag);
+                self.defeated_npcs.insert(npc.base.tag.clone());
+                if let Some(faction) = &npc.faction {
+                    self.change_reputation(faction, -1);
+                }
+            }
+            (SocialAction::Intimidate, false) => {
+                println!("El {} no se deja amedrentar y se prepara para pelear.", npc.base.tag);
+                self.shift_attitude(npc, -1);
+            }
+            (SocialAction::Persuade, true) => {
+                println!("El {} se deja convencer.", npc.base.tag);
+                self.shift_attitude(npc, 1);
+                if let Some(faction) = &npc.faction {
+                    self.change_reputation(faction, 1);
+                }
+            }
+            (SocialAction::Persuade, false) => {
+                println!("El {} no se deja convencer.", npc.base.tag);
+                if let Some(faction) = &npc.faction {
+                    self.change_reputation(faction, -1);
                 }
-                false
             }
-        } else {
-            false
         }
+        success
     }
 
-    pub fn execute_unequip(&mut self, args: &[&str]) -> bool {
-        if args.is_empty() {
-            println!("¿Qué quieres desequipar?");
-            println!("Comandos válidos:");
-            println!("- desequipar [nombre_personaje] [tipo_equipo]");
-            println!("- desequipar [tipo_equipo]");
-            println!("\nTipos de equipo disponibles:");
-            println!("- arma");
-            println!("- escudo");
-            println!("- armadura");
+    pub fn execute_recruit(&mut self, npc_tag: &str) -> bool {
+        let npc = match self.npc_here(npc_tag) {
+            Some(npc) => npc,
+            None => {
+                println!("No hay nadie llamado '{}' aquí.", npc_tag);
+                return false;
+            }
+        };
+        let recruitment = match &npc.recruitment {
+            Some(recruitment) => recruitment,
+            None => {
+                println!("El {} no tiene intención de unirse a vosotros.", npc.base.tag);
+                return false;
+            }
+        };
+        if self.attitude_of(npc) != Attitude::Friendly {
+            println!("El {} no confía lo bastante en vosotros para unirse al grupo.", npc.base.tag);
+            return false;
+        }
+        if self.characters.iter().filter(|character| character.is_companion()).count() >= MAX_COMPANIONS {
+            println!("No podéis llevar más de {} compañeros.", MAX_COMPANIONS);
+            return false;
+        }
+        if self.find_character(&recruitment.name).is_some() {
+            println!("Ya hay alguien llamado {} en el grupo.", recruitment.name);
+            return false;
+        }
+        if self.gold < recruitment.price {
+            println!("{} pide {} de oro y tienes {}.", recruitment.name, recruitment.price, self.gold);
             return false;
         }
 
-        let (character_index, equipment_type) = if args.len() == 1 {
-            (0, args[0])
-        } else {
-            // Buscar el personaje por nombre
-            if let Some(index) = self.characters.iter().position(|c| c.name.to_lowercase() == args[0].to_lowercase()) {
-                (index, args[1])
-            } else {
-                println!("No se encontró ningún personaje con ese nombre.");
-                println!("\nPersonajes disponibles:");
-                for character in &self.characters {
-                    println!("- {} ({})", character.name, character.class);
-                }
+        let mut companion = Character::new(recruitment.class.clone()).at_level(recruitment.level);
+        companion.name = recruitment.name.clone();
+        companion.companion = Some(Companion {
+            npc_tag: npc.base.tag.clone(),
+            loyalty: recruitment.loyalty,
+            behaviour: recruitment.behaviour.clone(),
+        });
+        for item in recruitment.equipment.iter().filter_map(|item_tag| spawn_item(item_tag)) {
+            if !matches!(item.to_equipment().map(|equipment| companion.equip(equipment)), Some(Ok(_))) {
+                companion.pack.push(item);
+            }
+        }
+
+        self.gold -= recruitment.price;
+        self.recruited_npcs.insert(npc.base.tag.clone());
+        println!("{} ({}, nivel {}) se une al grupo.", companion.name, companion.class, companion.level);
+        self.characters.push(companion);
+        true
+    }
+
+    pub fn execute_dismiss(&mut self, name: &str) -> bool {
+        let index = match self.find_character(name) {
+            Some(index) if self.characters[index].is_companion() => index,
+            Some(_) => {
+                println!("Solo puedes despedir a los compañeros que se han unido por el camino.");
+                return false;
+            }
+            None => {
+                println!("No se encontró ningún personaje llamado '{}'.", name);
                 return false;
             }
         };
+        println!("{} se despide del grupo.", self.characters[index].name);
+        self.remove_companion(index);
+        true
+    }
 
-        let equipment_type = match equipment_type.to_lowercase().as_str() {
-            "arma" | "weapon" => Some(EquipmentType::Weapon(WeaponType::Medium)),
-            "escudo" | "shield" => Some(EquipmentType::Shield),
-            "armadura" | "armor" => Some(EquipmentType::Armor(ArmorType::Light)),
-            _ => {
-                println!("Tipo de equipo no válido.");
-                println!("Tipos disponibles: arma, escudo, armadura");
-                None
+    // El compañero vuelve a su lugar de origen y deja en el suelo lo que el grupo le había dado,
+    // tanto en la mochila como equipado; solo se lleva el equipo con el que llegó
+    fn remove_companion(&mut self, index: usize) {
+        let mut companion = self.characters.remove(index);
+        let mut own_equipment = Vec::new();
+        if let Some(data) = companion.companion.take() {
+            self.recruited_npcs.remove(&data.npc_tag);
+            if let Some(recruitment) = find_npc(&data.npc_tag).and_then(|npc| npc.recruitment.as_ref()) {
+                own_equipment = recruitment.equipment.clone();
             }
-        };
+        }
 
-        if let Some(equipment_type) = equipment_type {
-            let character = &mut self.characters[character_index];
-            if let Some(equipment) = character.unequip(equipment_type) {
-                self.inventory.push(Item::from_equipment(equipment));
-                true
-            } else {
-                println!("No tienes ese tipo de equipo equipado.");
+        let equipped = [companion.weapon.take(), companion.shield.take(), companion.armor.take(), companion.bow.take()];
+        let mut left_behind: Vec<Item> = equipped.into_iter()
+            .flatten()
+            .map(|equipment| equipment.item)
+            .chain(companion.pack.drain(..))
+            .collect();
+        left_behind.retain(|item| match own_equipment.iter().position(|tag| *tag == item.base.tag) {
+            Some(own) => {
+                own_equipment.remove(own);
                 false
+            },
+            None => true,
+        });
+
+        if !left_behind.is_empty() {
+            if let Some(location_tag) = &self.current_location {
+                println!("{} deja sus cosas en el suelo.", companion.name);
+                self.dropped_items.entry(location_tag.clone()).or_default().append(&mut left_behind);
             }
-        } else {
-            false
         }
     }
 
-    pub fn execute_new(&mut self, input: &str) -> String {
-        if self.characters.len() >= 3 {
-            return "Ya tienes el máximo de personajes permitidos (3).".to_string();
+    // Cambia la lealtad de todos los compañeros; los que la pierden del todo se marchan
+    pub fn change_companion_loyalty(&mut self, amount: i32) {
+        let mut deserters = Vec::new();
+        for (index, character) in self.characters.iter_mut().enumerate() {
+            if !character.is_alive() {
+                continue;
+            }
+            if let Some(loyalty) = character.change_loyalty(amount) {
+                if amount < 0 {
+                    println!("A {} no le ha gustado nada (lealtad {}).", character.name, loyalty);
+                }
+                if loyalty <= 0 {
+                    deserters.push(index);
+                }
+            }
         }
+        for index in deserters.into_iter().rev() {
+            println!("{} ha perdido la confianza en vosotros y abandona el grupo.", self.characters[index].name);
+            self.remove_companion(index);
+        }
+    }
 
-        let existing_names: HashSet<String> = self.characters.iter()
-            .map(|c| c.name.clone())
-            .collect();
-
-        match parse_new_character(input.to_string(), &existing_names) {
-            Ok(character) => {
-                self.characters.push(character);
-                format!("¡Has creado un nuevo personaje! Ahora tienes {} personajes.", self.characters.len())
-            },
-            Err(e) => e
+    pub fn end_dialogue(&mut self) {
+        if matches!(self.mode, GameMode::Dialogue { .. }) {
+            self.mode = GameMode::Exploration;
         }
     }
 }
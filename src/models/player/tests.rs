@@ -1,7 +1,11 @@
 #[cfg(test)]
 mod tests {
-    use crate::models::player::{Player, TestInputReader, MockDiceRoller};
-    use crate::models::character::{Character, Class};
+    use crate::models::player::{Player, MockDiceRoller};
+    use crate::models::character::{Character, Class, EquipmentType, WeaponType, ArmorType, SocialAction};
+    use crate::models::object::{Item, Attitude, Faction, find_npc, spawn_item};
+    use crate::models::puzzle::find_puzzle;
+    use crate::models::mode::{GameMode, CombatAction};
+    use crate::models::map::{layout, render_ascii};
     use std::collections::HashSet;
 
     fn create_test_player() -> Player {
@@ -26,13 +30,15 @@ mod tests {
     fn test_handle_level_up_basic() {
         let mut player = create_test_player();
         player.encounters_won = 10;
-        let mut input_reader = TestInputReader::new("Aragorn
-".to_string());
+        player.check_level_up();
+        assert_eq!(player.mode, GameMode::LevelUp);
         let mut dice = MockDiceRoller { value: 6 };
-        player.handle_level_up(&mut input_reader, &mut dice);
+        assert!(player.execute_level_up("Aragorn", &mut dice));
         let aragorn = player.characters.iter().find(|c| c.name == "Aragorn").unwrap();
         assert_eq!(aragorn.level, 2);
         assert_eq!(player.leveled_up_last_time, Some("Aragorn".to_string()));
+        assert_eq!(player.encounters_won, 0);
+        assert_eq!(player.mode, GameMode::Exploration);
     }
 
     #[test]
@@ -45,10 +51,9 @@ mod tests {
             }
         }
         player.leveled_up_last_time = Some("Aragorn".to_string());
-        let mut input_reader = TestInputReader::new("Aragorn
-".to_string());
+        player.check_level_up();
         let mut dice = MockDiceRoller { value: 6 };
-        player.handle_level_up(&mut input_reader, &mut dice);
+        assert!(player.execute_level_up("aragorn", &mut dice));
         let aragorn = player.characters.iter().find(|c| c.name == "Aragorn").unwrap();
         assert_eq!(aragorn.level, 2);
     }
@@ -58,16 +63,33 @@ mod tests {
         let mut player = create_test_player();
         player.encounters_won = 10;
         player.leveled_up_last_time = Some("Aragorn".to_string());
-        let mut input_reader = TestInputReader::new("Gandalf
-".to_string());
+        player.check_level_up();
         let mut dice = MockDiceRoller { value: 6 };
-        player.handle_level_up(&mut input_reader, &mut dice);
+        // Aragorn repite antes de tiempo y la elección sigue pendiente
+        assert!(!player.execute_level_up("Aragorn", &mut dice));
+        assert_eq!(player.mode, GameMode::LevelUp);
+        assert!(player.execute_level_up("Gandalf", &mut dice));
         let gandalf = player.characters.iter().find(|c| c.name == "Gandalf").unwrap();
         assert_eq!(gandalf.level, 2);
         let aragorn = player.characters.iter().find(|c| c.name == "Aragorn").unwrap();
         assert_eq!(aragorn.level, 1);
     }
 
+    #[test]
+    fn test_level_up_by_number_and_failed_roll() {
+        let mut player = create_test_player();
+        player.encounters_won = 10;
+        player.leveled_up_last_time = Some("Aragorn".to_string());
+        player.check_level_up();
+        // La lista deja fuera a Aragorn: el 1 es Gandalf
+        assert!(!player.execute_level_up("7", &mut MockDiceRoller { value: 6 }));
+        assert!(player.execute_level_up("1", &mut MockDiceRoller { value: 1 }));
+        let gandalf = player.characters.iter().find(|c| c.name == "Gandalf").unwrap();
+        assert_eq!(gandalf.level, 1);
+        assert_eq!(player.encounters_won, 0);
+        assert_eq!(player.mode, GameMode::Exploration);
+    }
+
     #[test]
     #[should_panic(expected = "Ya existe un personaje con el nombre: Gandalf")]
     fn test_duplicate_character_names() {
@@ -87,4 +109,905 @@ mod tests {
         
         Player::new(characters); // Aquí es donde debe ocurrir el panic
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_drop_and_take_keep_item_instance() {
+        let mut player = create_test_player();
+        player.current_location = Some("pueblo".to_string());
+        let dagger = Item::new_equipment("daga", "una daga ligera", true, EquipmentType::Weapon(WeaponType::Light));
+        let other_dagger = Item::new_equipment("daga", "una daga ligera", true, EquipmentType::Weapon(WeaponType::Light));
+        let (dagger_id, other_id) = (dagger.id, other_dagger.id);
+        player.characters[0].pack.push(dagger);
+        player.characters[0].pack.push(other_dagger);
+
+        assert!(player.execute_equip(&["Aragorn", "daga"]));
+        assert!(player.execute_drop("daga"));
+        assert!(player.characters[0].pack.is_empty());
+
+        assert!(player.execute_unequip(&["Aragorn", "arma"]));
+        assert!(player.execute_take("daga", Some("Aragorn")));
+        let mut ids: Vec<u32> = player.characters[0].pack.iter().map(|item| item.id).collect();
+        ids.sort();
+        assert_eq!(ids, vec![dagger_id, other_id]);
+        assert!(player.characters[0].pack.iter().all(|item| item.base.tag == "daga"));
+    }
+
+    #[test]
+    fn test_each_character_carries_their_own_load() {
+        let mut player = create_test_player();
+        assert_eq!(player.encumbered_characters(), vec![false, false, false]);
+
+        player.characters[1].pack.push(Item::new("yunque", "un yunque").with_weight(15));
+        assert_eq!(player.character_loads(), vec![0, 15, 0]);
+        assert_eq!(player.encumbered_characters(), vec![false, true, false]);
+
+        // El guerrero carga con el yunque y el mago queda libre
+        assert!(player.execute_give("yunque", "Aragorn"));
+        assert_eq!(player.encumbered_characters(), vec![false, false, false]);
+        assert_eq!(player.carrier_of("yunque"), Some(0));
+    }
+
+    #[test]
+    fn test_encumbered_characters_tire_on_a_one() {
+        let mut player = create_test_player();
+        player.current_location = Some("pueblo".to_string());
+        player.characters[1].pack.push(Item::new("yunque", "un yunque").with_weight(15));
+        let hit_points: Vec<u32> = player.characters.iter().map(|c| c.hit_points).collect();
+
+        // Con un 6 nadie se agota
+        player.execute_go(Some("campo"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.characters[1].hit_points, hit_points[1]);
+
+        // Con un 1 solo acusa el cansancio quien va sobrecargado
+        player.execute_go(Some("pueblo"), &mut MockDiceRoller { value: 1 });
+        assert_eq!(player.current_location, Some("pueblo".to_string()));
+        assert_eq!(player.characters[0].hit_points, hit_points[0]);
+        assert_eq!(player.characters[1].hit_points, hit_points[1] - 1);
+        assert_eq!(player.characters[2].hit_points, hit_points[2]);
+    }
+
+    #[test]
+    fn test_buy_and_sell_at_the_village_shop() {
+        let mut player = create_test_player();
+        player.current_location = Some("pueblo".to_string());
+        assert_eq!(player.gold, 30);
+
+        // El comerciante es amistoso: vende con descuento y paga algo más
+        assert!(player.execute_buy("espada", Some("Aragorn")));
+        assert_eq!(player.gold, 21);
+        assert!(player.characters[0].carries("espada"));
+        assert_eq!(player.shop_stock["comerciante"]["espada"], 0);
+        assert!(!player.execute_buy("espada", None));
+
+        assert!(player.execute_sell("espada"));
+        assert_eq!(player.gold, 27);
+        assert_eq!(player.shop_stock["comerciante"]["espada"], 1);
+
+        // Pasado el tiempo de reposición la mercancía vuelve a su estado inicial
+        player.execute_buy("antorcha", None);
+        player.turns += 30;
+        player.execute_list_wares();
+        assert_eq!(player.shop_stock["comerciante"]["antorcha"], 5);
+    }
+
+    #[test]
+    fn test_no_shop_outside_the_village() {
+        let mut player = create_test_player();
+        player.current_location = Some("campo".to_string());
+        assert!(!player.execute_buy("antorcha", None));
+        assert_eq!(player.gold, 30);
+    }
+
+    #[test]
+    fn test_rest_at_the_inn() {
+        let mut player = create_test_player();
+        player.current_location = Some("posada".to_string());
+        player.characters[0].take_damage(3);
+        player.characters[1].spell_slots = 0;
+
+        // Tres aventureros vivos a 3 de oro cada uno
+        assert!(player.execute_rest());
+        assert_eq!(player.gold, 21);
+        assert_eq!(player.characters[0].hit_points, player.characters[0].max_hit_points);
+        assert_eq!(player.characters[1].spell_slots, 3);
+
+        player.current_location = Some("pueblo".to_string());
+        assert!(!player.execute_rest());
+    }
+
+    #[test]
+    fn test_rumors_rotate() {
+        let mut player = create_test_player();
+        player.current_location = Some("posada".to_string());
+        for _ in 0..5 {
+            assert!(player.execute_rumors());
+        }
+        assert_eq!(player.rumors_heard, 5);
+    }
+
+    #[test]
+    fn test_hire_replaces_fallen_character() {
+        let mut player = create_test_player();
+        player.current_location = Some("posada".to_string());
+        assert!(!player.execute_hire("Gimli enano"));
+
+        let legolas = &mut player.characters[2];
+        legolas.take_damage(legolas.max_hit_points);
+        legolas.pack.push(Item::new("cuerda", "una cuerda"));
+        assert_eq!(player.living_characters(), 2);
+
+        assert!(!player.execute_hire("Aragorn guerrero"));
+        assert!(!player.execute_hire("Gimli bardo"));
+        assert!(player.execute_hire("Gimli enano"));
+        assert_eq!(player.gold, 5);
+
+        let gimli = &player.characters[2];
+        assert_eq!(gimli.name, "Gimli");
+        assert_eq!(gimli.class, Class::Dwarf);
+        assert!(gimli.weapon.is_some());
+        assert!(gimli.carries("cuerda"));
+        assert_eq!(player.living_characters(), 3);
+    }
+
+    #[test]
+    fn test_hire_rejects_a_name_that_is_too_long() {
+        let mut player = create_test_player();
+        player.current_location = Some("posada".to_string());
+        let legolas = &mut player.characters[2];
+        legolas.take_damage(legolas.max_hit_points);
+        let gold = player.gold;
+
+        assert!(!player.execute_hire("Abcdefghijklmnopqrstuvwxyz enano"));
+        assert_eq!(player.gold, gold);
+        assert_eq!(player.characters[2].name, "Legolas");
+        assert!(!player.characters[2].is_alive());
+        assert_eq!(player.living_characters(), 2);
+    }
+
+    #[test]
+    fn test_dialogue_changes_merchant_attitude() {
+        let mut player = create_test_player();
+        player.current_location = Some("pueblo".to_string());
+        let merchant = find_npc("comerciante").unwrap();
+
+        assert!(player.execute_talk("comerciante"));
+        // Sin la moneda solo se ofrecen cuatro respuestas
+        assert!(!player.execute_choice(5));
+        assert!(player.execute_choice(2));
+        assert!(player.execute_choice(1));
+        assert_eq!(player.mode, GameMode::Exploration);
+        assert_eq!(player.attitude_of(merchant), Attitude::Neutral);
+
+        assert!(player.execute_buy("daga", None));
+        assert_eq!(player.gold, 26);
+    }
+
+    #[test]
+    fn test_dialogue_reward_is_given_once() {
+        let mut player = create_test_player();
+        player.current_location = Some("pueblo".to_string());
+        player.defeated_npcs.insert("goblins".to_string());
+
+        assert!(player.execute_talk("guardia"));
+        assert!(player.execute_choice(2));
+        assert!(player.execute_choice(1));
+        assert!(player.has_item("cuerda"));
+        assert!(player.flags.contains("recompensa_guardia"));
+
+        // La respuesta de la recompensa ya no aparece y en su lugar hay otra
+        player.execute_talk("guardia");
+        assert!(player.execute_choice(2));
+        assert_eq!(player.mode, GameMode::Dialogue { npc: "guardia".to_string(), node: "consejo".to_string() });
+        assert!(!player.execute_talk("goblins"));
+    }
+
+    #[test]
+    fn test_quest_from_dialogue_completes_on_defeat() {
+        let mut player = create_test_player();
+        player.current_location = Some("pueblo".to_string());
+
+        player.execute_talk("guardia");
+        assert!(player.execute_choice(1));
+        assert!(player.execute_choice(1));
+        assert_eq!(player.active_quests, vec!["goblins_bosque".to_string()]);
+
+        player.update_quests();
+        assert!(player.completed_quests.is_empty());
+
+        player.defeated_npcs.insert("goblins".to_string());
+        player.update_quests();
+        assert!(player.active_quests.is_empty());
+        assert_eq!(player.completed_quests, vec!["goblins_bosque".to_string()]);
+        assert_eq!(player.gold, 45);
+        assert_eq!(player.encounters_won, 1);
+    }
+
+    #[test]
+    fn test_fetch_quest_needs_the_giver() {
+        let mut player = create_test_player();
+        assert!(player.start_quest("libro_nigromante"));
+        assert!(!player.start_quest("libro_nigromante"));
+        player.characters[1].pack.push(Item::new("libro", "un libro de nigromancia"));
+
+        player.current_location = Some("biblioteca".to_string());
+        player.update_quests();
+        assert!(player.completed_quests.is_empty());
+
+        player.current_location = Some("pueblo".to_string());
+        player.update_quests();
+        assert_eq!(player.completed_quests, vec!["libro_nigromante".to_string()]);
+        assert!(!player.has_item("libro"));
+        assert_eq!(player.gold, 70);
+    }
+
+    #[test]
+    fn test_multi_objective_quest() {
+        let mut player = create_test_player();
+        player.start_quest("secreto_cueva");
+        player.current_location = Some("puerta".to_string());
+        player.solved_puzzles.insert("acertijo".to_string());
+        player.update_quests();
+        assert!(player.completed_quests.is_empty());
+
+        // La puerta de piedra también pide los símbolos; una vez abierta, basta con entrar
+        player.solved_puzzles.insert("simbolos".to_string());
+        player.execute_go(Some("camara"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("camara".to_string()));
+        player.update_quests();
+        assert_eq!(player.completed_quests, vec!["secreto_cueva".to_string()]);
+        assert!(player.has_item("escudo"));
+    }
+
+    #[test]
+    fn test_goblins_can_be_bribed() {
+        let mut player = create_test_player();
+        player.current_location = Some("bosque".to_string());
+        let goblins = find_npc("goblins").unwrap();
+        assert!(player.has_hostile_npcs());
+
+        assert!(player.execute_talk("goblins"));
+        assert!(player.execute_choice(1));
+        assert!(player.execute_choice(1));
+        assert_eq!(player.gold, 20);
+        assert_eq!(player.reputation_with(&Faction::Goblins), 3);
+        assert_eq!(player.attitude_of(goblins), Attitude::Neutral);
+        assert!(!player.has_hostile_npcs());
+    }
+
+    #[test]
+    fn test_village_reputation_shifts_attitudes() {
+        let mut player = create_test_player();
+        let guard = find_npc("guardia").unwrap();
+        let merchant = find_npc("comerciante").unwrap();
+        assert_eq!(player.attitude_of(guard), Attitude::Friendly);
+
+        player.change_reputation(&Faction::Village, -3);
+        assert_eq!(player.attitude_of(guard), Attitude::Neutral);
+
+        // La reputación se suma a lo que haya cambiado en una conversación
+        player.npc_attitudes.insert("comerciante".to_string(), Attitude::Neutral);
+        assert_eq!(player.attitude_of(merchant), Attitude::Hostile);
+
+        player.change_reputation(&Faction::Village, -3);
+        assert_eq!(player.attitude_of(guard), Attitude::Hostile);
+        player.current_location = Some("pueblo".to_string());
+        assert!(!player.execute_buy("antorcha", None));
+    }
+
+    #[test]
+    fn test_social_actions_use_dice() {
+        let mut player = create_test_player();
+        player.current_location = Some("bosque".to_string());
+        let goblins = find_npc("goblins").unwrap();
+
+        // Legolas, el pícaro, ofrece el soborno: 3 + 1 (pícaro) + 1 (10 de oro) supera el nivel 3 de los goblins
+        assert!(player.execute_social(SocialAction::Bribe, "goblins", Some(10), &mut MockDiceRoller { value: 3 }));
+        assert_eq!(player.gold, 20);
+        assert_eq!(player.attitude_of(goblins), Attitude::Neutral);
+
+        // Un 1 siempre falla
+        assert!(!player.execute_social(SocialAction::Persuade, "goblins", None, &mut MockDiceRoller { value: 1 }));
+        assert_eq!(player.reputation_with(&Faction::Goblins), -1);
+
+        assert!(player.execute_social(SocialAction::Intimidate, "goblins", None, &mut MockDiceRoller { value: 6 }));
+        assert!(player.defeated_npcs.contains("goblins"));
+        assert!(!player.execute_social(SocialAction::Bribe, "goblins", None, &mut MockDiceRoller { value: 6 }));
+    }
+
+    #[test]
+    fn test_failed_intimidation_angers_npc() {
+        let mut player = create_test_player();
+        player.current_location = Some("pueblo".to_string());
+        let guard = find_npc("guardia").unwrap();
+        assert!(!player.execute_social(SocialAction::Intimidate, "guardia", None, &mut MockDiceRoller { value: 2 }));
+        assert_eq!(player.attitude_of(guard), Attitude::Neutral);
+    }
+
+    #[test]
+    fn test_attacking_the_guard_turns_the_village_hostile() {
+        let mut player = create_test_player();
+        player.current_location = Some("pueblo".to_string());
+        let guard = find_npc("guardia").unwrap();
+        let merchant = find_npc("comerciante").unwrap();
+
+        player.provoke(guard);
+        assert_eq!(player.attitude_of(guard), Attitude::Hostile);
+        assert_eq!(player.attitude_of(merchant), Attitude::Hostile);
+        assert_eq!(player.reputation_with(&Faction::Village), -6);
+        assert!(!player.execute_buy("antorcha", None));
+    }
+
+    #[test]
+    fn test_recruit_and_dismiss_companion() {
+        let mut player = create_test_player();
+        player.current_location = Some("posada".to_string());
+
+        assert!(player.execute_recruit("mercenaria"));
+        assert_eq!(player.gold, 10);
+        assert_eq!(player.characters.len(), 4);
+        let brenna = &player.characters[3];
+        assert_eq!(brenna.name, "Brenna");
+        assert_eq!(brenna.level, 2);
+        assert!(brenna.weapon.is_some() && brenna.shield.is_some());
+        assert!(player.npc_here("mercenaria").is_none());
+        assert!(!player.execute_recruit("mercenaria"));
+
+        // Solo se puede despedir a los compañeros
+        assert!(!player.execute_dismiss("Aragorn"));
+        player.characters[3].pack.push(Item::new("cuerda", "una cuerda"));
+        assert!(player.execute_dismiss("Brenna"));
+        assert_eq!(player.characters.len(), 3);
+        assert!(player.npc_here("mercenaria").is_some());
+        assert_eq!(player.dropped_items["posada"].len(), 1);
+    }
+
+    #[test]
+    fn test_dismissed_companion_leaves_party_gear_behind() {
+        let mut player = create_test_player();
+        player.current_location = Some("posada".to_string());
+        assert!(player.execute_recruit("mercenaria"));
+
+        // La cota es del grupo; la espada y el escudo son de Brenna
+        player.characters[3].pack.push(Item::new_equipment("cota", "una cota de malla", true, EquipmentType::Armor(ArmorType::Heavy)));
+        assert!(player.execute_equip(&["Brenna", "cota"]));
+        assert!(player.characters[3].armor.is_some());
+
+        assert!(player.execute_dismiss("Brenna"));
+        let dropped: Vec<&str> = player.dropped_items["posada"].iter().map(|item| item.base.tag.as_str()).collect();
+        assert_eq!(dropped, vec!["cota"]);
+    }
+
+    #[test]
+    fn test_companion_needs_trust_to_join() {
+        let mut player = create_test_player();
+        player.current_location = Some("campo".to_string());
+        assert!(!player.execute_recruit("cazador"));
+
+        assert!(player.execute_social(SocialAction::Persuade, "cazador", None, &mut MockDiceRoller { value: 6 }));
+        assert!(player.execute_recruit("cazador"));
+        assert_eq!(player.characters[3].class, Class::Elf);
+    }
+
+    #[test]
+    fn test_disloyal_companion_leaves() {
+        let mut player = create_test_player();
+        player.current_location = Some("posada".to_string());
+        player.execute_recruit("mercenaria");
+
+        player.change_companion_loyalty(-4);
+        assert_eq!(player.characters.len(), 4);
+        player.change_companion_loyalty(-1);
+        assert_eq!(player.characters.len(), 3);
+        assert!(player.recruited_npcs.is_empty());
+    }
+
+    #[test]
+    fn test_reorder_and_rename() {
+        let mut player = create_test_player();
+        assert!(player.execute_reorder(&["legolas", "Gandalf"]));
+        let names: Vec<&str> = player.characters.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["Legolas", "Gandalf", "Aragorn"]);
+        assert!(!player.execute_reorder(&["Legolas", "Legolas"]));
+        assert!(!player.execute_reorder(&["Frodo"]));
+
+        assert!(!player.execute_rename("Aragorn", "gandalf"));
+        assert!(!player.execute_rename("Aragorn", "  "));
+        assert!(!player.execute_rename("Aragorn", "Aragorn hijo de Arathorn, heredero de Isildur"));
+        assert!(player.execute_rename("Aragorn", "Trancos"));
+        assert!(player.find_character("Trancos").is_some());
+        assert!(player.execute_sheet("Trancos"));
+    }
+
+    #[test]
+    fn test_retire_character_at_the_inn() {
+        let mut player = create_test_player();
+        player.current_location = Some("pueblo".to_string());
+        assert!(!player.execute_retire("Aragorn", "Boromir guerrero"));
+
+        player.current_location = Some("posada".to_string());
+        player.characters[0].pack.push(Item::new("cuerda", "una cuerda"));
+        assert!(player.execute_retire("Aragorn", "Boromir guerrero"));
+        let boromir = &player.characters[0];
+        assert_eq!(boromir.name, "Boromir");
+        assert_eq!(boromir.level, 1);
+        assert!(boromir.carries("cuerda"));
+        assert_eq!(player.gold, 30);
+    }
+
+    #[test]
+    fn test_retire_rejects_a_name_that_is_too_long() {
+        let mut player = create_test_player();
+        player.current_location = Some("posada".to_string());
+        assert!(!player.execute_retire("Aragorn", "Abcdefghijklmnopqrstuvwxyz guerrero"));
+        let names: Vec<&str> = player.characters.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["Aragorn", "Gandalf", "Legolas"]);
+        assert_eq!(player.characters[0].class, Class::Fighter);
+    }
+
+    #[test]
+    fn test_search_reveals_hidden_items() {
+        let mut player = create_test_player();
+        player.current_location = Some("biblioteca".to_string());
+        assert!(!player.execute_take("llave", None));
+
+        // La biblioteca está a oscuras: sin luz no se puede registrar
+        assert!(!player.execute_search(&mut MockDiceRoller { value: 6 }));
+        assert!(player.execute_cast_light());
+        // Sin la trampa de la trampilla, lo único que queda por descubrir es la llave
+        player.cleared_traps.insert("pasaje:trampilla".to_string());
+
+        // La probabilidad queda siempre entre el 5% y el 95%: un 96 falla y un 1 acierta
+        assert!(!player.execute_search(&mut MockDiceRoller { value: 96 }));
+        assert!(!player.execute_take("llave", None));
+        assert!(player.execute_search(&mut MockDiceRoller { value: 1 }));
+        assert!(player.execute_take("llave", Some("Legolas")));
+        assert!(player.characters[2].carries("llave"));
+        assert!(!player.execute_search(&mut MockDiceRoller { value: 6 }));
+    }
+
+    #[test]
+    fn test_perceptive_character_spots_hidden_items() {
+        let mut elf = Character::new(Class::Elf);
+        elf.set_name("Thranduil".to_string(), &HashSet::new());
+        let mut player = Player::new(vec![elf]);
+        player.execute_go(Some("ruinas"), &mut MockDiceRoller { value: 6 });
+        assert!(player.execute_take("bolsa", None));
+        assert!(player.execute_take("hacha", None));
+    }
+
+    #[test]
+    fn test_locked_room_needs_picking() {
+        let mut player = create_test_player();
+        player.current_location = Some("laboratorio".to_string());
+        player.defeated_npcs.insert("esqueletos".to_string());
+
+        player.execute_go(Some("biblioteca"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("laboratorio".to_string()));
+
+        // Legolas es pícaro: 3 + 1 alcanza la dificultad 4 de la puerta
+        assert!(player.execute_open("biblioteca", &mut MockDiceRoller { value: 3 }));
+        player.execute_go(Some("biblioteca"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("biblioteca".to_string()));
+    }
+
+    #[test]
+    fn test_master_key_needs_a_spellcaster() {
+        let mut player = create_test_player();
+        player.current_location = Some("laboratorio".to_string());
+        player.defeated_npcs.insert("esqueletos".to_string());
+        player.characters[0].pack.push(spawn_item("llave_maestra").unwrap());
+
+        // Aragorn no sabe hacerla funcionar
+        player.execute_go(Some("biblioteca"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("laboratorio".to_string()));
+
+        assert!(player.execute_give("llave_maestra", "Gandalf"));
+        player.execute_go(Some("biblioteca"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("biblioteca".to_string()));
+    }
+
+    #[test]
+    fn test_lock_picks_break_on_a_one() {
+        let mut player = create_test_player();
+        player.current_location = Some("biblioteca".to_string());
+        player.characters[2].pack.push(Item::new("ganzuas", "unas ganzúas"));
+
+        assert!(!player.execute_open("tesoro", &mut MockDiceRoller { value: 1 }));
+        assert!(!player.characters[2].carries("ganzuas"));
+
+        // La llave del pasaje lo abre sin tirar
+        player.characters[0].pack.push(Item::new("llave", "una llave de hierro"));
+        player.execute_go(Some("tesoro"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("tesoro".to_string()));
+    }
+
+    #[test]
+    fn test_chests_show_their_contents_once_open() {
+        let mut player = create_test_player();
+        player.current_location = Some("camara".to_string());
+        player.defeated_npcs.insert("ratas".to_string());
+        assert!(!player.execute_take("manta", None));
+        assert!(player.execute_open("baul", &mut MockDiceRoller { value: 1 }));
+        assert!(player.execute_take("manta", None));
+
+        // Aragorn revienta el cofre: 6 + 1 supera la dificultad 5 + 1, y el ruido no atrae a nadie
+        player.current_location = Some("tesoro".to_string());
+        assert!(!player.execute_take("gemas", None));
+        assert!(player.execute_force("cofre", &mut MockDiceRoller { value: 6 }));
+        assert!(player.execute_take("gemas", None));
+        assert!(player.npcs_at("tesoro").is_empty());
+    }
+
+    #[test]
+    fn test_forcing_a_lock_attracts_marauders() {
+        let mut player = create_test_player();
+        player.current_location = Some("tesoro".to_string());
+        assert!(!player.execute_force("cofre", &mut MockDiceRoller { value: 1 }));
+        assert!(player.npcs_at("tesoro").iter().any(|npc| npc.base.tag == "merodeadores"));
+        assert!(player.has_hostile_npcs());
+    }
+
+    #[test]
+    fn test_puzzles_open_the_stone_door() {
+        let mut player = create_test_player();
+        player.current_location = Some("puerta".to_string());
+        player.execute_go(Some("camara"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("puerta".to_string()));
+
+        assert!(player.execute_answer("La Vela"));
+        player.execute_go(Some("camara"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("puerta".to_string()));
+
+        assert!(!player.execute_press("ojo sol luna estrella"));
+        assert!(player.execute_press("sol, luna, estrella, ojo"));
+        player.execute_go(Some("camara"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("camara".to_string()));
+
+        // Lo resuelto sigue resuelto al volver a pasar
+        player.defeated_npcs.insert("ratas".to_string());
+        player.execute_go(Some("puerta"), &mut MockDiceRoller { value: 6 });
+        player.execute_go(Some("camara"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("camara".to_string()));
+    }
+
+    #[test]
+    fn test_failed_attempts_give_hints() {
+        let puzzle = find_puzzle("acertijo").unwrap();
+        assert!(puzzle.hint_after(1).is_none());
+        assert!(puzzle.hint_after(2).is_some());
+        assert_eq!(puzzle.hint_after(9), puzzle.hints.last().map(|hint| hint.as_str()));
+
+        let mut player = create_test_player();
+        player.current_location = Some("puerta".to_string());
+        assert!(!player.execute_answer("sombra"));
+        assert!(!player.execute_answer("árbol"));
+        assert_eq!(player.puzzle_failures.get("acertijo"), Some(&2));
+        assert!(player.execute_answer("cirio"));
+        assert!(!player.execute_answer("vela"));
+    }
+
+    #[test]
+    fn test_placing_the_coin_on_the_altar() {
+        let mut player = create_test_player();
+        player.current_location = Some("ruinas".to_string());
+        player.characters[0].pack.push(Item::new("cuerda", "una cuerda"));
+        player.characters[1].pack.push(Item::new("moneda", "una moneda de plata"));
+
+        assert!(!player.execute_place("cuerda"));
+        assert!(player.characters[0].carries("cuerda"));
+        assert!(!player.execute_take("amuleto", None));
+
+        assert!(player.execute_place("moneda"));
+        assert!(!player.characters[1].carries("moneda"));
+        assert!(player.execute_take("amuleto", None));
+    }
+
+    #[test]
+    fn test_one_way_well_needs_a_rope() {
+        let mut player = create_test_player();
+        player.current_location = Some("ruinas".to_string());
+        player.defeated_npcs.insert("orcos".to_string());
+        player.execute_go(Some("camara"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("ruinas".to_string()));
+
+        player.characters[0].pack.push(Item::new("cuerda", "una cuerda"));
+        player.execute_go(Some("camara"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("camara".to_string()));
+        assert!(!player.has_item("cuerda"));
+
+        player.defeated_npcs.insert("ratas".to_string());
+        player.execute_go(Some("ruinas"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("camara".to_string()));
+    }
+
+    #[test]
+    fn test_tunnel_collapses_behind_the_party() {
+        let mut player = create_test_player();
+        player.current_location = Some("laboratorio".to_string());
+        player.defeated_npcs.insert("esqueletos".to_string());
+        player.execute_go(Some("bosque"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("laboratorio".to_string()));
+
+        let mut dwarf = Character::new(Class::Dwarf);
+        dwarf.set_name("Gimli".to_string(), &HashSet::new());
+        let hit_points = dwarf.hit_points;
+        let mut player = Player::new(vec![dwarf]);
+        player.current_location = Some("laboratorio".to_string());
+        player.defeated_npcs.insert("esqueletos".to_string());
+        player.execute_go(Some("bosque"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("bosque".to_string()));
+        assert_eq!(player.characters[0].hit_points, hit_points - 1);
+
+        player.current_location = Some("laboratorio".to_string());
+        player.execute_go(Some("bosque"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("laboratorio".to_string()));
+    }
+
+    #[test]
+    fn test_village_gate_closes_at_night() {
+        let mut player = create_test_player();
+        player.current_location = Some("pueblo".to_string());
+        player.turns = 20;
+        player.execute_go(Some("campo"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("pueblo".to_string()));
+
+        // Tras una noche en la posada amanece y el portón se abre
+        player.execute_go(Some("posada"), &mut MockDiceRoller { value: 6 });
+        assert!(player.execute_rest());
+        assert_eq!(player.turns, 24);
+        player.execute_go(Some("pueblo"), &mut MockDiceRoller { value: 6 });
+        player.execute_go(Some("campo"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("campo".to_string()));
+    }
+
+    #[test]
+    fn test_torches_burn_out() {
+        let mut player = create_test_player();
+        player.current_location = Some("grieta".to_string());
+        assert!(player.is_dark_here());
+        player.execute_go(Some("corredor"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("grieta".to_string()));
+
+        player.characters[0].pack.push(Item::new("antorcha", "una antorcha").with_fuel(2));
+        assert!(!player.execute_light("cuerda"));
+        assert!(player.execute_light("antorcha"));
+        assert!(!player.is_dark_here());
+        player.execute_go(Some("corredor"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("corredor".to_string()));
+
+        player.advance_turn();
+        player.advance_turn();
+        assert!(!player.has_light());
+        assert!(!player.execute_light("antorcha"));
+    }
+
+    #[test]
+    fn test_light_spell_uses_a_slot() {
+        let mut player = create_test_player();
+        player.current_location = Some("camara".to_string());
+        let slots = player.characters[1].spell_slots;
+        assert!(player.execute_cast_light());
+        assert_eq!(player.characters[1].spell_slots, slots - 1);
+        assert!(!player.is_dark_here());
+
+        player.characters[1].spell_slots = 0;
+        for _ in 0..10 {
+            player.advance_turn();
+        }
+        assert!(player.is_dark_here());
+        assert!(!player.execute_cast_light());
+    }
+
+    #[test]
+    fn test_undetected_trap_springs_once() {
+        let mut player = create_test_player();
+        player.current_location = Some("camara".to_string());
+        player.defeated_npcs.insert("ratas".to_string());
+        player.defeated_npcs.insert("esqueletos".to_string());
+        let hit_points = player.characters[0].hit_points;
+
+        player.execute_go(Some("laboratorio"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.characters[0].hit_points, hit_points - 1);
+        player.execute_go(Some("camara"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.characters[0].hit_points, hit_points - 1);
+    }
+
+    #[test]
+    fn test_perceptive_character_spots_a_trap_on_a_good_roll() {
+        let mut elf = Character::new(Class::Elf);
+        elf.set_name("Thranduil".to_string(), &HashSet::new());
+        let mut player = Player::new(vec![elf]);
+        player.current_location = Some("camara".to_string());
+        player.defeated_npcs.insert("ratas".to_string());
+        player.defeated_npcs.insert("esqueletos".to_string());
+        let hit_points = player.characters[0].hit_points;
+
+        // 6 + 1 por ser perspicaz supera la dificultad 3 del derrumbe: lo ve y pasa de largo
+        player.execute_go(Some("laboratorio"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("laboratorio".to_string()));
+        assert!(player.detected_traps.contains("pasaje:pasillo_laboratorio"));
+        assert_eq!(player.characters[0].hit_points, hit_points);
+    }
+
+    #[test]
+    fn test_perceptive_character_misses_a_trap_on_a_bad_roll() {
+        let mut elf = Character::new(Class::Elf);
+        elf.set_name("Thranduil".to_string(), &HashSet::new());
+        let mut player = Player::new(vec![elf]);
+        player.current_location = Some("camara".to_string());
+        player.defeated_npcs.insert("ratas".to_string());
+        player.defeated_npcs.insert("esqueletos".to_string());
+        let hit_points = player.characters[0].hit_points;
+
+        // 1 + 1 no llega: el techo se les viene encima
+        player.execute_go(Some("laboratorio"), &mut MockDiceRoller { value: 1 });
+        assert!(player.detected_traps.is_empty());
+        assert!(player.cleared_traps.contains("pasaje:pasillo_laboratorio"));
+        assert!(player.characters[0].hit_points < hit_points);
+    }
+
+    #[test]
+    fn test_poison_needle_wears_off() {
+        let mut player = create_test_player();
+        player.current_location = Some("tesoro".to_string());
+        player.characters[0].hit_points = 10;
+        assert!(player.execute_force("cofre", &mut MockDiceRoller { value: 6 }));
+        assert_eq!(player.characters[0].hit_points, 9);
+        assert!(player.characters[0].is_poisoned());
+
+        for _ in 0..3 {
+            player.advance_turn();
+        }
+        assert_eq!(player.characters[0].hit_points, 6);
+        assert!(!player.characters[0].is_poisoned());
+    }
+
+    #[test]
+    fn test_rogue_disarms_a_found_trap() {
+        let mut player = create_test_player();
+        player.current_location = Some("biblioteca".to_string());
+        player.characters[0].pack.push(Item::new("llave", "una llave de hierro"));
+        assert!(player.execute_cast_light());
+        assert!(!player.execute_disarm(Some("tesoro"), &mut MockDiceRoller { value: 6 }));
+
+        // Legolas intuye las trampas: al registrar da con la de la trampilla
+        player.execute_search(&mut MockDiceRoller { value: 6 });
+        assert!(player.detected_traps.contains("pasaje:trampilla"));
+
+        // Sin ganzúas no hay forma de desarmarla
+        assert!(!player.execute_disarm(Some("tesoro"), &mut MockDiceRoller { value: 6 }));
+        player.characters[2].pack.push(Item::new("ganzuas", "unas ganzúas"));
+        assert!(player.execute_disarm(Some("tesoro"), &mut MockDiceRoller { value: 3 }));
+
+        let hit_points = player.characters[1].hit_points;
+        player.execute_go(Some("tesoro"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location, Some("tesoro".to_string()));
+        assert_eq!(player.characters[1].hit_points, hit_points);
+    }
+
+    #[test]
+    fn test_combat_is_played_one_round_per_command() {
+        let mut player = create_test_player();
+        player.current_location = Some("ruinas".to_string());
+        let mut dice = MockDiceRoller { value: 1 };
+
+        // Sin armas nadie derrota a ningún orco, y los contraataques hieren al grupo
+        assert!(player.execute_attack("", &mut dice));
+        match &player.mode {
+            GameMode::Combat(combat) => {
+                assert_eq!(combat.round, 1);
+                assert_eq!(combat.remaining, 7);
+            },
+            mode => panic!("Se esperaba un combate, no {:?}", mode),
+        }
+        assert!(player.characters.iter().any(|c| c.hit_points < c.max_hit_points));
+
+        // Consultar el estado no gasta una ronda; seguir luchando sí
+        player.execute_combat_action(CombatAction::Status, &mut dice);
+        assert!(matches!(&player.mode, GameMode::Combat(combat) if combat.round == 1));
+        player.execute_combat_action(CombatAction::Fight, &mut dice);
+        assert!(matches!(&player.mode, GameMode::Combat(combat) if combat.round == 2));
+
+        assert!(player.execute_combat_action(CombatAction::Flee, &mut dice));
+        assert_eq!(player.mode, GameMode::Exploration);
+        assert!(!player.defeated_npcs.contains("orcos"));
+    }
+
+    #[test]
+    fn test_combat_ends_when_the_last_enemy_falls() {
+        let mut player = create_test_player();
+        player.current_location = Some("bosque".to_string());
+        player.characters[0].pack.push(Item::new_equipment("daga", "una daga ligera", true, EquipmentType::Weapon(WeaponType::Light)));
+        assert!(player.execute_equip(&["Aragorn", "daga"]));
+        let mut dice = MockDiceRoller { value: 6 };
+
+        player.execute_attack("goblins", &mut dice);
+        let mut rounds = 1;
+        while player.in_combat() && rounds < 10 {
+            player.execute_attack("", &mut dice);
+            rounds += 1;
+        }
+        assert_eq!(player.mode, GameMode::Exploration);
+        assert!(player.defeated_npcs.contains("goblins"));
+        assert_eq!(player.encounters_won, 1);
+    }
+
+    #[test]
+    fn test_travel_follows_the_shortest_known_route() {
+        let mut player = create_test_player();
+        player.current_location = Some("posada".to_string());
+        for tag in ["posada", "pueblo", "campo", "cueva"] {
+            player.visited_locations.insert(tag.to_string());
+        }
+
+        assert_eq!(player.route_to("cueva"), Some(vec!["pueblo".to_string(), "campo".to_string(), "cueva".to_string()]));
+        assert_eq!(player.route_to("bosque"), None);
+
+        // De noche el portón está atrancado y no hay otro camino conocido
+        player.turns = 20;
+        assert_eq!(player.route_to("cueva"), None);
+        assert!(!player.execute_travel("cueva", &mut MockDiceRoller { value: 6 }));
+
+        // Cada etapa cuesta un turno; la primera es la de la propia orden
+        player.turns = 0;
+        assert!(player.execute_travel("cueva", &mut MockDiceRoller { value: 6 }));
+        assert_eq!(player.current_location, Some("cueva".to_string()));
+        assert_eq!(player.turns, 2);
+    }
+
+    #[test]
+    fn test_travel_stops_when_enemies_are_in_the_way() {
+        let mut player = create_test_player();
+        player.current_location = Some("campo".to_string());
+        for tag in ["campo", "cueva", "bosque", "ruinas"] {
+            player.visited_locations.insert(tag.to_string());
+        }
+
+        assert!(player.execute_travel("ruinas", &mut MockDiceRoller { value: 6 }));
+        assert_eq!(player.current_location, Some("bosque".to_string()));
+        assert!(player.has_hostile_npcs());
+    }
+
+    #[test]
+    fn test_map_shows_only_what_the_party_knows() {
+        let mut player = create_test_player();
+        player.current_location = Some("cueva".to_string());
+        player.visited_locations.insert("campo".to_string());
+        player.visited_locations.insert("bosque".to_string());
+
+        let map = render_ascii(&player.map_rooms());
+        assert!(map.contains("[@cueva]"));
+        assert!(map.contains("[bosque!]"));
+        assert!(map.contains("(pueblo)"));
+        // La grieta está oculta hasta que se descubre buscando
+        assert!(!map.contains("grieta"));
+
+        let path = std::env::temp_dir().join(format!("mapa-{}.dot", std::process::id()));
+        let path = path.to_str().unwrap();
+        assert!(player.execute_map_export(path));
+        let dot = std::fs::read_to_string(path).unwrap();
+        assert!(dot.starts_with("digraph mapa {"));
+        assert!(dot.contains("\"cueva\" -> \"campo\" [dir=none];"));
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_map_places_rooms_by_their_exits() {
+        let mut player = create_test_player();
+        player.current_location = Some("laboratorio".to_string());
+        for tag in ["pueblo", "posada", "campo", "cueva", "bosque", "ruinas", "grieta", "corredor", "puerta", "camara", "biblioteca", "tesoro"] {
+            player.visited_locations.insert(tag.to_string());
+        }
+        let rooms = player.map_rooms();
+        let positions = layout(&rooms);
+        let (x, y) = positions["pueblo"];
+        assert_eq!(positions["posada"], (x + 1, y));
+        assert_eq!(positions["campo"], (x, y - 1));
+        assert_eq!(positions["camara"], (positions["laboratorio"].0, positions["laboratorio"].1 - 1));
+
+        // La biblioteca sigue cerrada, y los atajos que suben o bajan se enumeran aparte
+        let map = render_ascii(&rooms);
+        assert!(map.lines().any(|line| line.contains("[biblioteca]") && line.contains("-X-[@laboratorio!]")));
+        assert!(map.contains("- laboratorio -> bosque (arriba), sin vuelta"));
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use lazy_static::lazy_static;
+use crate::models::object::Item;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PuzzleKind {
+    Riddle(Vec<String>),    // Respuestas aceptadas (se responde con `responder`)
+    Sequence(Vec<String>),  // Orden correcto de los símbolos (se pulsan con `pulsar`)
+    Placement(String),      // Objeto que hay que dejar en su sitio (con `colocar`)
+}
+
+#[derive(Debug, Clone)]
+pub struct Puzzle {
+    pub tag: String,
+    pub location: String,           // Tag de la ubicación donde está el enigma
+    pub title: String,
+    pub prompt: String,             // Lo que ve el grupo mientras no está resuelto
+    pub kind: PuzzleKind,
+    pub hints: Vec<String>,         // Pistas, de más vaga a más clara, tras varios fallos
+    pub solved_text: String,        // Lo que ocurre al resolverlo
+    pub reward: Option<Item>,       // Objeto que aparece en la sala al resolverlo
+}
+
+impl Puzzle {
+    pub fn new(tag: &str, location: &str, title: &str, prompt: &str, kind: PuzzleKind) -> Self {
+        Self {
+            tag: tag.to_string(),
+            location: location.to_string(),
+            title: title.to_string(),
+            prompt: prompt.to_string(),
+            kind,
+            hints: Vec::new(),
+            solved_text: String::new(),
+            reward: None,
+        }
+    }
+
+    pub fn with_hint(mut self, hint: &str) -> Self {
+        self.hints.push(hint.to_string());
+        self
+    }
+
+    pub fn with_solved_text(mut self, text: &str) -> Self {
+        self.solved_text = text.to_string();
+        self
+    }
+
+    pub fn with_reward(mut self, item: Item) -> Self {
+        self.reward = Some(item);
+        self
+    }
+
+    // Comprueba una respuesta, una secuencia de símbolos o el objeto colocado
+    pub fn check(&self, attempt: &str) -> bool {
+        match &self.kind {
+            PuzzleKind::Riddle(answers) => {
+                let attempt = normalize(attempt);
+                answers.iter().any(|answer| normalize(answer) == attempt)
+            },
+            PuzzleKind::Sequence(solution) => {
+                let pressed: Vec<String> = attempt.split(|c: char| c.is_whitespace() || c == ',')
+                    .filter(|symbol| !symbol.is_empty())
+                    .map(normalize)
+                    .collect();
+                pressed.len() == solution.len() && pressed.iter().zip(solution).all(|(pressed, symbol)| *pressed == normalize(symbol))
+            },
+            PuzzleKind::Placement(item_tag) => normalize(attempt) == normalize(item_tag),
+        }
+    }
+
+    // Pista que corresponde tras tantos fallos: ninguna hasta el segundo, luego una más clara cada vez
+    pub fn hint_after(&self, failures: u32) -> Option<&str> {
+        if failures < 2 || self.hints.is_empty() {
+            return None;
+        }
+        let index = ((failures - 2) as usize).min(self.hints.len() - 1);
+        Some(&self.hints[index])
+    }
+}
+
+// Minúsculas, sin tildes y sin artículo delante: "La Vela" y "vela" son la misma respuesta
+fn normalize(text: &str) -> String {
+    let text: String = text.trim().to_lowercase().chars()
+        .map(|c| match c {
+            'á' => 'a',
+            'é' => 'e',
+            'í' => 'i',
+            'ó' => 'o',
+            'ú' | 'ü' => 'u',
+            _ => c,
+        })
+        .collect();
+    let words: Vec<&str> = text.split_whitespace().collect();
+    match words.first() {
+        Some(&"el" | &"la" | &"los" | &"las" | &"un" | &"una" | &"unos" | &"unas") if words.len() > 1 => words[1..].join(" "),
+        _ => words.join(" "),
+    }
+}
+
+lazy_static! {
+    pub static ref PUZZLES: HashMap<String, Puzzle> = {
+        let mut m = HashMap::new();
+
+        m.insert("acertijo".to_string(),
+            Puzzle::new("acertijo", "puerta", "El acertijo de la puerta",
+                "Sobre la puerta hay una inscripción: «Soy alto cuando soy joven y bajo cuando soy viejo. ¿Qué soy?»",
+                PuzzleKind::Riddle(vec!["vela".to_string(), "velas".to_string(), "cirio".to_string(), "candela".to_string()]))
+                .with_hint("Piensa en algo que se consume mientras da luz.")
+                .with_hint("Se enciende por la mecha y se derrite poco a poco.")
+                .with_solved_text("Las letras de la inscripción brillan un instante y algo chasquea dentro de la puerta.")
+        );
+
+        m.insert("simbolos".to_string(),
+            Puzzle::new("simbolos", "puerta", "Los símbolos de la puerta",
+                "Bajo los símbolos erosionados hay cuatro losas que ceden al pulsarlas: ojo, estrella, sol y luna. Alguien grabó al lado: «Del día a la noche, y de la noche a quien todo lo ve».",
+                PuzzleKind::Sequence(vec!["sol".to_string(), "luna".to_string(), "estrella".to_string(), "ojo".to_string()]))
+                .with_hint("El día empieza con el sol.")
+                .with_hint("Tras el sol viene la luna, y tras la luna, las estrellas.")
+                .with_solved_text("Las cuatro losas se hunden a la vez y un mecanismo gruñe tras la piedra.")
+        );
+
+        m.insert("altar".to_string(),
+            Puzzle::new("altar", "ruinas", "La ofrenda del altar",
+                "En el altar de las ruinas hay un hueco redondo, del tamaño de una moneda, gastado por las ofrendas.",
+                PuzzleKind::Placement("moneda".to_string()))
+                .with_hint("Los fieles dejaban aquí algo de valor, pequeño y redondo.")
+                .with_hint("Una moneda de plata encajaría en el hueco.")
+                .with_solved_text("La moneda desaparece en el hueco y una losa del altar se desliza, dejando ver un amuleto.")
+                .with_reward(Item::new("amuleto", "un amuleto de plata con un ojo grabado").with_value(15))
+        );
+
+        m
+    };
+}
+
+pub fn find_puzzle(tag: &str) -> Option<&'static Puzzle> {
+    PUZZLES.get(tag)
+}
+
+pub fn puzzles_at(location_tag: &str) -> Vec<&'static Puzzle> {
+    let mut puzzles: Vec<&'static Puzzle> = PUZZLES.values()
+        .filter(|puzzle| puzzle.location == location_tag)
+        .collect();
+    puzzles.sort_by(|a, b| a.tag.cmp(&b.tag));
+    puzzles
+}
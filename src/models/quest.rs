@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use lazy_static::lazy_static;
+use crate::models::object::Faction;
+use crate::models::puzzle::find_puzzle;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Objective {
+    Defeat(String),     // Derrotar al grupo de NPCs con este tag
+    Fetch(String),      // Llevar el objeto a quien encargó la misión
+    Reach(String),      // Llegar a la ubicación
+    Puzzle(String),     // Resolver el enigma
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reward {
+    Gold(u32),
+    Item(String),
+    Experience(u32),    // Encuentros superados que suma al grupo
+    Reputation(Faction, i32),
+}
+
+#[derive(Debug, Clone)]
+pub struct Quest {
+    pub tag: String,
+    pub title: String,
+    pub description: String,
+    pub giver: String,              // Tag del NPC que encarga la misión
+    pub objectives: Vec<Objective>,
+    pub rewards: Vec<Reward>,
+}
+
+impl Quest {
+    pub fn new(tag: &str, title: &str, description: &str, giver: &str) -> Self {
+        Self {
+            tag: tag.to_string(),
+            title: title.to_string(),
+            description: description.to_string(),
+            giver: giver.to_string(),
+            objectives: Vec::new(),
+            rewards: Vec::new(),
+        }
+    }
+
+    pub fn with_objective(mut self, objective: Objective) -> Self {
+        self.objectives.push(objective);
+        self
+    }
+
+    pub fn with_reward(mut self, reward: Reward) -> Self {
+        self.rewards.push(reward);
+        self
+    }
+}
+
+impl Objective {
+    pub fn describe(&self) -> String {
+        match self {
+            Objective::Defeat(npc_tag) => format!("Derrotar a los {}", npc_tag),
+            Objective::Fetch(item_tag) => format!("Entregar {}", item_tag),
+            Objective::Reach(location_tag) => format!("Llegar a {}", location_tag),
+            Objective::Puzzle(puzzle_tag) => match find_puzzle(puzzle_tag) {
+                Some(puzzle) => format!("Resolver {}", puzzle.title.to_lowercase()),
+                None => format!("Resolver {}", puzzle_tag),
+            },
+        }
+    }
+}
+
+impl Reward {
+    pub fn describe(&self) -> String {
+        match self {
+            Reward::Gold(amount) => format!("{} de oro", amount),
+            Reward::Item(item_tag) => item_tag.clone(),
+            Reward::Experience(amount) => format!("{} XP", amount),
+            Reward::Reputation(faction, amount) => format!("{:+} de reputación con {}", amount, faction),
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref QUESTS: HashMap<String, Quest> = {
+        let mut m = HashMap::new();
+
+        m.insert("goblins_bosque".to_string(),
+            Quest::new("goblins_bosque", "Limpiar el bosque", "La guardia de Woodspring quiere librarse de los goblins que acechan en el bosque.", "guardia")
+                .with_objective(Objective::Defeat("goblins".to_string()))
+                .with_reward(Reward::Gold(15))
+                .with_reward(Reward::Experience(1))
+                .with_reward(Reward::Reputation(Faction::Village, 2))
+        );
+
+        m.insert("secreto_cueva".to_string(),
+            Quest::new("secreto_cueva", "El secreto de la cueva", "La guardia quiere saber qué se esconde tras la puerta de piedra bajo la cueva.", "guardia")
+                .with_objective(Objective::Puzzle("acertijo".to_string()))
+                .with_objective(Objective::Reach("camara".to_string()))
+                .with_reward(Reward::Item("escudo".to_string()))
+                .with_reward(Reward::Experience(2))
+                .with_reward(Reward::Reputation(Faction::Village, 1))
+        );
+
+        m.insert("libro_nigromante".to_string(),
+            Quest::new("libro_nigromante", "El libro del nigromante", "El comerciante colecciona libros raros y pagará bien por uno de la biblioteca perdida.", "comerciante")
+                .with_objective(Objective::Fetch("libro".to_string()))
+                .with_reward(Reward::Gold(40))
+                .with_reward(Reward::Reputation(Faction::Village, 1))
+        );
+
+        m
+    };
+}
+
+pub fn find_quest(tag: &str) -> Option<&'static Quest> {
+    QUESTS.get(tag)
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use lazy_static::lazy_static;
+use crate::models::object::Attitude;
+
+#[derive(Debug, Clone)]
+pub struct Shop {
+    pub npc_tag: String,          // Tag del NPC que atiende la tienda
+    pub stock: Vec<(String, u32)>, // Tag del objeto y cantidad cuando la tienda está surtida
+    pub restock_turns: u32,       // Turnos que tarda en reponer la mercancía
+}
+
+impl Shop {
+    pub fn new(npc_tag: &str, restock_turns: u32) -> Self {
+        Self {
+            npc_tag: npc_tag.to_string(),
+            stock: Vec::new(),
+            restock_turns,
+        }
+    }
+
+    pub fn with_item(mut self, item_tag: &str, quantity: u32) -> Self {
+        self.stock.push((item_tag.to_string(), quantity));
+        self
+    }
+
+    pub fn full_stock(&self) -> HashMap<String, u32> {
+        self.stock.iter().cloned().collect()
+    }
+
+    // Precio al que el comerciante vende; None si se niega a comerciar
+    pub fn sell_price(value: u32, attitude: &Attitude) -> Option<u32> {
+        match attitude {
+            Attitude::Hostile => None,
+            Attitude::Neutral => Some(value.max(1)),
+            Attitude::Friendly => Some((value * 9 / 10).max(1)),
+        }
+    }
+
+    // Precio que el comerciante paga por un objeto del grupo
+    pub fn buy_price(value: u32, attitude: &Attitude) -> Option<u32> {
+        match attitude {
+            Attitude::Hostile => None,
+            Attitude::Neutral => Some(value / 2),
+            Attitude::Friendly => Some(value * 6 / 10),
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref SHOPS: HashMap<String, Shop> = {
+        let mut m = HashMap::new();
+
+        // El pequeño comercio de Woodspring
+        m.insert("comerciante".to_string(),
+            Shop::new("comerciante", 30)
+                .with_item("antorcha", 5)
+                .with_item("farol", 2)
+                .with_item("cuerda", 3)
+                .with_item("daga", 2)
+                .with_item("espada", 1)
+                .with_item("escudo", 2)
+                .with_item("armadura", 1)
+        );
+
+        m
+    };
+}
+
+pub fn find_shop(npc_tag: &str) -> Option<&'static Shop> {
+    SHOPS.get(npc_tag)
+}
@@ -0,0 +1,63 @@
+use std::fmt::{Display, Formatter};
+use crate::models::character::StatusEffect;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrapKind {
+    Pit,                // Foso: cae quien va en cabeza
+    Dart,               // Dardos: alcanzan a todo el grupo
+    PoisonNeedle,       // Aguja envenenada: pincha a quien abre, y envenena
+    CollapsingCeiling,  // Techo que se derrumba sobre todo el grupo
+}
+
+impl Display for TrapKind {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            TrapKind::Pit => write!(f, "un foso oculto bajo una losa"),
+            TrapKind::Dart => write!(f, "unos lanzadores de dardos"),
+            TrapKind::PoisonNeedle => write!(f, "una aguja envenenada"),
+            TrapKind::CollapsingCeiling => write!(f, "un techo a punto de derrumbarse"),
+        }
+    }
+}
+
+impl TrapKind {
+    pub fn damage(&self) -> u32 {
+        match self {
+            TrapKind::Pit => 2,
+            TrapKind::Dart | TrapKind::PoisonNeedle | TrapKind::CollapsingCeiling => 1,
+        }
+    }
+
+    // Estado que deja en quien la sufre, además del daño
+    pub fn status_effect(&self) -> Option<StatusEffect> {
+        match self {
+            TrapKind::PoisonNeedle => Some(StatusEffect::Poisoned(3)),
+            _ => None,
+        }
+    }
+
+    pub fn hits_whole_party(&self) -> bool {
+        matches!(self, TrapKind::Dart | TrapKind::CollapsingCeiling)
+    }
+
+    pub fn sprung_text(&self) -> &'static str {
+        match self {
+            TrapKind::Pit => "¡Una losa cede bajo los pies y se abre un foso!",
+            TrapKind::Dart => "¡Un chasquido, y una lluvia de dardos sale de las paredes!",
+            TrapKind::PoisonNeedle => "¡Una aguja salta de la cerradura!",
+            TrapKind::CollapsingCeiling => "¡El techo cruje y se desploma una lluvia de piedras!",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Trap {
+    pub kind: TrapKind,
+    pub difficulty: i32,    // Resultado mínimo (1d6 + bonus) para descubrirla o desarmarla
+}
+
+impl Trap {
+    pub fn new(kind: TrapKind, difficulty: i32) -> Self {
+        Self { kind, difficulty }
+    }
+}
@@ -0,0 +1,97 @@
+// Vocabulario del intérprete de órdenes: verbos con sus sinónimos, palabras de relleno y pronombres
+
+// Cada verbo, con sus sinónimos, imperativos y abreviaturas (sin tildes)
+const VERBS: &[(&str, &[&str])] = &[
+    ("ir", &["ir", "ve", "vete", "andar", "anda", "caminar", "camina", "entrar", "entra", "volver", "vuelve"]),
+    ("viajar", &["viajar", "viaja"]),
+    ("subir", &["subir", "sube"]),
+    ("bajar", &["bajar", "baja"]),
+    ("mirar", &["mirar", "mira", "m", "l", "examinar", "examina", "x", "observar", "observa"]),
+    ("ver", &["ver"]),
+    ("coger", &["coger", "coge", "tomar", "toma", "recoger", "recoge", "agarrar", "agarra"]),
+    ("soltar", &["soltar", "suelta", "dejar", "deja", "tirar", "tira"]),
+    ("dar", &["dar", "da", "entregar", "entrega"]),
+    ("inventario", &["inventario", "inv", "i"]),
+    ("buscar", &["buscar", "busca", "registrar", "registra"]),
+    ("abrir", &["abrir", "abre"]),
+    ("forzar", &["forzar", "fuerza"]),
+    ("desarmar", &["desarmar", "desarma"]),
+    ("encender", &["encender", "enciende", "prender", "prende"]),
+    ("apagar", &["apagar", "apaga"]),
+    ("conjurar", &["conjurar", "conjura"]),
+    ("responder", &["responder", "responde", "decir", "di"]),
+    ("pulsar", &["pulsar", "pulsa", "presionar", "presiona"]),
+    ("colocar", &["colocar", "coloca", "poner", "pon"]),
+    ("estado", &["estado"]),
+    ("atacar", &["atacar", "ataca", "luchar", "lucha", "pelear", "pelea", "matar", "mata"]),
+    ("continuar", &["continuar", "continua", "seguir", "sigue"]),
+    ("huir", &["huir", "huye", "escapar", "escapa"]),
+    ("hablar", &["hablar", "habla", "charlar", "charla"]),
+    ("sobornar", &["sobornar", "soborna"]),
+    ("intimidar", &["intimidar", "intimida"]),
+    ("persuadir", &["persuadir", "persuade", "convencer", "convence"]),
+    ("comprar", &["comprar", "compra"]),
+    ("vender", &["vender", "vende"]),
+    ("diario", &["diario", "misiones"]),
+    ("descansar", &["descansar", "descansa", "dormir", "duerme"]),
+    ("rumores", &["rumores"]),
+    ("contratar", &["contratar", "contrata"]),
+    ("ficha", &["ficha"]),
+    ("reordenar", &["reordenar", "reordena"]),
+    ("renombrar", &["renombrar", "renombra"]),
+    ("retirar", &["retirar", "retira"]),
+    ("reclutar", &["reclutar", "recluta"]),
+    ("despedir", &["despedir", "despide"]),
+    ("equipar", &["equipar", "equipa"]),
+    ("desequipar", &["desequipar", "desequipa"]),
+    ("deshacer", &["deshacer", "deshaz"]),
+    ("historial", &["historial"]),
+    ("mapa", &["mapa", "map"]),
+    ("salir", &["salir", "fin"]),
+    ("ayuda", &["ayuda", "?"]),
+];
+
+// Artículos, preposiciones y conjunciones que no cambian el sentido de la orden
+const FILLER_WORDS: &[&str] = &[
+    "el", "la", "los", "las", "un", "una", "unos", "unas",
+    "al", "del", "a", "de", "con", "en", "hacia", "por", "para", "y",
+];
+
+// Pronombres que se pegan al imperativo: "cógelo", "ábrela", "dáselo", "háblale"
+const ENCLITICS: &[&str] = &["selos", "selas", "selo", "sela", "los", "las", "lo", "la", "le"];
+
+// Minúsculas y sin tildes: "Cámara" y "camara" son la misma palabra
+pub fn normalize(word: &str) -> String {
+    word.trim().to_lowercase().chars()
+        .map(|c| match c {
+            'á' => 'a',
+            'é' => 'e',
+            'í' => 'i',
+            'ó' => 'o',
+            'ú' | 'ü' => 'u',
+            _ => c,
+        })
+        .collect()
+}
+
+pub fn is_filler(word: &str) -> bool {
+    FILLER_WORDS.contains(&normalize(word).as_str())
+}
+
+fn canonical_verb(word: &str) -> Option<&'static str> {
+    VERBS.iter()
+        .find(|(_, synonyms)| synonyms.contains(&word))
+        .map(|(verb, _)| *verb)
+}
+
+// Verbo al que corresponde la primera palabra, y si lleva un pronombre pegado ("cogelo" -> coger, sí)
+pub fn split_verb(word: &str) -> Option<(&'static str, bool)> {
+    let word = normalize(word);
+    if let Some(verb) = canonical_verb(&word) {
+        return Some((verb, false));
+    }
+    ENCLITICS.iter()
+        .filter_map(|enclitic| word.strip_suffix(enclitic))
+        .find_map(canonical_verb)
+        .map(|verb| (verb, true))
+}
@@ -1,65 +1,481 @@
-use crate::models::player::Player;
+mod grammar;
+pub mod shortcuts;
+
+use std::collections::HashSet;
+use crate::models::player::{Player, RealDiceRoller, Scope, dice_rolled};
+use crate::models::character::SocialAction;
+use crate::models::object::Direction;
+use crate::models::mode::{GameMode, CombatAction};
+use crate::models::puzzle::{PuzzleKind, find_puzzle};
+use shortcuts::Shortcuts;
 
 #[derive(Debug, Clone)]
 pub enum Command {
     Go(Option<String>),
+    Travel(String),
     Look,
-    Take(String),
+    Take(String, Option<String>),
     Drop(String),
-    Inventory,
+    Give(String, String),
+    Inventory(Option<String>),
     Search,
+    Open(String),
+    Force(String),
+    Answer(String),
+    Disarm(Option<String>),
+    LightUp(String),
+    Extinguish(String),
+    CastLight,
+    Press(String),
+    Place(String),
     Status,
     Attack(String),
-    Talk,
+    Combat(CombatAction),
+    LevelUp(String),                          // Personaje elegido para subir de nivel, por nombre o número
+    Talk(String),
+    Social(SocialAction, String, Option<u32>),
+    Choice(u32),
+    Buy(String, Option<String>),
+    Sell(String),
+    ListWares,
+    Journal,
+    Rest,
+    Rumors,
+    Hire(String),
+    Recruit(String),
+    Sheet(String),
+    Reorder(Vec<String>),
+    Rename(String, String),
+    Retire(String, String),
+    Dismiss(String),
     Equip(Vec<String>),
     Unequip(Vec<String>),
+    Map(Option<String>),                      // Con fichero, exporta el mapa en formato DOT
+    Undo,
+    History(Option<usize>),
+    Shortcut(String),                         // Gestión de alias y macros, tal como se escribió
+    Macro(Vec<String>),                       // Órdenes en que se ha expandido una macro, por orden
     Salir,
     Help,
+    Unknown(String),                          // Lo que no se ha entendido, con el motivo
+    Ambiguous(String, Vec<(String, String)>), // Orden a medias ("coger {}") y tag y descripción de los candidatos
+}
+
+// Fichero al que se exporta el mapa si no se dice otro
+const MAP_FILE: &str = "mapa.dot";
+
+// Órdenes que muestra `historial` si no se dice cuántas
+const HISTORY_LENGTH: usize = 10;
+
+impl Command {
+    // Las órdenes que solo consultan no cambian nada que haya que poder deshacer
+    fn changes_state(&self) -> bool {
+        !matches!(self,
+            Command::Look | Command::Inventory(_) | Command::Status | Command::Journal |
+            Command::Sheet(_) | Command::ListWares | Command::Map(_) | Command::Help | Command::Salir)
+    }
+
+    // Aquello sobre lo que actúa la orden, para poder referirse a ello después con un pronombre
+    fn referent(&self) -> Option<&str> {
+        let referent = match self {
+            Command::Take(item, _) | Command::Drop(item) | Command::Give(item, _) |
+            Command::LightUp(item) | Command::Extinguish(item) | Command::Place(item) |
+            Command::Buy(item, _) | Command::Sell(item) => item,
+            Command::Open(target) | Command::Force(target) => target,
+            Command::Attack(npc) | Command::Talk(npc) | Command::Social(_, npc, _) | Command::Recruit(npc) => npc,
+            _ => return None,
+        };
+        Some(referent.as_str()).filter(|referent| !referent.is_empty())
+    }
+}
+
+// Lee las palabras que siguen al verbo, reconociendo en cada hueco aquello que se nombra
+struct Arguments<'a> {
+    player: &'a Player,
+    verb: &'static str,
+    words: Vec<String>,
+    next: usize,
+    raw: Vec<String>,   // Las mismas palabras tal como se escribieron, con mayúsculas y artículos
+    implied: usize,     // Palabras añadidas por un pronombre, que no aparecen en `raw`
+}
+
+impl<'a> Arguments<'a> {
+    fn new(player: &'a Player, verb: &'static str, words: Vec<String>) -> Self {
+        Self { player, verb, words, next: 0, raw: Vec::new(), implied: 0 }
+    }
+
+    fn with_raw(mut self, raw: Vec<String>, implied: usize) -> Self {
+        self.raw = raw;
+        self.implied = implied;
+        self
+    }
+
+    // Siguiente nombre, buscado en el ámbito indicado. Se prueba primero con el nombre más largo
+    // ("cota de malla"), y si encaja con varias cosas se pregunta a cuál se refiere
+    fn referent(&mut self, scope: Scope) -> Result<Option<String>, Command> {
+        let rest = &self.words[self.next..];
+        if rest.is_empty() {
+            return Ok(None);
+        }
+        let referents = self.player.referents(scope);
+        for length in (1..=rest.len()).rev() {
+            let candidates = matching_referents(&rest[..length], &referents);
+            match candidates.len() {
+                0 => continue,
+                1 => {
+                    self.next += length;
+                    return Ok(Some(candidates[0].0.clone()));
+                },
+                _ => {
+                    let template: Vec<&str> = std::iter::once(self.verb)
+                        .chain(self.words[..self.next].iter().map(|word| word.as_str()))
+                        .chain(std::iter::once("{}"))
+                        .chain(rest[length..].iter().map(|word| word.as_str()))
+                        .collect();
+                    return Err(Command::Ambiguous(template.join(" "), candidates));
+                },
+            }
+        }
+        // Si no encaja con nada se pasa tal cual, y la orden dirá que no lo encuentra
+        self.next += 1;
+        Ok(Some(rest[0].clone()))
+    }
+
+    fn word(&mut self) -> Option<String> {
+        let word = self.words.get(self.next).cloned();
+        self.next += 1;
+        word
+    }
+
+    // El resto de la orden tal como se escribió: "renombrar legolas Lady Mara" -> "Lady Mara"
+    fn raw_rest(&mut self) -> String {
+        let mut consumed = self.next.saturating_sub(self.implied);
+        let start = self.raw.iter()
+            .position(|word| {
+                if consumed == 0 {
+                    return true;
+                }
+                if !grammar::is_filler(word) {
+                    consumed -= 1;
+                }
+                false
+            })
+            .unwrap_or(self.raw.len());
+        self.next = self.words.len();
+        self.raw[start..].join(" ")
+    }
+
+    fn rest(&mut self) -> Vec<String> {
+        let rest = self.words.get(self.next..).map(|rest| rest.to_vec()).unwrap_or_default();
+        self.next = self.words.len();
+        rest
+    }
 }
 
-pub fn parse_command(input: &str) -> Command {
+// Una palabra basta para nombrar algo si es su tag, una palabra de su descripción,
+// o el comienzo de su tag (de al menos tres letras)
+const MIN_PARTIAL_LENGTH: usize = 3;
+
+fn matching_referents(phrase: &[String], referents: &[(String, String)]) -> Vec<(String, String)> {
+    let phrase: Vec<String> = phrase.iter().map(|word| grammar::normalize(word)).collect();
+    let is_exact = |tag: &str| grammar::normalize(tag) == phrase.join("_");
+    let matches = |tag: &str, description: &str| {
+        let tag = grammar::normalize(tag);
+        let description = grammar::normalize(description);
+        let description_words: Vec<&str> = description.split(|c: char| !c.is_alphanumeric()).collect();
+        phrase.iter().all(|word| {
+            tag.split('_').any(|part| part == word || (word.len() >= MIN_PARTIAL_LENGTH && part.starts_with(word.as_str()))) ||
+                description_words.contains(&word.as_str())
+        })
+    };
+
+    // Si algo se llama exactamente así, no hay duda posible
+    let mut candidates: Vec<(String, String)> = referents.iter()
+        .filter(|(tag, _)| is_exact(tag))
+        .cloned()
+        .collect();
+    if candidates.is_empty() {
+        candidates = referents.iter()
+            .filter(|(tag, description)| matches(tag, description))
+            .cloned()
+            .collect();
+    }
+    let mut seen = HashSet::new();
+    candidates.retain(|(tag, _)| seen.insert(tag.clone()));
+    candidates
+}
+
+// Si quedaba pendiente elegir entre varias cosas y la respuesta nombra solo una, se completa la orden
+fn answer_to_disambiguation(player: &Player, input: &str) -> Option<String> {
+    let (template, candidates) = player.pending_disambiguation.as_ref()?;
+    let words: Vec<String> = input.split_whitespace()
+        .filter(|word| !grammar::is_filler(word))
+        .map(|word| word.to_string())
+        .collect();
+    if words.is_empty() {
+        return None;
+    }
+    let referents: Vec<(String, String)> = candidates.iter()
+        .map(|tag| (tag.clone(), String::new()))
+        .collect();
+    match matching_referents(&words, &referents).as_slice() {
+        [(tag, _)] => Some(template.replace("{}", tag)),
+        _ => None,
+    }
+}
+
+pub fn parse_command(player: &Player, input: &str) -> Command {
+    if Shortcuts::is_management(input) {
+        return Command::Shortcut(input.trim().to_string());
+    }
+    // Los alias y las macros se sustituyen antes de interpretar la orden
+    let mut lines = player.shortcuts.expand(input);
+    match lines.len() {
+        0 | 1 => parse_line(player, &lines.pop().unwrap_or_default()),
+        _ => Command::Macro(lines),
+    }
+}
+
+// Interpreta una sola orden, ya sin alias ni macros
+fn parse_line(player: &Player, input: &str) -> Command {
+    let original = input.trim();
+    let input = original.to_lowercase();
+    let (input, original) = match answer_to_disambiguation(player, &input) {
+        Some(completed) => (completed.clone(), completed),
+        None => (input, original.to_string()),
+    };
     let words: Vec<&str> = input.split_whitespace().collect();
-    match words.first() {
-        Some(&"1") | Some(&"2") | Some(&"3") | Some(&"4") => Command::Attack(words[0].to_string()),
-        Some(&"ir") => Command::Go(words.get(1).map(|&s| s.to_string())),
-        Some(&"mirar") => Command::Look,
-        Some(&"coger") => Command::Take(words.get(1).unwrap_or(&"").to_string()),
-        Some(&"soltar") => Command::Drop(words.get(1).unwrap_or(&"").to_string()),
-        Some(&"inventario") => Command::Inventory,
-        Some(&"buscar") => Command::Search,
-        Some(&"estado") => Command::Status,
-        Some(&"atacar") => Command::Attack(words.get(1).unwrap_or(&"").to_string()),
-        Some(&"hablar") => Command::Talk,
-        Some(&"equipar") => Command::Equip(words[1..].iter().map(|&s| s.to_string()).collect()),
-        Some(&"desequipar") => Command::Unequip(words[1..].iter().map(|&s| s.to_string()).collect()),
-        Some(&"salir") => Command::Salir,
-        Some(&"ayuda") => Command::Help,
-        _ => Command::Look,
+    let first = match words.first() {
+        Some(first) => *first,
+        None => return Command::Unknown("¿Qué quieres hacer?".to_string()),
+    };
+    if let Ok(choice) = first.parse::<u32>() {
+        return Command::Choice(choice);
+    }
+
+    // Una dirección sola ("n", "oeste") es ir hacia allí
+    let direction = grammar::normalize(first);
+    if words.len() == 1 && Direction::from_word(&direction).is_some() {
+        return Command::Go(Some(direction));
+    }
+
+    let (verb, pronoun) = match grammar::split_verb(first) {
+        Some(found) => found,
+        None => return match &player.mode {
+            // Ante un enigma, lo que no es una orden es el siguiente intento
+            GameMode::Puzzle(tag) => puzzle_attempt(player, tag, &input),
+            // Al subir de nivel, lo que no es una orden es el nombre del elegido
+            GameMode::LevelUp => Command::LevelUp(original),
+            _ => Command::Unknown(format!("No entiendo '{}'. Escribe 'ayuda' para ver las órdenes disponibles.", first)),
+        },
+    };
+    let mut args: Vec<String> = words[1..].iter()
+        .filter(|word| !grammar::is_filler(word))
+        .map(|word| word.to_string())
+        .collect();
+    // "cógelo": el pronombre se refiere a lo último que se nombró
+    if pronoun {
+        match &player.last_referent {
+            Some(referent) => args.insert(0, referent.clone()),
+            None => return Command::Unknown("No sé a qué te refieres.".to_string()),
+        }
+    }
+    // Las respuestas a acertijos se dejan tal cual, con sus artículos
+    let answer = words[1..].join(" ");
+
+    let raw = original.split_whitespace().skip(1).map(str::to_string).collect();
+    let args = Arguments::new(player, verb, args).with_raw(raw, usize::from(pronoun));
+
+    parse_verb(verb, args, answer).unwrap_or_else(|ambiguous| ambiguous)
+}
+
+fn puzzle_attempt(player: &Player, puzzle_tag: &str, input: &str) -> Command {
+    let words: Vec<String> = input.split_whitespace()
+        .filter(|word| !grammar::is_filler(word))
+        .map(|word| word.to_string())
+        .collect();
+    match find_puzzle(puzzle_tag).map(|puzzle| &puzzle.kind) {
+        Some(PuzzleKind::Riddle(_)) => Command::Answer(input.to_string()),
+        Some(PuzzleKind::Sequence(_)) => Command::Press(words.join(" ")),
+        Some(PuzzleKind::Placement(_)) => parse_verb("colocar", Arguments::new(player, "colocar", words), String::new())
+            .unwrap_or_else(|ambiguous| ambiguous),
+        None => Command::Unknown(format!("No entiendo '{}'.", input)),
     }
 }
 
+fn parse_verb(verb: &str, mut args: Arguments, answer: String) -> Result<Command, Command> {
+    let command = match verb {
+        "ir" => match args.words.first().map(|word| grammar::normalize(word)) {
+            Some(direction) if Direction::from_word(&direction).is_some() => Command::Go(Some(direction)),
+            _ => Command::Go(args.referent(Scope::Destinations)?),
+        },
+        "viajar" => Command::Travel(args.referent(Scope::Destinations)?.unwrap_or_default()),
+        "subir" => Command::Go(Some("arriba".to_string())),
+        "bajar" => Command::Go(Some("abajo".to_string())),
+        "mirar" => Command::Look,
+        "ver" => match args.word().map(|word| grammar::normalize(&word)) {
+            Some(word) if word == "mercancia" => Command::ListWares,
+            _ => Command::Look,
+        },
+        "coger" => Command::Take(args.referent(Scope::Room)?.unwrap_or_default(), args.referent(Scope::Party)?),
+        "soltar" => Command::Drop(args.referent(Scope::Carried)?.unwrap_or_default()),
+        "dar" => Command::Give(args.referent(Scope::Carried)?.unwrap_or_default(), args.referent(Scope::Party)?.unwrap_or_default()),
+        "inventario" => Command::Inventory(args.referent(Scope::Party)?),
+        "buscar" => Command::Search,
+        "abrir" => Command::Open(args.referent(Scope::Fixtures)?.unwrap_or_default()),
+        "forzar" => Command::Force(args.referent(Scope::Fixtures)?.unwrap_or_default()),
+        "desarmar" => Command::Disarm(args.referent(Scope::Fixtures)?),
+        "encender" => Command::LightUp(args.referent(Scope::Carried)?.unwrap_or_default()),
+        "apagar" => Command::Extinguish(args.referent(Scope::Carried)?.unwrap_or_default()),
+        "conjurar" => match args.word().map(|word| grammar::normalize(&word)) {
+            Some(word) if word == "luz" => Command::CastLight,
+            _ => Command::Unknown("Uso: conjurar luz".to_string()),
+        },
+        "responder" => Command::Answer(answer),
+        "pulsar" => Command::Press(args.rest().join(" ")),
+        "colocar" => Command::Place(args.referent(Scope::Carried)?.unwrap_or_default()),
+        "estado" => Command::Status,
+        "atacar" => Command::Attack(args.referent(Scope::Npcs)?.unwrap_or_default()),
+        "continuar" => Command::Combat(CombatAction::Fight),
+        "huir" => Command::Combat(CombatAction::Flee),
+        "hablar" => Command::Talk(args.referent(Scope::Npcs)?.unwrap_or_default()),
+        "sobornar" => Command::Social(SocialAction::Bribe, args.referent(Scope::Npcs)?.unwrap_or_default(), args.word().and_then(|offer| offer.parse().ok())),
+        "intimidar" => Command::Social(SocialAction::Intimidate, args.referent(Scope::Npcs)?.unwrap_or_default(), None),
+        "persuadir" => Command::Social(SocialAction::Persuade, args.referent(Scope::Npcs)?.unwrap_or_default(), None),
+        "comprar" => Command::Buy(args.referent(Scope::Wares)?.unwrap_or_default(), args.referent(Scope::Party)?),
+        "vender" => Command::Sell(args.referent(Scope::Carried)?.unwrap_or_default()),
+        "diario" => Command::Journal,
+        "descansar" => Command::Rest,
+        "rumores" => Command::Rumors,
+        "contratar" => Command::Hire(args.rest().join(" ")),
+        "ficha" => Command::Sheet(args.referent(Scope::Party)?.unwrap_or_default()),
+        "reordenar" => {
+            let mut names = Vec::new();
+            while let Some(name) = args.referent(Scope::Party)? {
+                names.push(name);
+            }
+            Command::Reorder(names)
+        },
+        "renombrar" => Command::Rename(args.referent(Scope::Party)?.unwrap_or_default(), args.raw_rest()),
+        "retirar" => Command::Retire(args.referent(Scope::Party)?.unwrap_or_default(), args.rest().join(" ")),
+        "reclutar" => Command::Recruit(args.referent(Scope::Npcs)?.unwrap_or_default()),
+        "despedir" => Command::Dismiss(args.referent(Scope::Party)?.unwrap_or_default()),
+        "equipar" => Command::Equip(args.rest()),
+        "desequipar" => Command::Unequip(args.rest()),
+        "deshacer" => Command::Undo,
+        "mapa" => match args.word().map(|word| grammar::normalize(&word)) {
+            Some(word) if word == "dot" => Command::Map(Some(args.word().unwrap_or_else(|| MAP_FILE.to_string()))),
+            _ => Command::Map(None),
+        },
+        "historial" => Command::History(args.word().and_then(|count| count.parse().ok())),
+        "salir" => Command::Salir,
+        "ayuda" => Command::Help,
+        _ => Command::Unknown(format!("No entiendo '{}'.", verb)),
+    };
+    Ok(command)
+}
+
 pub fn execute_command(player: &mut Player, command: Command) -> String {
-    // Si estamos en combate y el comando es un número, tratarlo como una acción de combate
-    if player.current_combat_enemies.is_some() {
-        if let Command::Attack(target) = &command {
-            if let Ok(choice) = target.parse::<u32>() {
-                if choice >= 1 && choice <= 4 {
-                    player.execute_attack(&target);
+    // Lo que no se ha entendido no gasta turno
+    match &command {
+        Command::Unknown(reason) => return reason.clone(),
+        Command::Ambiguous(template, candidates) => {
+            let mut question = String::from("¿A cuál te refieres?");
+            for (tag, description) in candidates {
+                question.push_str(&format!("\n- {} [{}]", description, tag));
+            }
+            player.pending_disambiguation = Some((template.clone(), candidates.iter().map(|(tag, _)| tag.clone()).collect()));
+            return question;
+        },
+        Command::Undo => {
+            player.execute_undo();
+            return "".to_string();
+        },
+        Command::History(count) => {
+            player.execute_history(count.unwrap_or(HISTORY_LENGTH));
+            return "".to_string();
+        },
+        // Los atajos no forman parte de la partida: ni gastan turno ni se deshacen
+        Command::Shortcut(line) => return player.shortcuts.handle(line).unwrap_or_default(),
+        // Cada paso de una macro es una orden más, con su turno y su punto para deshacer
+        Command::Macro(lines) => {
+            for line in lines {
+                println!("> {}", line);
+                let response = execute_command(player, parse_line(player, line));
+                if !response.is_empty() {
+                    println!("{}", response);
+                }
+                if player.is_party_defeated() {
+                    break;
                 }
             }
-            // Si estamos en combate, ignorar otros comandos excepto los de combate
+            return "".to_string();
+        },
+        // Consultar el mapa no gasta un turno
+        Command::Map(path) => {
+            match path {
+                Some(path) => player.execute_map_export(path),
+                None => player.execute_map(),
+            };
+            return "".to_string();
+        },
+        _ => player.pending_disambiguation = None,
+    }
+    if let Some(referent) = command.referent() {
+        player.last_referent = Some(referent.to_string());
+    }
+
+    // En combate los números son las acciones del menú, y solo se puede luchar, huir o consultar
+    let command = match (&player.mode, command) {
+        (GameMode::Combat(_), Command::Choice(choice)) => match CombatAction::from_choice(choice) {
+            Some(action) => Command::Combat(action),
+            None => return "Usa los números 1-4 para las acciones de combate.".to_string(),
+        },
+        (GameMode::Combat(_), Command::Attack(_)) => Command::Combat(CombatAction::Fight),
+        (GameMode::Combat(_), command) if command.changes_state() && !matches!(command, Command::Combat(_)) => {
             return "No puedes hacer eso durante el combate. Usa los números 1-4 para las acciones de combate.".to_string();
-        }
+        },
+        // Hasta elegir quién sube de nivel, los números son los de la lista y no se puede hacer otra cosa
+        (GameMode::LevelUp, Command::Choice(choice)) => Command::LevelUp(choice.to_string()),
+        (GameMode::LevelUp, command) if command.changes_state() && !matches!(command, Command::LevelUp(_)) => {
+            return "Antes de seguir, elige qué personaje sube de nivel.".to_string();
+        },
+        (_, command) => command,
+    };
+
+    // Se guarda el estado para poder deshacer la orden, salvo que acabe dependiendo de los dados
+    if command.changes_state() {
+        player.save_undo_point();
+    }
+    let rolls = dice_rolled();
+
+    player.advance_turn();
+
+    // Cualquier otra orden que no sea elegir una respuesta da por terminada la conversación,
+    // y cualquiera que no sea un intento deja el enigma para más tarde
+    if !matches!(command, Command::Choice(_)) {
+        player.end_dialogue();
+    }
+    if !matches!(command, Command::Answer(_) | Command::Press(_) | Command::Place(_)) {
+        player.end_puzzle();
     }
 
-    match command {
-        Command::Go(location) => player.execute_go(location.as_deref()),
+    let response = match command {
+        Command::Go(location) => player.execute_go(location.as_deref(), &mut RealDiceRoller),
+        Command::Travel(location) => {
+            if location.is_empty() {
+                "Uso: viajar [lugar]".to_string()
+            } else {
+                player.execute_travel(&location, &mut RealDiceRoller);
+                "".to_string()
+            }
+        },
         Command::Look => {
             player.execute_look();
             "".to_string()
         } ,
-        Command::Take(item) => {
-            if player.execute_take(&item) {
+        Command::Take(item, character) => {
+            if player.execute_take(&item, character.as_deref()) {
                 "".to_string()
             } else {
                 "No puedes coger ese objeto.".to_string()
@@ -72,26 +488,215 @@ pub fn execute_command(player: &mut Player, command: Command) -> String {
                 "No puedes soltar ese objeto.".to_string()
             }
         },
-        Command::Inventory => {
-            player.execute_inventory();
+        Command::Give(item, character) => {
+            if item.is_empty() || character.is_empty() {
+                "Uso: dar [objeto] [personaje]".to_string()
+            } else if player.execute_give(&item, &character) {
+                "".to_string()
+            } else {
+                "No puedes dar ese objeto.".to_string()
+            }
+        },
+        Command::Inventory(character) => {
+            player.execute_inventory(character.as_deref());
             "".to_string()
         },
         Command::Search => {
-            player.execute_search();
+            player.execute_search(&mut RealDiceRoller);
+            "".to_string()
+        },
+        Command::Open(target) => {
+            if target.is_empty() {
+                "Uso: abrir [puerta|pasaje|cofre]".to_string()
+            } else {
+                player.execute_open(&target, &mut RealDiceRoller);
+                "".to_string()
+            }
+        },
+        Command::Force(target) => {
+            if target.is_empty() {
+                "Uso: forzar [puerta|pasaje|cofre]".to_string()
+            } else {
+                player.execute_force(&target, &mut RealDiceRoller);
+                "".to_string()
+            }
+        },
+        Command::Disarm(target) => {
+            player.execute_disarm(target.as_deref(), &mut RealDiceRoller);
             "".to_string()
         },
+        Command::LightUp(item) => {
+            if item.is_empty() {
+                "Uso: encender [objeto]".to_string()
+            } else {
+                player.execute_light(&item);
+                "".to_string()
+            }
+        },
+        Command::Extinguish(item) => {
+            if item.is_empty() {
+                "Uso: apagar [objeto]".to_string()
+            } else {
+                player.execute_extinguish(&item);
+                "".to_string()
+            }
+        },
+        Command::CastLight => {
+            player.execute_cast_light();
+            "".to_string()
+        },
+        Command::Answer(answer) => {
+            if answer.is_empty() {
+                "Uso: responder [respuesta]".to_string()
+            } else {
+                player.execute_answer(&answer);
+                "".to_string()
+            }
+        },
+        Command::Press(sequence) => {
+            if sequence.is_empty() {
+                "Uso: pulsar [símbolo]...".to_string()
+            } else {
+                player.execute_press(&sequence);
+                "".to_string()
+            }
+        },
+        Command::Place(item) => {
+            if item.is_empty() {
+                "Uso: colocar [objeto]".to_string()
+            } else {
+                player.execute_place(&item);
+                "".to_string()
+            }
+        },
         Command::Status => {
             player.execute_status();
             "".to_string()
         },
-        Command::Attack(target) => { 
-            player.execute_attack(&target);
+        Command::Attack(target) => {
+            player.execute_attack(&target, &mut RealDiceRoller);
+            "".to_string()
+        },
+        Command::Combat(action) => {
+            player.execute_combat_action(action, &mut RealDiceRoller);
+            "".to_string()
+        },
+        Command::LevelUp(choice) => {
+            player.execute_level_up(&choice, &mut RealDiceRoller);
+            "".to_string()
+        },
+        Command::Talk(npc) => {
+            player.execute_talk(&npc);
             "".to_string()
         },
-        Command::Talk => {
-            println!("¿Con quién quieres hablar?");
+        Command::Social(action, npc, offer) => {
+            if npc.is_empty() {
+                format!("Uso: {} [npc]", action)
+            } else {
+                player.execute_social(action, &npc, offer, &mut RealDiceRoller);
+                "".to_string()
+            }
+        },
+        Command::Choice(choice) => {
+            player.execute_choice(choice);
+            "".to_string()
+        },
+        Command::Buy(item, character) => {
+            if player.execute_buy(&item, character.as_deref()) {
+                "".to_string()
+            } else {
+                "No puedes comprar ese objeto.".to_string()
+            }
+        },
+        Command::Sell(item) => {
+            if player.execute_sell(&item) {
+                "".to_string()
+            } else {
+                "No puedes vender ese objeto.".to_string()
+            }
+        },
+        Command::ListWares => {
+            player.execute_list_wares();
             "".to_string()
         },
+        Command::Journal => {
+            player.execute_journal();
+            "".to_string()
+        },
+        Command::Rest => {
+            if player.execute_rest() {
+                "".to_string()
+            } else {
+                "No podéis descansar aquí.".to_string()
+            }
+        },
+        Command::Rumors => {
+            player.execute_rumors();
+            "".to_string()
+        },
+        Command::Hire(args) => {
+            if args.is_empty() {
+                "Uso: contratar [nombre] [clase]".to_string()
+            } else if player.execute_hire(&args) {
+                "".to_string()
+            } else {
+                "No puedes contratar a nadie.".to_string()
+            }
+        },
+        Command::Sheet(name) => {
+            if name.is_empty() {
+                "Uso: ficha [personaje]".to_string()
+            } else {
+                player.execute_sheet(&name);
+                "".to_string()
+            }
+        },
+        Command::Reorder(names) => {
+            let names: Vec<&str> = names.iter().map(|s| s.as_str()).collect();
+            if names.is_empty() {
+                "Uso: reordenar [personaje] [personaje]...".to_string()
+            } else if player.execute_reorder(&names) {
+                "".to_string()
+            } else {
+                "No se ha cambiado el orden de marcha.".to_string()
+            }
+        },
+        Command::Rename(name, new_name) => {
+            if name.is_empty() || new_name.is_empty() {
+                "Uso: renombrar [personaje] [nuevo nombre]".to_string()
+            } else if player.execute_rename(&name, &new_name) {
+                "".to_string()
+            } else {
+                "No puedes cambiar ese nombre.".to_string()
+            }
+        },
+        Command::Retire(name, replacement) => {
+            if name.is_empty() || replacement.is_empty() {
+                "Uso: retirar [personaje] [nuevo nombre] [clase]".to_string()
+            } else if player.execute_retire(&name, &replacement) {
+                "".to_string()
+            } else {
+                "No puedes retirar a ese personaje.".to_string()
+            }
+        },
+        Command::Recruit(npc) => {
+            if npc.is_empty() {
+                "Uso: reclutar [npc]".to_string()
+            } else if player.execute_recruit(&npc) {
+                "".to_string()
+            } else {
+                "No puedes reclutar a ese NPC.".to_string()
+            }
+        },
+        Command::Dismiss(name) => {
+            if name.is_empty() {
+                "Uso: despedir [personaje]".to_string()
+            } else if player.execute_dismiss(&name) {
+                "".to_string()
+            } else {
+                "No puedes despedir a ese personaje.".to_string()
+            }
+        },
         Command::Equip(args) => {
             let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
             if player.execute_equip(&args) {
@@ -116,19 +721,277 @@ pub fn execute_command(player: &mut Player, command: Command) -> String {
         Command::Help => {
             let mut help = String::from("Comandos disponibles:\n");
             help.push_str("  mirar - Observar la ubicación actual\n");
-            help.push_str("  ir [lugar] - Ir a una ubicación\n");
-            help.push_str("  coger [objeto] - Recoger un objeto\n");
+            help.push_str("  ir [lugar|dirección] - Ir a una ubicación (también n, s, e, o, arriba, abajo)\n");
+            help.push_str("  viajar [lugar] - Ir por el camino más corto a un lugar en el que ya habéis estado\n");
+            help.push_str("  coger [objeto] [personaje] - Recoger un objeto\n");
             help.push_str("  soltar [objeto] - Soltar un objeto\n");
-            help.push_str("  inventario - Ver tu inventario\n");
-            help.push_str("  buscar - Buscar objetos ocultos\n");
+            help.push_str("  dar [objeto] [personaje] - Pasar un objeto a otro personaje\n");
+            help.push_str("  inventario [personaje] - Ver el inventario del grupo o de un personaje\n");
+            help.push_str("  buscar - Buscar objetos ocultos y trampas\n");
+            help.push_str("  abrir [lugar|cofre] - Abrir una cerradura con la llave o con ganzúas\n");
+            help.push_str("  forzar [lugar|cofre] - Echar abajo una cerradura a golpes (hace ruido)\n");
+            help.push_str("  desarmar [lugar|cofre] - Desarmar una trampa descubierta (pícaro con ganzúas)\n");
+            help.push_str("  encender [objeto] - Encender una antorcha o un farol\n");
+            help.push_str("  apagar [objeto] - Apagar una luz para ahorrar combustible\n");
+            help.push_str("  conjurar luz - Gastar un hechizo para alumbrar al grupo durante un rato\n");
+            help.push_str("  responder [respuesta] - Responder al acertijo del lugar\n");
+            help.push_str("  pulsar [símbolo]... - Pulsar símbolos o palancas en el orden indicado\n");
+            help.push_str("  colocar [objeto] - Colocar un objeto donde lo pide un enigma\n");
             help.push_str("  estado - Ver el estado del grupo\n");
-            help.push_str("  atacar - Atacar a un enemigo\n");
+            help.push_str("  atacar [npc] - Atacar a los enemigos o a un NPC concreto; cada orden es una ronda de combate\n");
+            help.push_str("  1-4, continuar, huir - En combate: seguir luchando, huir, usar un objeto o ver el estado\n");
+            help.push_str("  [personaje] o su número - Tras 10 encuentros superados: elegir quién sube de nivel\n");
             help.push_str("  hablar [npc] - Hablar con un NPC\n");
-            help.push_str("  equipar [personaje] [tipo] - Equipar un objeto\n");
+            help.push_str("  [número] - Elegir una respuesta en una conversación\n");
+            help.push_str("  sobornar [npc] [oro] - Ofrecer oro a un NPC para ganarse su favor\n");
+            help.push_str("  intimidar [npc] - Asustar a un NPC para que huya\n");
+            help.push_str("  persuadir [npc] - Convencer a un NPC para que os trate mejor\n");
+            help.push_str("  ver mercancía - Ver lo que vende el comerciante\n");
+            help.push_str("  comprar [objeto] [personaje] - Comprar un objeto al comerciante\n");
+            help.push_str("  vender [objeto] - Vender un objeto al comerciante\n");
+            help.push_str("  diario - Ver las misiones activas y completadas\n");
+            help.push_str("  descansar - Pasar la noche en la posada y recuperarse\n");
+            help.push_str("  rumores - Escuchar los rumores de la posada\n");
+            help.push_str("  contratar [nombre] [clase] - Contratar a un sustituto para un personaje caído\n");
+            help.push_str("  ficha [personaje] - Ver la ficha completa de un personaje\n");
+            help.push_str("  reordenar [personaje]... - Cambiar el orden de marcha del grupo\n");
+            help.push_str("  renombrar [personaje] [nuevo nombre] - Cambiar el nombre de un personaje\n");
+            help.push_str("  retirar [personaje] [nuevo nombre] [clase] - Retirar a un personaje en la posada y sustituirlo\n");
+            help.push_str("  reclutar [npc] - Invitar a un NPC amistoso a unirse al grupo\n");
+            help.push_str("  despedir [personaje] - Despedir a un compañero reclutado\n");
+            help.push_str("  equipar [personaje] [tipo|objeto] - Equipar un objeto\n");
             help.push_str("  desequipar [personaje] [tipo] - Desequipar un objeto\n");
+            help.push_str("  deshacer - Deshacer la última orden (no lo que ha dependido de los dados, ni en combate)\n");
+            help.push_str("  historial [n] - Ver las últimas órdenes de la sesión\n");
+            help.push_str("  mapa [dot [fichero]] - Ver el mapa de lo explorado, o exportarlo para Graphviz\n");
+            help.push_str("  alias [nombre] = [orden] - Crear un atajo, p. ej. 'alias b = buscar' ('alias' los lista, 'alias borrar [nombre]' lo quita)\n");
+            help.push_str("  macro [nombre] = [orden]; [orden]... - Encadenar varias órdenes bajo un nombre ('macro' las lista, 'macro borrar [nombre]' la quita)\n");
             help.push_str("  salir - Salir del juego\n");
+            help.push_str("\nPuedes escribir con naturalidad: 'coge la antorcha', 'ir al bosque', 'i' para el inventario,\n");
+            help.push_str("'cógelo' para lo último que nombraste, o solo el principio de un nombre ('ir al bos').\n");
             help
         },
         _ => "Comando no válido.".to_string(),
+    };
+
+    player.update_quests();
+    if dice_rolled() != rolls {
+        player.forget_undo_points();
+    }
+    response
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::character::{Character, Class};
+    use crate::models::object::Item;
+    use crate::models::mode::Combat;
+    use crate::models::player::MockDiceRoller;
+
+    fn player_at(location: &str) -> Player {
+        let mut characters = vec![Character::new(Class::Fighter), Character::new(Class::Rogue)];
+        let mut existing_names = HashSet::new();
+        characters[0].set_name("Aragorn".to_string(), &existing_names);
+        existing_names.insert("Aragorn".to_string());
+        characters[1].set_name("Legolas".to_string(), &existing_names);
+        let mut player = Player::new(characters);
+        player.current_location = Some(location.to_string());
+        player
+    }
+
+    fn run(player: &mut Player, line: &str) -> String {
+        let command = parse_command(player, line);
+        execute_command(player, command)
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_articles_prepositions_and_synonyms() {
+        let player = player_at("cueva");
+        assert!(matches!(parse_command(&player, "coge la antorcha"), Command::Take(item, None) if item == "antorcha"));
+        assert!(matches!(parse_command(&player, "ir al campo"), Command::Go(Some(place)) if place == "campo"));
+        assert!(matches!(parse_command(&player, "n"), Command::Go(Some(direction)) if direction == "n"));
+        assert!(matches!(parse_command(&player, "i"), Command::Inventory(None)));
+        assert!(matches!(parse_command(&player, "x"), Command::Look));
+        assert!(matches!(parse_command(&player, "equipar"), Command::Equip(args) if args.is_empty()));
+    }
+
+    #[test]
+    fn test_multi_word_names_and_partial_tags() {
+        let player = player_at("campo");
+        assert!(matches!(parse_command(&player, "coger la moneda de plata"), Command::Take(item, None) if item == "moneda"));
+        assert!(matches!(parse_command(&player, "coger cuerda para legolas"), Command::Take(item, Some(name)) if item == "cuerda" && name == "legolas"));
+        assert!(matches!(parse_command(&player, "coger cue leg"), Command::Take(item, Some(name)) if item == "cuerda" && name == "legolas"));
+        assert!(matches!(parse_command(&player, "hablar con el caz"), Command::Talk(npc) if npc == "cazador"));
+        // Lo que no está a la vista se pasa tal cual para que la orden diga que no lo encuentra
+        assert!(matches!(parse_command(&player, "coger espada"), Command::Take(item, None) if item == "espada"));
+    }
+
+    #[test]
+    fn test_ambiguous_names_ask_which_one() {
+        let mut player = player_at("cueva");
+        player.dropped_items.insert("cueva".to_string(), vec![
+            Item::new("espada_corta", "una espada corta"),
+            Item::new("espada_larga", "una espada larga"),
+        ]);
+
+        let command = parse_command(&player, "coger la espada");
+        assert!(matches!(&command, Command::Ambiguous(template, candidates) if template == "coger {}" && candidates.len() == 2));
+        let turns = player.turns;
+        let question = execute_command(&mut player, command);
+        assert!(question.contains("[espada_larga]"));
+        assert_eq!(player.turns, turns);
+
+        assert!(matches!(parse_command(&player, "la larga"), Command::Take(item, None) if item == "espada_larga"));
+        // Cualquier otra orden deja la pregunta sin efecto
+        execute_command(&mut player, Command::Look);
+        assert!(player.pending_disambiguation.is_none());
+        assert!(matches!(parse_command(&player, "larga"), Command::Unknown(_)));
+    }
+
+    #[test]
+    fn test_pronouns_refer_to_the_last_thing_named() {
+        let mut player = player_at("cueva");
+        assert!(matches!(parse_command(&player, "cógelo"), Command::Unknown(_)));
+
+        run(&mut player, "coger antorcha");
+        assert!(matches!(parse_command(&player, "suéltala"), Command::Drop(item) if item == "antorcha"));
+        assert!(matches!(parse_command(&player, "dáselo a legolas"), Command::Give(item, name) if item == "antorcha" && name == "legolas"));
+    }
+
+    #[test]
+    fn test_unknown_input_is_not_understood() {
+        let mut player = player_at("cueva");
+        let turns = player.turns;
+        let command = parse_command(&player, "bailar una jota");
+        assert!(matches!(&command, Command::Unknown(reason) if reason.starts_with("No entiendo 'bailar'")));
+        execute_command(&mut player, command);
+        assert_eq!(player.turns, turns);
+    }
+
+    #[test]
+    fn test_go_by_direction() {
+        let mut player = player_at("campo");
+        player.execute_go(Some("norte"), &mut MockDiceRoller { value: 6 });
+        assert_eq!(player.current_location.as_deref(), Some("pueblo"));
+        assert!(player.execute_go(Some("o"), &mut MockDiceRoller { value: 6 }).starts_with("No hay ninguna salida hacia el oeste"));
+        // La grieta no aparece por su dirección hasta descubrirla
+        player.current_location = Some("cueva".to_string());
+        assert!(player.execute_go(Some("s"), &mut MockDiceRoller { value: 6 }).starts_with("No hay ninguna salida hacia el sur"));
+    }
+
+    #[test]
+    fn test_undo_restores_the_state_before_the_last_command() {
+        let mut player = player_at("campo");
+        let turns = player.turns;
+        run(&mut player, "coger cuerda");
+        run(&mut player, "mirar");
+        assert!(player.has_item("cuerda"));
+
+        execute_command(&mut player, Command::Undo);
+        assert!(!player.has_item("cuerda"));
+        assert_eq!(player.turns, turns);
+        assert!(!player.execute_undo());
+    }
+
+    #[test]
+    fn test_dice_rolls_cannot_be_undone() {
+        let mut player = player_at("campo");
+        run(&mut player, "coger moneda");
+        run(&mut player, "buscar");
+        assert!(!player.execute_undo());
+        assert!(player.has_item("moneda"));
+
+        run(&mut player, "soltar moneda");
+        player.mode = GameMode::Combat(Combat::new(vec!["goblins".to_string()], 2));
+        assert!(!player.execute_undo());
+        player.mode = GameMode::Exploration;
+        assert!(player.execute_undo());
+        assert!(player.has_item("moneda"));
+    }
+
+    #[test]
+    fn test_history_survives_undo() {
+        let mut player = player_at("campo");
+        for line in ["coger cuerda", "soltar cuerda"] {
+            player.command_history.push(line.to_string());
+            run(&mut player, line);
+        }
+        player.command_history.push("deshacer".to_string());
+        run(&mut player, "deshacer");
+        assert!(player.has_item("cuerda"));
+        assert_eq!(player.command_history, vec!["coger cuerda", "soltar cuerda", "deshacer"]);
+        assert!(matches!(parse_command(&player, "historial 5"), Command::History(Some(5))));
+    }
+
+    #[test]
+    fn test_aliases_and_macros_are_expanded_when_parsing() {
+        let mut player = player_at("campo");
+        let turns = player.turns;
+        assert!(run(&mut player, "alias cs = coger").starts_with("Alias 'cs'"));
+        assert!(run(&mut player, "macro rutina = cs cuerda; mirar").starts_with("Macro 'rutina'"));
+        assert_eq!(player.turns, turns);
+
+        assert!(matches!(parse_command(&player, "CS cuerda"), Command::Take(item, None) if item == "cuerda"));
+        assert!(matches!(parse_command(&player, "rutina"), Command::Macro(lines) if lines == vec!["coger cuerda", "mirar"]));
+        run(&mut player, "rutina");
+        assert!(player.has_item("cuerda"));
+        assert_eq!(player.turns, turns + 2);
+
+        // Deshacer el último paso no borra los atajos
+        assert!(player.execute_undo());
+        assert!(matches!(parse_command(&player, "rutina"), Command::Macro(_)));
+    }
+
+    #[test]
+    fn test_combat_mode_only_accepts_combat_actions() {
+        let mut player = player_at("ruinas");
+        player.mode = GameMode::Combat(Combat::new(vec!["orcos".to_string()], 7));
+
+        assert!(run(&mut player, "coger bolsa").starts_with("No puedes hacer eso durante el combate"));
+        assert!(run(&mut player, "5").starts_with("Usa los números 1-4"));
+        assert!(matches!(parse_command(&player, "huye"), Command::Combat(CombatAction::Flee)));
+        run(&mut player, "2");
+        assert_eq!(player.mode, GameMode::Exploration);
+    }
+
+    #[test]
+    fn test_level_up_mode_waits_for_a_character() {
+        let mut player = player_at("campo");
+        player.mode = GameMode::LevelUp;
+        player.encounters_won = 10;
+        assert!(run(&mut player, "coger cuerda").starts_with("Antes de seguir"));
+        assert!(!player.has_item("cuerda"));
+        assert!(matches!(parse_command(&player, "Legolas"), Command::LevelUp(name) if name == "Legolas"));
+
+        run(&mut player, "legolas");
+        assert_eq!(player.mode, GameMode::Exploration);
+        assert_eq!(player.encounters_won, 0);
+    }
+
+    #[test]
+    fn test_puzzle_mode_takes_bare_attempts() {
+        let mut player = player_at("puerta");
+        run(&mut player, "responder fuego");
+        assert_eq!(player.mode, GameMode::Puzzle("acertijo".to_string()));
+
+        assert!(matches!(parse_command(&player, "la vela"), Command::Answer(answer) if answer == "la vela"));
+        run(&mut player, "la vela");
+        assert!(player.solved_puzzles.contains("acertijo"));
+        assert_eq!(player.mode, GameMode::Exploration);
+
+        // Cualquier otra orden deja el enigma para más tarde
+        run(&mut player, "pulsar ojo");
+        assert_eq!(player.mode, GameMode::Puzzle("simbolos".to_string()));
+        run(&mut player, "mirar");
+        assert_eq!(player.mode, GameMode::Exploration);
+        assert!(matches!(parse_command(&player, "sol"), Command::Unknown(_)));
+    }
+
+    #[test]
+    fn test_rename_keeps_the_whole_name_as_written() {
+        let mut player = player_at("posada");
+        run(&mut player, "renombrar a legolas Lady Mara");
+        let party: Vec<String> = player.referents(Scope::Party).into_iter().map(|(_, description)| description).collect();
+        assert!(party.iter().any(|description| description.starts_with("Lady Mara (")));
+    }
+}
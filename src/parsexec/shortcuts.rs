@@ -0,0 +1,267 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use super::grammar;
+use crate::models::object::Direction;
+
+// Fichero, en el directorio de la partida, donde se guardan los alias y las macros
+pub const CONFIG_FILE: &str = "aventura.cfg";
+
+// Límite de alias y macros anidados, para que una macro que se llama a sí misma no se expanda sin fin
+const MAX_EXPANSION_DEPTH: usize = 8;
+// Órdenes que puede lanzar de una vez una macro
+const MAX_MACRO_STEPS: usize = 50;
+
+// Atajos definidos por el jugador: alias de una palabra ("b" -> "buscar") y macros que
+// encadenan varias órdenes ("explorar" -> "mirar; buscar; buscar")
+#[derive(Debug, Clone, Default)]
+pub struct Shortcuts {
+    path: Option<PathBuf>,  // Sin fichero los atajos solo duran la partida
+    aliases: BTreeMap<String, String>,
+    macros: BTreeMap<String, Vec<String>>,
+}
+
+impl Shortcuts {
+    // El fichero tiene el mismo formato que las órdenes: una línea `alias ...` o `macro ...` por atajo
+    pub fn load(path: &str) -> Self {
+        let mut shortcuts = Self::default();
+        if let Ok(config) = fs::read_to_string(path) {
+            for line in config.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+                if let Err(reason) = shortcuts.define(line) {
+                    println!("{}: se ignora '{}': {}", path, line, reason);
+                }
+            }
+        }
+        shortcuts.path = Some(PathBuf::from(path));
+        shortcuts
+    }
+
+    // Las órdenes que hay que ejecutar, en orden, tras sustituir alias y macros
+    pub fn expand(&self, input: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        self.expand_into(input, 0, &mut lines);
+        lines.truncate(MAX_MACRO_STEPS);
+        lines
+    }
+
+    fn expand_into(&self, line: &str, depth: usize, lines: &mut Vec<String>) {
+        if lines.len() >= MAX_MACRO_STEPS {
+            return;
+        }
+        let line = line.trim();
+        let (first, rest) = line.split_once(' ').unwrap_or((line, ""));
+        if first.is_empty() {
+            return;
+        }
+        let first = first.to_lowercase();
+        if depth < MAX_EXPANSION_DEPTH {
+            if let Some(steps) = self.macros.get(&first) {
+                for step in steps {
+                    if lines.len() >= MAX_MACRO_STEPS {
+                        break;
+                    }
+                    self.expand_into(step, depth + 1, lines);
+                }
+                return;
+            }
+            // Lo que sigue al alias se añade a su expansión: "cs antorcha" -> "coger antorcha"
+            if let Some(expansion) = self.aliases.get(&first) {
+                self.expand_into(&format!("{} {}", expansion, rest), depth + 1, lines);
+                return;
+            }
+        }
+        lines.push(line.to_string());
+    }
+
+    // Si la línea es una de las órdenes que gestionan los atajos (`alias ...`, `macro ...`)
+    pub fn is_management(input: &str) -> bool {
+        let command = input.split_whitespace().next().unwrap_or("").to_lowercase();
+        command == "alias" || command == "macro"
+    }
+
+    // Órdenes para gestionar los atajos; cualquier otra cosa se deja pasar (None)
+    pub fn handle(&mut self, input: &str) -> Option<String> {
+        if !Self::is_management(input) {
+            return None;
+        }
+        let input = &input.to_lowercase();
+        let (command, rest) = input.trim().split_once(' ').unwrap_or((input.trim(), ""));
+        let rest = rest.trim();
+
+        let response = if rest.is_empty() {
+            self.list(command)
+        } else if let Some(name) = rest.strip_prefix("borrar ") {
+            self.remove(command, name.trim())
+        } else if rest.contains('=') {
+            match self.define(input) {
+                Ok(response) => match self.save() {
+                    Ok(()) => response,
+                    Err(reason) => format!("{}\nNo se pudo guardar en {}: {}", response, self.path_display(), reason),
+                },
+                Err(reason) => reason,
+            }
+        } else {
+            self.show(command, rest)
+        };
+        Some(response)
+    }
+
+    // Define un alias o una macro a partir de una línea `alias b = buscar` o `macro explorar = mirar; buscar`
+    fn define(&mut self, line: &str) -> Result<String, String> {
+        let (command, definition) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        let (name, expansion) = definition.split_once('=')
+            .ok_or_else(|| format!("Uso: {} [nombre] = [orden]", command))?;
+        let name = name.trim();
+        let expansion = expansion.trim();
+
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err("El nombre de un atajo debe ser una sola palabra.".to_string());
+        }
+        if name == "alias" || name == "macro" || grammar::split_verb(name).is_some() ||
+            Direction::from_word(name).is_some() || name.parse::<u32>().is_ok() {
+            return Err(format!("'{}' ya es una orden del juego y no se puede redefinir.", name));
+        }
+        if expansion.is_empty() {
+            return Err(format!("Falta la orden a la que equivale '{}'.", name));
+        }
+
+        // Un nombre solo puede ser un alias o una macro, no las dos cosas
+        match command {
+            "alias" => {
+                self.macros.remove(name);
+                self.aliases.insert(name.to_string(), expansion.to_string());
+                Ok(format!("Alias '{}' = '{}'.", name, expansion))
+            },
+            "macro" => {
+                let steps: Vec<String> = expansion.split(';')
+                    .map(str::trim)
+                    .filter(|step| !step.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                self.aliases.remove(name);
+                let response = format!("Macro '{}' = {}.", name, steps.join("; "));
+                self.macros.insert(name.to_string(), steps);
+                Ok(response)
+            },
+            _ => Err(format!("No se reconoce '{}'.", command)),
+        }
+    }
+
+    fn remove(&mut self, command: &str, name: &str) -> String {
+        let removed = match command {
+            "alias" => self.aliases.remove(name).is_some(),
+            _ => self.macros.remove(name).is_some(),
+        };
+        if !removed {
+            return format!("No existe ningún {} llamado '{}'.", command, name);
+        }
+        match self.save() {
+            Ok(()) => format!("Se ha borrado el {} '{}'.", command, name),
+            Err(reason) => format!("Se ha borrado el {} '{}', pero no se pudo guardar en {}: {}", command, name, self.path_display(), reason),
+        }
+    }
+
+    fn show(&self, command: &str, name: &str) -> String {
+        let definition = match command {
+            "alias" => self.aliases.get(name).cloned(),
+            _ => self.macros.get(name).map(|steps| steps.join("; ")),
+        };
+        match definition {
+            Some(definition) => format!("{} = {}", name, definition),
+            None => format!("No existe ningún {} llamado '{}'.", command, name),
+        }
+    }
+
+    fn list(&self, command: &str) -> String {
+        let definitions: Vec<String> = match command {
+            "alias" => self.aliases.iter()
+                .map(|(name, expansion)| format!("- {} = {}", name, expansion))
+                .collect(),
+            _ => self.macros.iter()
+                .map(|(name, steps)| format!("- {} = {}", name, steps.join("; ")))
+                .collect(),
+        };
+        if definitions.is_empty() {
+            format!("No hay ningún {} definido. Uso: {} [nombre] = [orden]", command, command)
+        } else {
+            format!("{}:\n{}", if command == "alias" { "Alias" } else { "Macros" }, definitions.join("\n"))
+        }
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let mut config = String::from("# Alias y macros de Aventura en Woodspring\n");
+        for (name, expansion) in &self.aliases {
+            config.push_str(&format!("alias {} = {}\n", name, expansion));
+        }
+        for (name, steps) in &self.macros {
+            config.push_str(&format!("macro {} = {}\n", name, steps.join("; ")));
+        }
+        fs::write(path, config)
+    }
+
+    fn path_display(&self) -> String {
+        self.path.as_ref().map(|path| path.display().to_string()).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aliases_and_macros_expand() {
+        let mut shortcuts = Shortcuts::default();
+        shortcuts.handle("alias b = buscar");
+        shortcuts.handle("alias cs = coger");
+        shortcuts.handle("macro explorar = mirar; b; b; cs antorcha");
+
+        assert_eq!(shortcuts.expand("b"), vec!["buscar"]);
+        assert_eq!(shortcuts.expand("cs la cuerda"), vec!["coger la cuerda"]);
+        assert_eq!(shortcuts.expand("explorar"), vec!["mirar", "buscar", "buscar", "coger antorcha"]);
+        assert_eq!(shortcuts.expand("ir al bosque"), vec!["ir al bosque"]);
+
+        // Una macro que se llama a sí misma no se expande sin fin
+        shortcuts.handle("macro bucle = bucle; bucle");
+        assert!(shortcuts.expand("bucle").len() <= MAX_MACRO_STEPS);
+    }
+
+    #[test]
+    fn test_wide_recursive_macro_stops_at_the_step_limit() {
+        // Diez llamadas por nivel y ocho niveles serían cien millones de órdenes sin el corte
+        let mut shortcuts = Shortcuts::default();
+        shortcuts.handle("macro a = a;a;a;a;a;a;a;a;a;a");
+        assert_eq!(shortcuts.expand("a").len(), MAX_MACRO_STEPS);
+    }
+
+    #[test]
+    fn test_builtin_commands_cannot_be_redefined() {
+        let mut shortcuts = Shortcuts::default();
+        assert!(shortcuts.handle("alias mirar = buscar").unwrap().contains("no se puede redefinir"));
+        assert!(shortcuts.handle("alias n = buscar").unwrap().contains("no se puede redefinir"));
+        assert_eq!(shortcuts.expand("mirar"), vec!["mirar"]);
+        assert_eq!(shortcuts.handle("ir al bosque"), None);
+    }
+
+    #[test]
+    fn test_shortcuts_are_saved_and_loaded() {
+        let path = std::env::temp_dir().join(format!("aventura-{}.cfg", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+
+        let mut shortcuts = Shortcuts::load(path);
+        shortcuts.handle("alias b = buscar");
+        shortcuts.handle("macro explorar = mirar; buscar");
+        shortcuts.handle("alias x2 = mirar");
+        shortcuts.handle("alias borrar x2");
+
+        let loaded = Shortcuts::load(path);
+        assert_eq!(loaded.expand("b"), vec!["buscar"]);
+        assert_eq!(loaded.expand("explorar"), vec!["mirar", "buscar"]);
+        assert_eq!(loaded.expand("x2"), vec!["x2"]);
+        let _ = fs::remove_file(path);
+    }
+}